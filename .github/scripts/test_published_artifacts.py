@@ -143,11 +143,12 @@ def main() -> None:
     target_dir = Path(os.environ.get("CARGO_TARGET_DIR", ROOT / "target")).resolve()
     archives = {
         name: target_dir / "package" / f"{name}-{versions[name]}.crate"
-        for name in ["candle-einops-macros", "candle-einops"]
+        for name in ["candle-einops-syntax", "candle-einops-macros", "candle-einops"]
     }
 
     with tempfile.TemporaryDirectory(prefix="candle-einops-artifacts-") as temporary:
         temporary_dir = Path(temporary)
+        syntax_dir = safe_unpack(archives["candle-einops-syntax"], temporary_dir / "syntax")
         macro_dir = safe_unpack(archives["candle-einops-macros"], temporary_dir / "macro")
         runtime_dir = safe_unpack(archives["candle-einops"], temporary_dir / "runtime")
 
@@ -155,18 +156,23 @@ def main() -> None:
         env["CARGO_TARGET_DIR"] = str(temporary_dir / "target")
         env["RUSTDOCFLAGS"] = "-D warnings"
 
-        print("Testing macro artifact first", flush=True)
-        test_artifact(macro_dir, env)
+        print("Testing syntax artifact first", flush=True)
+        test_artifact(syntax_dir, env)
 
         cargo_config = temporary_dir / ".cargo"
         cargo_config.mkdir()
+        syntax_path = json.dumps(str(syntax_dir))
         macro_path = json.dumps(str(macro_dir))
         (cargo_config / "config.toml").write_text(
             "[patch.crates-io]\n"
+            f"candle-einops-syntax = {{ path = {syntax_path} }}\n"
             f"candle-einops-macros = {{ path = {macro_path} }}\n",
             encoding="utf-8",
         )
 
+        print("Testing macro artifact against the unpacked syntax artifact", flush=True)
+        test_artifact(macro_dir, env)
+
         print("Testing runtime artifact against the unpacked macro artifact", flush=True)
         test_artifact(runtime_dir, env)
         print("Testing normal, renamed, and keyword downstream consumers", flush=True)
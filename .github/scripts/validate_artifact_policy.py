@@ -62,7 +62,7 @@ def main() -> int:
 
     packages = {
         package: package_paths(package, failures)
-        for package in ["candle-einops", "candle-einops-macros"]
+        for package in ["candle-einops", "candle-einops-macros", "candle-einops-syntax"]
     }
     for package, packaged in packages.items():
         leaked = sorted(
@@ -10,6 +10,7 @@ import tomllib
 ROOT = Path(__file__).resolve().parents[2]
 RUNTIME_MANIFEST = ROOT / "Cargo.toml"
 MACRO_MANIFEST = ROOT / "candle-einops-macros/Cargo.toml"
+SYNTAX_MANIFEST = ROOT / "candle-einops-syntax/Cargo.toml"
 
 
 def read(path: str) -> str:
@@ -19,6 +20,7 @@ def read(path: str) -> str:
 def main() -> int:
     runtime = tomllib.loads(RUNTIME_MANIFEST.read_text(encoding="utf-8"))
     macros = tomllib.loads(MACRO_MANIFEST.read_text(encoding="utf-8"))
+    syntax = tomllib.loads(SYNTAX_MANIFEST.read_text(encoding="utf-8"))
     readme = read("README.md")
     changelog = read("CHANGELOG.md")
     contract = read("docs/einsum-contract.md")
@@ -28,12 +30,19 @@ def main() -> int:
     artifact_runner = read(".github/scripts/test_published_artifacts.py")
     failures: list[str] = []
 
-    versions = {runtime["package"]["version"], macros["package"]["version"]}
+    versions = {
+        runtime["package"]["version"],
+        macros["package"]["version"],
+        syntax["package"]["version"],
+    }
     if versions != {"0.2.0"}:
-        failures.append("runtime and macro crates must both be versioned 0.2.0")
+        failures.append("runtime, macro, and syntax crates must all be versioned 0.2.0")
     macro_requirement = runtime["dependencies"]["candle-einops-macros"]["version"]
     if macro_requirement != "=0.2.0":
         failures.append("the private macro/runtime ABI requires an exact =0.2.0 dependency")
+    for manifest, name in [(runtime, "runtime"), (macros, "macro")]:
+        if manifest["dependencies"]["candle-einops-syntax"]["version"] != "=0.2.0":
+            failures.append(f"the {name} crate must share the grammar through an exact =0.2.0 dependency")
 
     forbidden_history = [
         "no einsum in the root",
@@ -103,28 +112,30 @@ def main() -> int:
 
     if "einsum" not in runtime["package"]["description"].lower():
         failures.append("runtime package description must advertise einsum support")
-    if macros["package"].get("documentation") != "https://docs.rs/candle-einops":
-        failures.append("macro package metadata must direct users to runtime documentation")
-
-    for license_name in ["LICENSE-APACHE", "LICENSE-MIT"]:
-        if not (ROOT / "candle-einops-macros" / license_name).is_file():
-            failures.append(f"the published macro crate must carry {license_name}")
-
-    package_list = subprocess.run(
-        ["cargo", "package", "--list", "--allow-dirty", "-p", "candle-einops-macros"],
-        cwd=ROOT,
-        text=True,
-        stdout=subprocess.PIPE,
-        stderr=subprocess.PIPE,
-        check=False,
-    )
-    if package_list.returncode != 0:
-        failures.append(f"could not inspect macro package: {package_list.stderr.strip()}")
-    else:
-        packaged = set(package_list.stdout.splitlines())
+    for manifest, name in [(macros, "macro"), (syntax, "syntax")]:
+        if manifest["package"].get("documentation") != "https://docs.rs/candle-einops":
+            failures.append(f"{name} package metadata must direct users to runtime documentation")
+
+    for package, name in [("candle-einops-macros", "macro"), ("candle-einops-syntax", "syntax")]:
         for license_name in ["LICENSE-APACHE", "LICENSE-MIT"]:
-            if license_name not in packaged:
-                failures.append(f"macro artifact must include {license_name}")
+            if not (ROOT / package / license_name).is_file():
+                failures.append(f"the published {name} crate must carry {license_name}")
+
+        package_list = subprocess.run(
+            ["cargo", "package", "--list", "--allow-dirty", "-p", package],
+            cwd=ROOT,
+            text=True,
+            stdout=subprocess.PIPE,
+            stderr=subprocess.PIPE,
+            check=False,
+        )
+        if package_list.returncode != 0:
+            failures.append(f"could not inspect {name} package: {package_list.stderr.strip()}")
+        else:
+            packaged = set(package_list.stdout.splitlines())
+            for license_name in ["LICENSE-APACHE", "LICENSE-MIT"]:
+                if license_name not in packaged:
+                    failures.append(f"{name} artifact must include {license_name}")
 
     if failures:
         print("Einsum release validation failed:", file=sys.stderr)
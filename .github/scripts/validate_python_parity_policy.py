@@ -95,7 +95,7 @@ def main() -> int:
     excludes = set(runtime_manifest["package"].get("exclude", []))
     if "parity/" not in excludes:
         failures.append("the runtime package must explicitly exclude parity/")
-    for package in ["candle-einops", "candle-einops-macros"]:
+    for package in ["candle-einops", "candle-einops-macros", "candle-einops-syntax"]:
         leaked = sorted(
             path
             for path in package_paths(package, failures)
@@ -1,20 +1,22 @@
 mod parse;
 #[cfg(test)]
 mod properties;
+mod shape;
 mod tokens;
 
+use candle_einops_syntax::einops;
 use proc_macro_crate::{FoundCrate, crate_name};
 use proc_macro2::{Ident, Span};
 use quote::quote;
-use syn::parse::ParseStream;
 
 use parse::{
-    Composition, Decomposition, Index, Operation, Shape, parse_composition_permute_repeat,
-    parse_decomposition, parse_reduce,
+    Composition, Decomposition, Index, Operation, Shape, Split, parse_composition_permute_repeat,
+    parse_decomposition, parse_reduce, parse_split,
 };
+pub use shape::einops_shape;
 use tokens::{
-    to_tokens_composition, to_tokens_decomposition, to_tokens_permute, to_tokens_reduce,
-    to_tokens_repeat,
+    to_tokens_arg_reduce, to_tokens_composition, to_tokens_decomposition, to_tokens_permute,
+    to_tokens_reduce, to_tokens_repeat, to_tokens_size_assertions, to_tokens_sizes,
 };
 
 pub fn einops(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
@@ -23,18 +25,32 @@ pub fn einops(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::Token
     Ok(code)
 }
 
+pub fn einops_with_sizes(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let parsed_expression = ParsedExpression {
+        return_sizes: true,
+        ..syn::parse2(input)?
+    };
+    let code = quote! { #parsed_expression };
+    Ok(code)
+}
+
 #[derive(Debug)]
 struct ParsedExpression {
     runtime_crate: syn::Path,
     candle_crate: syn::Path,
     tensor: syn::Ident,
     tensor_expression: proc_macro2::TokenStream,
+    // Binding for the reducer argument used by `reduce(...)` groups
+    reducer: Option<(syn::Ident, proc_macro2::TokenStream)>,
+    // Whether the named input axis lengths are returned with the tensor
+    return_sizes: bool,
     expression: Expression,
 }
 
 impl syn::parse::Parse for ParsedExpression {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let expression: Expression = input.parse::<syn::LitStr>()?.parse()?;
+        let pattern = input.parse::<syn::LitStr>()?;
+        let expression = Expression::parse(&pattern)?;
 
         input.parse::<syn::Token![,]>()?;
 
@@ -45,41 +61,75 @@ impl syn::parse::Parse for ParsedExpression {
             (tensor_ident, tensor_tokens)
         };
 
-        let runtime_crate = match crate_name("candle-einops") {
-            // Rustdoc reports the documented package as `Itself`, even though
-            // doctests compile as an external wrapper crate. The runtime crate
-            // provides this canonical self-alias for ordinary in-crate calls.
-            Ok(FoundCrate::Itself) => syn::parse_quote!(::candle_einops),
-            Ok(FoundCrate::Name(name)) => external_crate_path(&name)?,
-            Err(error) => {
-                return Err(syn::Error::new(
-                    Span::call_site(),
-                    format!("could not resolve the `candle-einops` runtime crate: {error}"),
-                ));
-            }
-        };
-
-        let candle_crate = match crate_name("candle-core") {
-            Ok(FoundCrate::Itself) => syn::parse_quote!(crate),
-            Ok(FoundCrate::Name(name)) => external_crate_path(&name)?,
-            Err(error) => {
-                return Err(syn::Error::new(
-                    Span::call_site(),
-                    format!("could not resolve the `candle-core` crate: {error}"),
-                ));
-            }
+        let reducer_expr = if input.peek(syn::Token![,]) {
+            input.parse::<syn::Token![,]>()?;
+            Some(input.parse::<syn::Expr>()?)
+        } else {
+            None
         };
+        let uses_reducer = expression
+            .reduce
+            .iter()
+            .any(|(_, operation)| matches!(operation, Operation::Custom));
+        if uses_reducer && reducer_expr.is_none() {
+            return Err(syn::Error::new(
+                pattern.span(),
+                "`reduce(...)` requires a reducer argument after the tensor",
+            ));
+        }
+        if let Some(expr) = reducer_expr.as_ref().filter(|_| !uses_reducer) {
+            return Err(syn::Error::new_spanned(
+                expr,
+                "A reducer argument requires a `reduce(...)` group in the expression",
+            ));
+        }
+
+        let runtime_crate = runtime_crate_path()?;
+        let candle_crate = candle_crate_path()?;
+
+        let reducer = reducer_expr.map(|expr| {
+            let reducer_ident = private_ident("reducer");
+            let reducer_tokens = quote!(let #reducer_ident: &dyn #runtime_crate::Reducer = &#expr;);
+            (reducer_ident, reducer_tokens)
+        });
 
         Ok(Self {
             runtime_crate,
             candle_crate,
             tensor: tensor_ident,
             tensor_expression: tensor_tokens,
+            reducer,
+            return_sizes: false,
             expression,
         })
     }
 }
 
+fn runtime_crate_path() -> syn::Result<syn::Path> {
+    match crate_name("candle-einops") {
+        // Rustdoc reports the documented package as `Itself`, even though
+        // doctests compile as an external wrapper crate. The runtime crate
+        // provides this canonical self-alias for ordinary in-crate calls.
+        Ok(FoundCrate::Itself) => Ok(syn::parse_quote!(::candle_einops)),
+        Ok(FoundCrate::Name(name)) => external_crate_path(&name),
+        Err(error) => Err(syn::Error::new(
+            Span::call_site(),
+            format!("could not resolve the `candle-einops` runtime crate: {error}"),
+        )),
+    }
+}
+
+fn candle_crate_path() -> syn::Result<syn::Path> {
+    match crate_name("candle-core") {
+        Ok(FoundCrate::Itself) => Ok(syn::parse_quote!(crate)),
+        Ok(FoundCrate::Name(name)) => external_crate_path(&name),
+        Err(error) => Err(syn::Error::new(
+            Span::call_site(),
+            format!("could not resolve the `candle-core` crate: {error}"),
+        )),
+    }
+}
+
 fn private_ident(name: &str) -> Ident {
     Ident::new(&format!("__candle_einops_{name}"), Span::mixed_site())
 }
@@ -104,32 +154,49 @@ struct Expression {
     decomposition: Vec<Decomposition>,
     // Step 2, Reducing dimensions with operations like min, max, ..
     reduce: Vec<(Index, Operation)>,
+    // Step 2, Or replacing dimensions with the index from argmax or argmin
+    index_reduce: Vec<(Index, Operation)>,
     // Step 3, Transposing dimensions
     permute: Vec<Index>,
     // Step 4, Tiling or repeating dimensions
     repeat: Vec<(Index, Shape)>,
     // Step 5, Combining dimensions into a single dimension
     composition: Vec<Composition>,
+    // Step 6, Unbinding the leading output axis into several tensors
+    split: Option<Split>,
 }
 
-impl syn::parse::Parse for Expression {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
+impl Expression {
+    fn parse(pattern: &syn::LitStr) -> syn::Result<Self> {
+        let einops::Expression {
+            decomposition: left,
+            composition: right,
+            split,
+        } = einops::Expression::parse(&pattern.value())
+            .map_err(|message| syn::Error::new(pattern.span(), message))?;
+
         let (decomposition, requires_decomposition, minimum_input_rank) =
-            parse_decomposition(input)?;
+            parse_decomposition(pattern, &left)?;
 
-        let reduce = parse_reduce(&decomposition);
+        // The grammar allows index reductions only on their own
+        let (index_reduce, reduce): (Vec<_>, Vec<_>) = parse_reduce(&decomposition)
+            .into_iter()
+            .partition(|(_, operation)| operation.is_index());
 
         let (composition, permute, repeat) =
-            parse_composition_permute_repeat(input, &decomposition)?;
+            parse_composition_permute_repeat(pattern, &decomposition, &right)?;
+        let split = split.then(|| parse_split(&decomposition, &right));
 
         Ok(Expression {
             minimum_input_rank,
             requires_decomposition,
             decomposition,
             reduce,
+            index_reduce,
             permute,
             repeat,
             composition,
+            split,
         })
     }
 }
@@ -141,6 +208,8 @@ impl quote::ToTokens for ParsedExpression {
             candle_crate,
             tensor: tensor_ident,
             tensor_expression: tensor_tokens,
+            reducer,
+            return_sizes,
             expression,
         } = self;
         let Expression {
@@ -148,9 +217,11 @@ impl quote::ToTokens for ParsedExpression {
             requires_decomposition,
             decomposition,
             reduce,
+            index_reduce,
             permute,
             repeat,
             composition,
+            split,
         } = expression;
 
         // Variable to store the shape slice
@@ -193,12 +264,63 @@ impl quote::ToTokens for ParsedExpression {
         let decomposition_ignored_len =
             !decomposition_tokens.is_empty() && last_unknown_index.is_some();
 
+        // Standalone `name:size` axes are checked before anything else runs
+        let assertion_tokens = to_tokens_size_assertions(
+            candle_crate,
+            decomposition,
+            &ignored_len_ident,
+            &shape_ident,
+        );
+        let sizes_ident = private_ident("sizes");
+        let sizes_tokens = if *return_sizes {
+            let sizes = to_tokens_sizes(
+                runtime_crate,
+                candle_crate,
+                decomposition,
+                &ignored_len_ident,
+                &shape_ident,
+            );
+            quote!(let #sizes_ident = #sizes;)
+        } else {
+            proc_macro2::TokenStream::new()
+        };
+        let inspection_ignored_len = (!assertion_tokens.is_empty() || *return_sizes)
+            && decomposition.iter().any(|expression| {
+                matches!(
+                    expression,
+                    Decomposition::Named {
+                        index: Index::Unknown(_),
+                        ..
+                    } | Decomposition::Derived {
+                        index: Index::Unknown(_),
+                        ..
+                    }
+                )
+            });
+
         // If needed we generate tokens for reducing the tensor
-        let (reduce_tokens, reduce_ignored_len) = if !reduce.is_empty() {
+        let (reduce_tokens, reduce_ignored_len) = if !index_reduce.is_empty() {
+            let requires_ignored_len = index_reduce
+                .iter()
+                .any(|(index, _)| matches!(index, Index::Range(_) | Index::Unknown(_)));
+            let tokens = to_tokens_arg_reduce(
+                runtime_crate,
+                index_reduce,
+                tensor_ident,
+                &ignored_len_ident,
+            );
+            (tokens, requires_ignored_len)
+        } else if !reduce.is_empty() {
             let requires_ignored_len = reduce
                 .iter()
                 .any(|(index, _)| matches!(index, Index::Range(_) | Index::Unknown(_)));
-            let tokens = to_tokens_reduce(runtime_crate, reduce, tensor_ident, &ignored_len_ident);
+            let tokens = to_tokens_reduce(
+                runtime_crate,
+                reduce,
+                reducer.as_ref().map(|(reducer_ident, _)| reducer_ident),
+                tensor_ident,
+                &ignored_len_ident,
+            );
             (tokens, requires_ignored_len)
         } else {
             (proc_macro2::TokenStream::new(), false)
@@ -331,6 +453,7 @@ impl quote::ToTokens for ParsedExpression {
         };
 
         let ignored_len_tokens = if decomposition_ignored_len
+            || inspection_ignored_len
             || reduce_ignored_len
             || permute_ignored_len
             || repeat_ignored_len
@@ -364,7 +487,11 @@ impl quote::ToTokens for ParsedExpression {
             composition_tokens.is_empty(),
         ];
 
-        let error_tokens = if tokens_empty.iter().all(|x| *x) {
+        // Assertions and sizes inspect the input even when its layout is kept
+        let inspects_input = !assertion_tokens.is_empty() || *return_sizes;
+        let identity = tokens_empty.iter().all(|empty| *empty) && split.is_none();
+
+        let error_tokens = if identity && !inspects_input {
             // If transformations are applied, we raise a compile time error
             quote!(compile_error!(
                 "No transformations applied, no need for einops"
@@ -373,7 +500,7 @@ impl quote::ToTokens for ParsedExpression {
             proc_macro2::TokenStream::new()
         };
 
-        let shape_tokens = if tokens_empty.iter().all(|empty| *empty) {
+        let shape_tokens = if identity && !inspects_input {
             proc_macro2::TokenStream::new()
         } else {
             quote!(let #shape_ident = #runtime_crate::Backend::shape(&#tensor_ident);)
@@ -425,10 +552,67 @@ impl quote::ToTokens for ParsedExpression {
             quote!(let #shape_ident = #runtime_crate::Backend::shape(&#tensor_ident);)
         };
 
+        // Tensor lists are resolved to a stacking backend at compile time;
+        // every other input is used unchanged
+        let input_kind_ident = private_ident("input_kind");
+        let input_kind_tokens = if shape_tokens.is_empty() {
+            proc_macro2::TokenStream::new()
+        } else {
+            quote! {
+                let #input_kind_ident = {
+                    use #runtime_crate::__private::{
+                        BackendInputKind as _, ListInputKind as _, TensorInputKind as _,
+                    };
+                    (&&#runtime_crate::__private::EinopsInput(&#tensor_ident)).einops_input_kind()
+                };
+                let #tensor_ident = #input_kind_ident.prepare(#tensor_ident)?;
+            }
+        };
+
+        let result_tokens = match split {
+            // Keep the output owned, as it is for every other expression
+            None if identity => {
+                quote!(#runtime_crate::Backend::reshape(#tensor_ident, &#shape_ident))
+            }
+            None => quote!(::core::result::Result::Ok(#tensor_ident)),
+            Some(Split { length: None }) => quote!(#runtime_crate::Backend::unbind(#tensor_ident)),
+            Some(Split {
+                length: Some(length),
+            }) => quote! {
+                let #tensor_ident = #runtime_crate::Backend::unbind(#tensor_ident)?;
+                <[_; #length] as ::core::convert::TryFrom<::std::vec::Vec<_>>>::try_from(#tensor_ident)
+                    .map_err(|pieces| {
+                        #candle_crate::Error::msg(::std::format!(
+                            "split produced {} tensors, expected {}",
+                            pieces.len(),
+                            #length,
+                        ))
+                    })
+            },
+        };
+
+        let result_tokens = if *return_sizes && split.is_none() && !identity {
+            quote!(::core::result::Result::Ok((#tensor_ident, #sizes_ident)))
+        } else if *return_sizes {
+            quote! {
+                let #tensor_ident = { #result_tokens }?;
+                ::core::result::Result::Ok((#tensor_ident, #sizes_ident))
+            }
+        } else {
+            result_tokens
+        };
+
+        let reducer_tokens = reducer
+            .as_ref()
+            .map(|(_, reducer_tokens)| reducer_tokens.clone())
+            .unwrap_or_default();
+
         let code = quote! {(|| -> #runtime_crate::Result<_> {
             #error_tokens
 
             #tensor_tokens
+            #reducer_tokens
+            #input_kind_tokens
 
             #shape_tokens
 
@@ -436,6 +620,9 @@ impl quote::ToTokens for ParsedExpression {
 
             #ignored_len_tokens
 
+            #assertion_tokens
+            #sizes_tokens
+
             #decomposition_tokens
 
             #reduce_tokens
@@ -450,7 +637,7 @@ impl quote::ToTokens for ParsedExpression {
             #composition_shape_tokens
             #composition_tokens
 
-            ::core::result::Result::Ok(#tensor_ident)
+            #result_tokens
         })()};
 
         code.to_tokens(tokens);
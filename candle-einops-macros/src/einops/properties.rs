@@ -77,7 +77,8 @@ fn assert_corpus<'a>(patterns: impl IntoIterator<Item = &'a str>) {
 
 fn planning_unwinds(pattern: &str) -> bool {
     catch_unwind(AssertUnwindSafe(|| {
-        let Ok(expression) = syn::parse_str::<Expression>(pattern) else {
+        let pattern = syn::LitStr::new(pattern, proc_macro2::Span::call_site());
+        let Ok(expression) = Expression::parse(&pattern) else {
             return;
         };
 
@@ -88,6 +89,8 @@ fn planning_unwinds(pattern: &str) -> bool {
             candle_crate: syn::parse_quote!(::candle_core),
             tensor,
             tensor_expression,
+            reducer: None,
+            return_sizes: false,
             expression,
         };
         let mut planned = TokenStream::new();
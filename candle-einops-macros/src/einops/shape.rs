@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use quote::quote;
+use syn::parse::ParseStream;
+
+use super::{candle_crate_path, private_ident, runtime_crate_path};
+
+pub fn einops_shape(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let parsed_shape: ParsedShape = syn::parse2(input)?;
+    Ok(quote! { #parsed_shape })
+}
+
+#[derive(Debug)]
+struct ParsedShape {
+    runtime_crate: syn::Path,
+    candle_crate: syn::Path,
+    source: String,
+    // `None` for axes skipped with `_`
+    axes: Vec<Option<syn::Ident>>,
+    // Position of the '..' symbol among the axes
+    ellipsis: Option<usize>,
+    tensor_expression: syn::Expr,
+}
+
+impl syn::parse::Parse for ParsedShape {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let pattern = input.parse::<syn::LitStr>()?;
+        let (axes, ellipsis) = pattern.parse_with(parse_shape_axes)?;
+
+        input.parse::<syn::Token![,]>()?;
+        let tensor_expression = input.parse::<syn::Expr>()?;
+
+        Ok(Self {
+            runtime_crate: runtime_crate_path()?,
+            candle_crate: candle_crate_path()?,
+            source: pattern.value(),
+            axes,
+            ellipsis,
+            tensor_expression,
+        })
+    }
+}
+
+type ShapeAxes = (Vec<Option<syn::Ident>>, Option<usize>);
+
+fn parse_shape_axes(input: ParseStream) -> syn::Result<ShapeAxes> {
+    let mut axes = Vec::new();
+    let mut ellipsis = None;
+    let mut names = HashSet::new();
+    while !input.is_empty() {
+        if input.peek(syn::Token![_]) {
+            input.parse::<syn::Token![_]>()?;
+            axes.push(None);
+        } else if input.peek(syn::Token![..]) {
+            let span = input.span();
+            input.parse::<syn::Token![..]>()?;
+            if ellipsis.replace(axes.len()).is_some() {
+                return Err(syn::Error::new(
+                    span,
+                    "Ellipsis `..` appears more than once",
+                ));
+            }
+        } else if input.peek(syn::Ident) {
+            let name = input.parse::<syn::Ident>()?;
+            if !names.insert(name.to_string()) {
+                return Err(syn::Error::new(
+                    name.span(),
+                    format!("Axis `{name}` appears more than once"),
+                ));
+            }
+            axes.push(Some(name));
+        } else {
+            return Err(
+                input.error("Only identifiers, `_`, and `..` are allowed in a shape pattern")
+            );
+        }
+    }
+    Ok((axes, ellipsis))
+}
+
+impl quote::ToTokens for ParsedShape {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let ParsedShape {
+            runtime_crate,
+            candle_crate,
+            source,
+            axes,
+            ellipsis,
+            tensor_expression,
+        } = self;
+
+        let tensor_ident = private_ident("input");
+        let shape_ident = private_ident("input_shape");
+        let ignored_len_ident = private_ident("input_ignored_len");
+        let struct_ident = private_ident("Shape");
+
+        let minimum_input_rank = axes.len();
+        let last_required_index = minimum_input_rank.saturating_sub(1);
+
+        // Without '..' the pattern must name every axis, with it the axes
+        // after '..' are offset by the number of axes it captures
+        let rank_validation_tokens = if ellipsis.is_some() {
+            quote!(let #ignored_len_ident = #shape_ident.len() - #minimum_input_rank;)
+        } else {
+            quote! {
+                if #shape_ident.len() != #minimum_input_rank {
+                    return ::core::result::Result::Err(#candle_crate::Error::msg(::std::format!(
+                        "einops expression `{}` expects {} axes, input rank is {}",
+                        #source,
+                        #minimum_input_rank,
+                        #shape_ident.len(),
+                    )));
+                }
+            }
+        };
+
+        let (names, positions): (Vec<_>, Vec<_>) = axes
+            .iter()
+            .enumerate()
+            .filter_map(|(position, name)| {
+                let name = name.as_ref()?;
+                let position = match ellipsis {
+                    Some(ellipsis) if position >= *ellipsis => {
+                        quote!(#position + #ignored_len_ident)
+                    }
+                    _ => quote!(#position),
+                };
+                Some((name, position))
+            })
+            .unzip();
+
+        let code = quote! {(|| -> #runtime_crate::Result<_> {
+            #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+            struct #struct_ident {
+                #(#names: usize,)*
+            }
+
+            let #tensor_ident = #tensor_expression;
+            let #shape_ident = #runtime_crate::Backend::shape(&#tensor_ident);
+
+            if #shape_ident.len() < #minimum_input_rank {
+                return ::core::result::Result::Err(#candle_crate::Error::msg(::std::format!(
+                    "shape index {} out of range for rank {}; einops expression requires at least {} axes",
+                    #last_required_index,
+                    #shape_ident.len(),
+                    #minimum_input_rank,
+                )));
+            }
+            #rank_validation_tokens
+
+            ::core::result::Result::Ok(#struct_ident {
+                #(#names: #shape_ident[#positions],)*
+            })
+        })()};
+
+        code.to_tokens(tokens);
+    }
+}
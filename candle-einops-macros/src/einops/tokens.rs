@@ -317,9 +317,20 @@ pub fn to_tokens_permute(
 pub fn to_tokens_reduce(
     runtime_crate: &syn::Path,
     reduce: &[(Index, Operation)],
+    reducer_ident: Option<&syn::Ident>,
     tensor_ident: &syn::Ident,
     ignored_len_ident: &syn::Ident,
 ) -> proc_macro2::TokenStream {
+    // Custom `reduce(...)` groups need the reducer alongside the operations
+    let (reduce_method, reducer) = match reducer_ident {
+        Some(reducer_ident) => (quote!(reduce_axes_with), quote!(, #reducer_ident)),
+        None => (quote!(reduce_axes), proc_macro2::TokenStream::new()),
+    };
+
+    // Braced `{op}` operations are evaluated once per distinct expression
+    let mut operation_bindings: Vec<(String, proc_macro2::Ident, proc_macro2::TokenStream)> =
+        Vec::new();
+
     let (reduce_indices, reduce_operations, ignored_indices, ignored_operations) =
         reduce.iter().fold(
             (Vec::new(), Vec::new(), None, None),
@@ -331,12 +342,41 @@ pub fn to_tokens_reduce(
             ),
              expression| {
                 let (index, operation) = expression;
+                let custom = matches!(operation, Operation::Custom);
                 let operation = match operation {
                     Operation::Min => quote!(#runtime_crate::Operation::Min),
                     Operation::Max => quote!(#runtime_crate::Operation::Max),
                     Operation::Sum => quote!(#runtime_crate::Operation::Sum),
                     Operation::Mean => quote!(#runtime_crate::Operation::Mean),
                     Operation::Prod => quote!(#runtime_crate::Operation::Prod),
+                    Operation::LogSumExp => quote!(#runtime_crate::Operation::LogSumExp),
+                    Operation::Var => quote!(#runtime_crate::Operation::Var),
+                    Operation::Std => quote!(#runtime_crate::Operation::Std),
+                    Operation::Norm => quote!(#runtime_crate::Operation::Norm),
+                    Operation::Custom => quote!(#runtime_crate::ReduceStep::Custom),
+                    Operation::Expr(expr) => {
+                        let key = expr.to_string();
+                        let binding = match operation_bindings.iter().find(|(k, ..)| *k == key) {
+                            Some((_, binding, _)) => binding.clone(),
+                            None => {
+                                let binding = private_ident(&format!(
+                                    "operation_{}",
+                                    operation_bindings.len()
+                                ));
+                                operation_bindings.push((key, binding.clone(), expr.clone()));
+                                binding
+                            }
+                        };
+                        quote!(#binding)
+                    }
+                    Operation::ArgMin | Operation::ArgMax => {
+                        unreachable!("index reductions are lowered by `to_tokens_arg_reduce`")
+                    }
+                };
+                let operation = if reducer_ident.is_some() && !custom {
+                    quote!(#runtime_crate::ReduceStep::Operation(#operation))
+                } else {
+                    operation
                 };
                 match index {
                     Index::Known(i) => {
@@ -362,6 +402,11 @@ pub fn to_tokens_reduce(
             },
         );
 
+    let bindings = operation_bindings
+        .iter()
+        .map(|(_, binding, expr)| quote!(let #binding: #runtime_crate::Operation = #expr;));
+    let bindings = quote!(#(#bindings)*);
+
     match (
         ignored_indices,
         ignored_operations,
@@ -369,17 +414,20 @@ pub fn to_tokens_reduce(
     ) {
         (Some(ignored_indices), Some(ignored_operations), true) => {
             quote!(
-                let #tensor_ident = #runtime_crate::Backend::reduce_axes(
+                #bindings
+                let #tensor_ident = #runtime_crate::Backend::#reduce_method(
                     #tensor_ident,
                     &mut #ignored_indices
                         .zip(#ignored_operations)
                         .collect::<::std::vec::Vec<(_, _)>>()
+                    #reducer
                 )?;
             )
         }
         (Some(ignored_indices), Some(ignored_operations), false) => {
             quote!(
-                let #tensor_ident = #runtime_crate::Backend::reduce_axes(
+                #bindings
+                let #tensor_ident = #runtime_crate::Backend::#reduce_method(
                     #tensor_ident,
                     &mut [#(#reduce_indices),*]
                         .into_iter()
@@ -390,13 +438,15 @@ pub fn to_tokens_reduce(
                                 .chain(#ignored_operations)
                         )
                         .collect::<::std::vec::Vec<(_, _)>>()
+                    #reducer
                 )?;
             )
         }
         (None, None, false) => {
             quote!(
-                let #tensor_ident = #runtime_crate::Backend::reduce_axes(
-                    #tensor_ident, &mut [#((#reduce_indices, #reduce_operations)),*]
+                #bindings
+                let #tensor_ident = #runtime_crate::Backend::#reduce_method(
+                    #tensor_ident, &mut [#((#reduce_indices, #reduce_operations)),*] #reducer
                 )?;
             )
         }
@@ -404,6 +454,45 @@ pub fn to_tokens_reduce(
     }
 }
 
+pub fn to_tokens_arg_reduce(
+    runtime_crate: &syn::Path,
+    reduce: &[(Index, Operation)],
+    tensor_ident: &syn::Ident,
+    ignored_len_ident: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    let operation = match reduce[0].1 {
+        Operation::ArgMin => quote!(#runtime_crate::IndexOperation::ArgMin),
+        Operation::ArgMax => quote!(#runtime_crate::IndexOperation::ArgMax),
+        _ => unreachable!("value reductions are lowered by `to_tokens_reduce`"),
+    };
+
+    // The group keeps the order in which its axes are written
+    let axes = if reduce
+        .iter()
+        .any(|(index, _)| matches!(index, Index::Range(_)))
+    {
+        let segments = reduce.iter().map(|(index, _)| match index {
+            Index::Known(i) => quote!([#i]),
+            Index::Unknown(i) => quote!([#i + #ignored_len_ident - 1]),
+            Index::Range(i) => quote!(#i..(#i + #ignored_len_ident)),
+        });
+        quote!(::std::iter::empty()#(.chain(#segments))*.collect::<::std::vec::Vec<usize>>())
+    } else {
+        let indices = reduce.iter().map(|(index, _)| match index {
+            Index::Known(i) => quote!(#i),
+            Index::Unknown(i) => quote!(#i + #ignored_len_ident - 1),
+            Index::Range(_) => unreachable!(),
+        });
+        quote!([#(#indices),*])
+    };
+
+    quote!(
+        let #tensor_ident = #runtime_crate::Backend::arg_reduce_axes(
+            #tensor_ident, &#axes, #operation
+        )?;
+    )
+}
+
 pub fn to_tokens_decomposition(
     runtime_crate: &syn::Path,
     candle_crate: &syn::Path,
@@ -415,30 +504,17 @@ pub fn to_tokens_decomposition(
     let (known_indices, ignored_indices, unknown_indices) = left_expression.iter().fold(
         (Vec::new(), proc_macro2::TokenStream::new(), Vec::new()),
         |(mut known_indices, mut ignored_indices, mut unknown_indices), expression| {
+            let extent =
+                decomposition_extent(candle_crate, expression, ignored_len_ident, shape_ident);
             match expression {
                 Decomposition::Named {
                     index: Index::Known(_),
-                    shape: Some(Shape::Lit(size)),
                     ..
-                } => known_indices.push(quote!(#size)),
-                Decomposition::Named {
+                }
+                | Decomposition::Derived {
                     index: Index::Known(_),
-                    shape: Some(Shape::Expr(size)),
-                    ..
-                } => known_indices.push(quote!(#size)),
-                Decomposition::Named {
-                    index: Index::Known(i),
-                    ..
-                } => known_indices.push(quote!(#shape_ident[#i])),
-                Decomposition::Derived {
-                    index: Index::Known(i),
-                    shape_calc,
                     ..
-                } => known_indices.push(checked_derived_dimension(
-                    candle_crate,
-                    quote!(#shape_ident[#i]),
-                    shape_calc,
-                )),
+                } => known_indices.extend(extent),
                 Decomposition::Named {
                     index: Index::Range(i),
                     ..
@@ -449,27 +525,12 @@ pub fn to_tokens_decomposition(
                 }
                 Decomposition::Named {
                     index: Index::Unknown(_),
-                    shape: Some(Shape::Lit(size)),
                     ..
-                } => unknown_indices.push(quote!(#size)),
-                Decomposition::Named {
+                }
+                | Decomposition::Derived {
                     index: Index::Unknown(_),
-                    shape: Some(Shape::Expr(size)),
                     ..
-                } => unknown_indices.push(quote!(#size)),
-                Decomposition::Named {
-                    index: Index::Unknown(i),
-                    ..
-                } => unknown_indices.push(quote!(#shape_ident[#i + #ignored_len_ident - 1])),
-                Decomposition::Derived {
-                    index: Index::Unknown(i),
-                    shape_calc,
-                    ..
-                } => unknown_indices.push(checked_derived_dimension(
-                    candle_crate,
-                    quote!(#shape_ident[#i + #ignored_len_ident - 1]),
-                    shape_calc,
-                )),
+                } => unknown_indices.extend(extent),
                 _ => unreachable!(),
             }
             (known_indices, ignored_indices, unknown_indices)
@@ -516,6 +577,121 @@ pub fn to_tokens_decomposition(
     )
 }
 
+/// Checks the length of every input axis written alone as `name:size`
+/// or `(name:size)`, before any reshape can fail with a less specific error.
+pub fn to_tokens_size_assertions(
+    candle_crate: &syn::Path,
+    left_expression: &[Decomposition],
+    ignored_len_ident: &syn::Ident,
+    shape_ident: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    left_expression
+        .iter()
+        .filter_map(|expression| match expression {
+            Decomposition::Named {
+                name,
+                index: index @ (Index::Known(i) | Index::Unknown(i)),
+                shape: Some(shape),
+                ..
+            } if left_expression
+                .iter()
+                .filter(|other| decomposition_index(other) == *i)
+                .count()
+                == 1 =>
+            {
+                let dimension = match index {
+                    Index::Unknown(_) => quote!(#shape_ident[#i + #ignored_len_ident - 1]),
+                    _ => quote!(#shape_ident[#i]),
+                };
+                let (label, size) = match shape {
+                    Shape::Lit(size) => (name.clone(), quote!(#size)),
+                    Shape::Expr(size) => (size.to_string(), quote!(#size)),
+                };
+                Some(quote! {
+                    if #dimension != #size {
+                        return ::core::result::Result::Err(#candle_crate::Error::msg(::std::format!(
+                            "axis `{}` has length {}, expected {}",
+                            #label,
+                            #dimension,
+                            #size,
+                        )));
+                    }
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Collects the extent of every named input axis into a `Sizes` value.
+pub fn to_tokens_sizes(
+    runtime_crate: &syn::Path,
+    candle_crate: &syn::Path,
+    left_expression: &[Decomposition],
+    ignored_len_ident: &syn::Ident,
+    shape_ident: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    let entries = left_expression.iter().filter_map(|expression| {
+        let name = match expression {
+            Decomposition::Named { name, .. } | Decomposition::Derived { name, .. } => name,
+        };
+        // Literal sizes and braced fields do not name an axis
+        if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') || name.contains('.') {
+            return None;
+        }
+        let extent =
+            decomposition_extent(candle_crate, expression, ignored_len_ident, shape_ident)?;
+        Some(quote!((#name, #extent)))
+    });
+    quote!(
+        <#runtime_crate::Sizes as ::core::iter::FromIterator<_>>::from_iter([#(#entries),*])
+    )
+}
+
+fn decomposition_index(expression: &Decomposition) -> usize {
+    match expression {
+        Decomposition::Named { index, .. } | Decomposition::Derived { index, .. } => match index {
+            Index::Known(i) | Index::Unknown(i) | Index::Range(i) => *i,
+        },
+    }
+}
+
+/// Returns the extent of one elementary input axis, or `None` for `..`.
+fn decomposition_extent(
+    candle_crate: &syn::Path,
+    expression: &Decomposition,
+    ignored_len_ident: &syn::Ident,
+    shape_ident: &syn::Ident,
+) -> Option<proc_macro2::TokenStream> {
+    let dimension = |index: &Index| match index {
+        Index::Known(i) => quote!(#shape_ident[#i]),
+        Index::Unknown(i) => quote!(#shape_ident[#i + #ignored_len_ident - 1]),
+        Index::Range(_) => unreachable!("'..' has no single extent"),
+    };
+    match expression {
+        Decomposition::Named {
+            index: Index::Range(_),
+            ..
+        } => None,
+        Decomposition::Named {
+            shape: Some(Shape::Lit(size)),
+            ..
+        } => Some(quote!(#size)),
+        Decomposition::Named {
+            shape: Some(Shape::Expr(size)),
+            ..
+        } => Some(quote!(#size)),
+        Decomposition::Named { index, .. } => Some(dimension(index)),
+        Decomposition::Derived {
+            index, shape_calc, ..
+        } => Some(checked_derived_dimension(
+            candle_crate,
+            dimension(index),
+            shape_calc,
+        )),
+    }
+}
+
 fn checked_derived_dimension(
     candle_crate: &syn::Path,
     dimension: proc_macro2::TokenStream,
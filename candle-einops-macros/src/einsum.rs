@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 
+use candle_einops_syntax::{
+    LabelReduction, rewrite_numpy_equation, split_label_reductions, validate_label,
+};
 use proc_macro_crate::{FoundCrate, crate_name};
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::{ToTokens, quote};
@@ -27,6 +30,9 @@ struct Equation {
     operands: Vec<Operand>,
     output: Vec<AxisId>,
     output_ellipsis_position: Option<usize>,
+    /// Labels annotated as `mean(t)`, `max(t)`, or `min(t)`, which trail
+    /// `output` and are reduced after the contraction in this order.
+    reductions: Vec<(AxisId, LabelReduction)>,
     names: Vec<String>,
 }
 
@@ -70,7 +76,9 @@ impl Equation {
             });
         }
 
-        let output_axis_list = parse_axis_list(output_text, literal.span(), "output axis list")?;
+        let (output_text, groups) = split_label_reductions(output_text)
+            .map_err(|message| syn::Error::new(literal.span(), message))?;
+        let output_axis_list = parse_axis_list(&output_text, literal.span(), "output axis list")?;
         if output_axis_list.ellipsis_position.is_some()
             && !operands
                 .iter()
@@ -99,11 +107,36 @@ impl Equation {
             output_names.push(label);
             output.push(axis);
         }
+        let mut reductions = Vec::<(AxisId, LabelReduction)>::new();
+        for (reduction, labels) in groups {
+            for label in labels {
+                let error = |message: String| syn::Error::new(literal.span(), message);
+                if output_names.contains(&label) {
+                    return Err(error(format!(
+                        "einsum label `{label}` is both kept and reduced"
+                    )));
+                }
+                let axis = interned.get(&label).copied().ok_or_else(|| {
+                    error(format!(
+                        "einsum reduced label `{label}` does not occur in the input"
+                    ))
+                })?;
+                if reductions.iter().any(|(reduced, _)| *reduced == axis) {
+                    return Err(error(format!(
+                        "einsum label `{label}` is reduced more than once"
+                    )));
+                }
+                reductions.push((axis, reduction));
+            }
+        }
+        // Reduced labels stay in the output until every contraction has run
+        output.extend(reductions.iter().map(|(axis, _)| *axis));
 
         Ok(Self {
             operands,
             output,
             output_ellipsis_position: output_axis_list.ellipsis_position,
+            reductions,
             names,
         })
     }
@@ -302,19 +335,7 @@ fn parse_axis_list(text: &str, span: Span, kind: &str) -> syn::Result<AxisList>
                 ));
             }
         } else {
-            let mut characters = label.chars();
-            let valid_start = characters
-                .next()
-                .is_some_and(|character| character == '_' || character.is_alphabetic());
-            if !valid_start
-                || !characters.all(|character| character == '_' || character.is_alphanumeric())
-            {
-                return Err(syn::Error::new(
-                    span,
-                    format!("invalid einsum axis label `{label}`"),
-                ));
-            }
-            labels.push(label.to_owned());
+            labels.push(parse_label(label, span)?);
         }
     }
     Ok(AxisList {
@@ -323,25 +344,88 @@ fn parse_axis_list(text: &str, span: Span, kind: &str) -> syn::Result<AxisList>
     })
 }
 
+fn parse_label(label: &str, span: Span) -> syn::Result<String> {
+    validate_label(label).map_err(|message| syn::Error::new(span, message))?;
+    Ok(label.to_owned())
+}
+
+/// Returns the runtime `EinsumLabelReduction` variant for `reduction`.
+fn reduction_variant(reduction: LabelReduction) -> Ident {
+    let name = match reduction {
+        LabelReduction::Mean => "Mean",
+        LabelReduction::Max => "Max",
+        LabelReduction::Min => "Min",
+    };
+    Ident::new(name, Span::call_site())
+}
+
+mod kw {
+    syn::custom_keyword!(accumulate);
+    syn::custom_keyword!(device);
+    syn::custom_keyword!(numpy);
+    syn::custom_keyword!(operand);
+    syn::custom_keyword!(path);
+    syn::custom_keyword!(promote);
+    syn::custom_keyword!(semiring);
+}
+
+/// Rewrites a NumPy-style equation into the whitespace-delimited grammar.
+fn numpy_equation(literal: &syn::LitStr) -> syn::Result<syn::LitStr> {
+    let equation = rewrite_numpy_equation(&literal.value())
+        .map_err(|message| syn::Error::new(literal.span(), message))?;
+    Ok(syn::LitStr::new(&equation, literal.span()))
+}
+
 struct Invocation {
     runtime_crate: syn::Path,
     operands: Vec<syn::Expr>,
     operand_idents: Vec<Ident>,
     equation: Equation,
+    path: Option<Vec<(usize, usize)>>,
+    accumulate: Option<Ident>,
+    promote: bool,
+    device: Option<usize>,
+    semiring: Option<Ident>,
 }
 
 impl Parse for Invocation {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let semiring = if input.peek(kw::semiring) {
+            input.parse::<kw::semiring>()?;
+            input.parse::<syn::Token![=]>()?;
+            let name = input.parse::<Ident>()?;
+            input.parse::<syn::Token![,]>()?;
+            match name.to_string().as_str() {
+                "sum_product" => None,
+                "max_plus" => Some(Ident::new("MaxPlus", name.span())),
+                "min_plus" => Some(Ident::new("MinPlus", name.span())),
+                "log_sum_exp" => Some(Ident::new("LogSumExp", name.span())),
+                _ => {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        "einsum semiring must be `sum_product`, `max_plus`, `min_plus`, or `log_sum_exp`",
+                    ));
+                }
+            }
+        } else {
+            None
+        };
+        let numpy = input.parse::<Option<kw::numpy>>()?.is_some();
         let literal = input.parse::<syn::LitStr>()?;
+        let literal = if numpy {
+            numpy_equation(&literal)?
+        } else {
+            literal
+        };
         let equation = Equation::parse(&literal)?;
         input.parse::<syn::Token![,]>()?;
         let mut operands = Vec::new();
         loop {
-            if input.is_empty() {
+            if input.is_empty() || input.peek(syn::Token![;]) {
                 break;
             }
             operands.push(input.parse::<syn::Expr>()?);
-            if input.is_empty() {
+            if input.is_empty() || input.peek(syn::Token![;]) {
                 break;
             }
             input.parse::<syn::Token![,]>()?;
@@ -357,6 +441,86 @@ impl Parse for Invocation {
             ));
         }
 
+        let mut path = None;
+        let mut accumulate = None;
+        let mut promote = false;
+        let mut device = None;
+        if input.parse::<Option<syn::Token![;]>>()?.is_some() {
+            loop {
+                let lookahead = input.lookahead1();
+                if lookahead.peek(kw::path) {
+                    let keyword = input.parse::<kw::path>()?;
+                    if semiring.is_some() {
+                        return Err(syn::Error::new(
+                            keyword.span,
+                            "einsum option `path` only applies to the sum-product semiring",
+                        ));
+                    }
+                    input.parse::<syn::Token![=]>()?;
+                    let parsed = parse_path(input, operands.len())?;
+                    set_option(&mut path, parsed, keyword.span, "path")?;
+                } else if lookahead.peek(kw::accumulate) {
+                    let keyword = input.parse::<kw::accumulate>()?;
+                    input.parse::<syn::Token![=]>()?;
+                    let dtype = input.parse::<Ident>()?;
+                    let variant = match dtype.to_string().as_str() {
+                        "f32" => Ident::new("F32", dtype.span()),
+                        "f64" => Ident::new("F64", dtype.span()),
+                        _ => {
+                            return Err(syn::Error::new(
+                                dtype.span(),
+                                "einsum accumulation type must be `f32` or `f64`",
+                            ));
+                        }
+                    };
+                    set_option(&mut accumulate, variant, keyword.span, "accumulate")?;
+                } else if lookahead.peek(kw::promote) {
+                    let keyword = input.parse::<kw::promote>()?;
+                    input.parse::<syn::Token![=]>()?;
+                    let rule = input.parse::<Ident>()?;
+                    if rule != "widest" {
+                        return Err(syn::Error::new(
+                            rule.span(),
+                            "einsum promotion must be `widest`",
+                        ));
+                    }
+                    if std::mem::replace(&mut promote, true) {
+                        return Err(syn::Error::new(
+                            keyword.span,
+                            "duplicate einsum option `promote`",
+                        ));
+                    }
+                } else if lookahead.peek(kw::device) {
+                    let keyword = input.parse::<kw::device>()?;
+                    input.parse::<syn::Token![=]>()?;
+                    input.parse::<kw::operand>()?;
+                    let content;
+                    syn::parenthesized!(content in input);
+                    let literal = content.parse::<syn::LitInt>()?;
+                    let index = literal.base10_parse::<usize>()?;
+                    if index >= operands.len() {
+                        return Err(syn::Error::new(
+                            literal.span(),
+                            format!(
+                                "einsum device operand {index} is out of range for {} operands",
+                                operands.len()
+                            ),
+                        ));
+                    }
+                    set_option(&mut device, index, keyword.span, "device")?;
+                } else {
+                    return Err(lookahead.error());
+                }
+                if input.is_empty() {
+                    break;
+                }
+                input.parse::<syn::Token![,]>()?;
+                if input.is_empty() {
+                    break;
+                }
+            }
+        }
+
         let operand_idents = (0..operands.len())
             .map(|index| private_ident(&format!("operand_{index}")))
             .collect();
@@ -365,10 +529,81 @@ impl Parse for Invocation {
             operands,
             operand_idents,
             equation,
+            path,
+            accumulate,
+            promote,
+            device,
+            semiring,
         })
     }
 }
 
+fn set_option<T>(slot: &mut Option<T>, value: T, span: Span, name: &str) -> syn::Result<()> {
+    if slot.replace(value).is_some() {
+        return Err(syn::Error::new(
+            span,
+            format!("duplicate einsum option `{name}`"),
+        ));
+    }
+    Ok(())
+}
+
+/// Parses `[(i, j), ...]` and checks each step against the operands still
+/// live, where both are removed and their intermediate is appended.
+fn parse_path(input: ParseStream, operands: usize) -> syn::Result<Vec<(usize, usize)>> {
+    let content;
+    let brackets = syn::bracketed!(content in input);
+    let steps = content.parse_terminated(
+        |step| {
+            let pair;
+            let parens = syn::parenthesized!(pair in step);
+            let first = pair.parse::<syn::LitInt>()?;
+            pair.parse::<syn::Token![,]>()?;
+            let second = pair.parse::<syn::LitInt>()?;
+            pair.parse::<Option<syn::Token![,]>>()?;
+            if !pair.is_empty() {
+                return Err(pair.error("einsum path steps are pairs of operand positions"));
+            }
+            Ok((
+                parens.span.join(),
+                first.base10_parse::<usize>()?,
+                second.base10_parse::<usize>()?,
+            ))
+        },
+        syn::Token![,],
+    )?;
+    let expected = operands.saturating_sub(1);
+    if steps.len() != expected {
+        return Err(syn::Error::new(
+            brackets.span.join(),
+            format!(
+                "einsum path has {} steps, but {operands} operands need {expected}",
+                steps.len()
+            ),
+        ));
+    }
+    let mut path = Vec::with_capacity(steps.len());
+    for (step, (span, first, second)) in steps.into_iter().enumerate() {
+        let live = operands - step;
+        if first.max(second) >= live {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "einsum path step {step} ({first}, {second}) is out of range for {live} operands"
+                ),
+            ));
+        }
+        if first == second {
+            return Err(syn::Error::new(
+                span,
+                format!("einsum path step {step} contracts operand {first} with itself"),
+            ));
+        }
+        path.push((first, second));
+    }
+    Ok(path)
+}
+
 impl ToTokens for Invocation {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let Self {
@@ -376,12 +611,28 @@ impl ToTokens for Invocation {
             operands,
             operand_idents,
             equation,
+            path,
+            accumulate,
+            promote,
+            device,
+            semiring,
         } = self;
         let bindings = operand_idents
             .iter()
             .zip(operands)
             .map(|(ident, operand)| quote!(let #ident = #operand;));
-        let execution = if equation.operands.len() > 2 || equation.requires_runtime_normalization()
+        let private = quote!(#runtime_crate::__private);
+        let accumulate_dtype = match accumulate {
+            Some(dtype) => quote!(::core::option::Option::Some(#private::DType::#dtype)),
+            None => quote!(::core::option::Option::None),
+        };
+        // Specs sum in the accumulation dtype at each reduction site
+        let accumulation = accumulate
+            .is_some()
+            .then(|| quote!(.with_accumulation(#accumulate_dtype)));
+        let execution = if semiring.is_some()
+            || equation.operands.len() > 2
+            || equation.requires_runtime_normalization()
         {
             let patterns = equation.operands.iter().map(|pattern| {
                 let labels = pattern
@@ -407,8 +658,17 @@ impl ToTokens for Invocation {
                     &[#(#output_labels),*],
                     #output_position,
                 ),
-            ));
-            if equation.operands.len() == 1 {
+            )#accumulation);
+            if let Some(semiring) = semiring {
+                let operand_refs = operand_idents.iter().map(
+                    |operand| quote!(#runtime_crate::__private::einsum_operand_ref(&#operand)),
+                );
+                quote!(#runtime_crate::__private::execute_semiring_einsum(
+                    &[#(#operand_refs),*],
+                    #spec,
+                    #runtime_crate::__private::EinsumSemiring::#semiring,
+                ))
+            } else if equation.operands.len() == 1 {
                 let operand = &operand_idents[0];
                 quote!(#runtime_crate::__private::execute_unary_ellipsis_einsum(
                     &#operand,
@@ -426,10 +686,20 @@ impl ToTokens for Invocation {
                 let operand_refs = operand_idents.iter().map(
                     |operand| quote!(#runtime_crate::__private::einsum_operand_ref(&#operand)),
                 );
-                quote!(#runtime_crate::__private::execute_nary_einsum(
-                    &[#(#operand_refs),*],
-                    #spec,
-                ))
+                match path {
+                    Some(path) => {
+                        let steps = path.iter().map(|(first, second)| quote!((#first, #second)));
+                        quote!(#runtime_crate::__private::execute_nary_einsum_with_path(
+                            &[#(#operand_refs),*],
+                            #spec,
+                            &[#(#steps),*],
+                        ))
+                    }
+                    None => quote!(#runtime_crate::__private::execute_nary_einsum(
+                        &[#(#operand_refs),*],
+                        #spec,
+                    )),
+                }
             }
         } else if equation.operands.len() == 1 {
             let operand = &operand_idents[0];
@@ -443,7 +713,7 @@ impl ToTokens for Invocation {
                         #input_rank,
                         #output_rank,
                         &[#(#permutation),*],
-                    ),
+                    )#accumulation,
                 )
             )
         } else {
@@ -481,10 +751,46 @@ impl ToTokens for Invocation {
                         &[#(#batch_labels),*],
                         &[#(#contracted_labels),*],
                         &[#(#output_permutation),*],
-                    ),
+                    )#accumulation,
                 )
             )
         };
+        let execution = if equation.reductions.is_empty() {
+            execution
+        } else {
+            let output = private_ident("output");
+            let reductions = equation.reductions.iter().map(|(axis, reduction)| {
+                let label = &equation.names[axis.0];
+                let variant = reduction_variant(*reduction);
+                quote!((#label, #private::EinsumLabelReduction::#variant))
+            });
+            quote!(#execution.and_then(|#output| {
+                #private::reduce_einsum_labels(#output, &[#(#reductions),*], #accumulate_dtype)
+            }))
+        };
+        let execution = if *promote || device.is_some() {
+            let promote = if *promote {
+                quote!(#private::EinsumPromotion::Widest)
+            } else {
+                quote!(#private::EinsumPromotion::Strict)
+            };
+            let device = match device {
+                Some(index) => quote!(#private::EinsumDevicePolicy::Operand(#index)),
+                None => quote!(#private::EinsumDevicePolicy::Strict),
+            };
+            with_converted_operands(
+                operand_idents,
+                &private,
+                quote!(execute_einsum_with_policy),
+                quote!(#private::EinsumOperandPolicy {
+                    promote: #promote,
+                    device: #device,
+                }),
+                execution,
+            )
+        } else {
+            execution
+        };
         quote!({
             #(#bindings)*
             #execution
@@ -493,6 +799,33 @@ impl ToTokens for Invocation {
     }
 }
 
+/// Wraps `execution` in a runtime function that converts the operands, with
+/// the operand bindings shadowed by the converted tensors inside its closure.
+fn with_converted_operands(
+    operand_idents: &[Ident],
+    private: &TokenStream,
+    function: TokenStream,
+    argument: TokenStream,
+    execution: TokenStream,
+) -> TokenStream {
+    let operands = private_ident("operands");
+    let operand_refs = operand_idents
+        .iter()
+        .map(|operand| quote!(#private::einsum_operand_ref(&#operand)));
+    let rebindings = operand_idents
+        .iter()
+        .enumerate()
+        .map(|(index, ident)| quote!(let #ident = #operands[#index];));
+    quote!(#private::#function(
+        &[#(#operand_refs),*],
+        #argument,
+        |#operands| {
+            #(#rebindings)*
+            #execution
+        },
+    ))
+}
+
 fn option_tokens(value: Option<usize>) -> TokenStream {
     match value {
         Some(value) => quote!(::core::option::Option::Some(#value)),
@@ -602,6 +935,48 @@ mod tests {
         assert_eq!(equation.operands[0].axes.len(), 2);
     }
 
+    #[test]
+    fn rewrites_numpy_equations() {
+        let rewrite = |equation: &str| {
+            let literal = syn::LitStr::new(equation, Span::call_site());
+            numpy_equation(&literal).map(|literal| literal.value())
+        };
+        assert_eq!(rewrite("bij,bjk->bik").unwrap(), "b i j, b j k -> b i k");
+        assert_eq!(rewrite("ij,jk").unwrap(), "i j, j k -> i k");
+        assert_eq!(rewrite("ba").unwrap(), "b a -> a b");
+        assert_eq!(rewrite("ii").unwrap(), "i i -> ");
+        assert_eq!(rewrite("...ij,...jk").unwrap(), ".. i j, .. j k -> .. i k");
+        assert_eq!(rewrite("bA,Ab").unwrap(), "b A, A b -> ");
+        assert_eq!(rewrite("Ba,c").unwrap(), "B a, c -> B a c");
+        assert_eq!(rewrite(" i j -> j i ").unwrap(), "i j -> j i");
+        assert!(rewrite("i..j").is_err());
+        assert!(rewrite("i1").is_err());
+        assert!(rewrite("i->i->i").is_err());
+        assert!(rewrite("ij->i,j").is_err());
+    }
+
+    #[test]
+    fn keeps_reduced_labels_as_trailing_output_axes() {
+        let literal: syn::LitStr = syn::parse_quote!("b t d, d e -> b max(t) e");
+        let equation = Equation::parse(&literal).expect("valid reduced equation");
+        let names = |axes: &[AxisId]| {
+            axes.iter()
+                .map(|axis| equation.names[axis.0].as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&equation.output), ["b", "e", "t"]);
+        assert_eq!(equation.reductions, [(AxisId(1), LabelReduction::Max)]);
+        assert_eq!(equation.binary_plan().contracted_labels, ["d"]);
+
+        let parse =
+            |equation: &str| Equation::parse(&syn::LitStr::new(equation, Span::call_site()));
+        assert!(parse("a b -> mean(a b)").is_ok());
+        assert!(parse("a b -> a prod(b)").is_err());
+        assert!(parse("a b -> a b mean(b)").is_err());
+        assert!(parse("a b -> mean(a) mean(a) b").is_err());
+        assert!(parse("a b -> a (b)").is_err());
+    }
+
     #[test]
     fn retains_repeated_labels_for_runtime_diagonal_normalization() {
         let literal: syn::LitStr = syn::parse_quote!("batch i i i -> batch i");
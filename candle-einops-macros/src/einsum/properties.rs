@@ -71,9 +71,11 @@ fn planning_unwinds(text: &str) -> bool {
         }
 
         for operand_count in 0..=4 {
-            let operands = (0..operand_count).map(|_| quote!(()));
+            let operands = (0..operand_count).map(|_| quote!(())).collect::<Vec<_>>();
             let invocation = quote!(#literal, #(#operands),*);
             let _ = syn::parse2::<Invocation>(invocation);
+            let invocation = quote!(numpy #literal, #(#operands),*);
+            let _ = syn::parse2::<Invocation>(invocation);
         }
     }))
     .is_err()
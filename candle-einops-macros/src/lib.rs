@@ -12,12 +12,48 @@ pub fn einops(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         .into()
 }
 
+/// Applies an einops expression and also returns the named axis lengths
+///
+/// Expands to a `Result<(Tensor, Sizes)>`, where `Sizes` holds the length of
+/// every named input axis. This macro is re-exported as
+/// `candle_einops::einops_with_sizes`.
+#[proc_macro]
+pub fn einops_with_sizes(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    einops::einops_with_sizes(input.into())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Extracts named axis lengths from a tensor's shape
+///
+/// Returns a value with one `usize` field per named axis. `_` skips an axis
+/// and `..` skips any number of axes. This macro is re-exported as
+/// `candle_einops::einops_shape`.
+#[proc_macro]
+pub fn einops_shape(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    einops::einops_shape(input.into())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
 /// Evaluates an explicit-output Einstein summation equation.
 ///
 /// Supports any positive number of operands with named axes and at most one
 /// `..` per axis list. Repeated input labels extract diagonals before
-/// contraction. Use this through `candle_einops::einsum`; generated code calls
-/// a private runtime ABI, so `candle-einops-macros` and `candle-einops` must use
+/// contraction. Output groups such as `mean(t)`, `max(t)`, or `min(t)` keep
+/// their labels through every contraction and then reduce them, in the order
+/// written, instead of summing. A `numpy` prefix, as in
+/// `einsum!(numpy "ij,jk", a, b)`, accepts single-character labels, `...`, and
+/// implicit outputs. Options follow the operands after `;`:
+/// `path = [(1, 2), (0, 1)]` fixes the contraction order in `opt_einsum` form
+/// and is checked against the operand count at compile time,
+/// `accumulate = f32` (or `f64`) runs each GEMM and sum over narrower
+/// floating-point operands in that dtype and casts its result back, and
+/// `promote = widest` and `device = operand(0)` convert operands of different
+/// dtypes or devices once before planning. A leading `semiring = max_plus,`
+/// (or `min_plus` or `log_sum_exp`) replaces the multiply and sum with another
+/// semiring. Use this through `candle_einops::einsum`; generated code calls a
+/// private runtime ABI, so `candle-einops-macros` and `candle-einops` must use
 /// exactly the same version.
 #[proc_macro]
 pub fn einsum(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
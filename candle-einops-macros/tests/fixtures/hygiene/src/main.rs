@@ -1,5 +1,5 @@
 use ::candle_core::{Device, Result, Tensor};
-use ::candle_einops::einops;
+use ::candle_einops::{einops, einops_shape};
 
 struct Vec;
 
@@ -32,5 +32,8 @@ fn main() -> Result<()> {
     let reduced = einops!("sum(..) ->", &tensor)?;
     assert_eq!(reduced.to_scalar::<f32>()?, 15f32);
 
+    let shape = einops_shape!("input .. input_shape", &tensor)?;
+    assert_eq!((shape.input, shape.input_shape), (2, 3));
+
     Ok(())
 }
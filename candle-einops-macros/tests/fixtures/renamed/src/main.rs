@@ -1,5 +1,5 @@
 use candle_core::{Device, Result, Tensor};
-use einops_runtime::{einops, einsum};
+use einops_runtime::{einops, einops_shape, einsum};
 
 fn main() -> Result<()> {
     let vector = Tensor::arange(0f32, 6f32, &Device::Cpu)?;
@@ -13,6 +13,9 @@ fn main() -> Result<()> {
     let composed = einops!("a b -> (a b)", &matrix)?;
     assert_eq!(composed.dims(), &[6]);
 
+    let shape = einops_shape!("a _", &matrix)?;
+    assert_eq!(shape.a, 2);
+
     let transposed = einsum!("rows columns -> columns rows", &matrix)?;
     assert_eq!(transposed.dims(), &[3, 2]);
     let product = einsum!("row inner, inner column -> row column", &matrix, &transposed)?;
@@ -0,0 +1,5 @@
+use candle_einops_macros::einsum;
+
+fn main() {
+    let _ = einsum!("i, i ->", (), (); accumulate = i32);
+}
@@ -0,0 +1,5 @@
+use candle_einops_macros::einsum;
+
+fn main() {
+    let _ = einsum!("b t d, d e -> b median(t) e", (), ());
+}
@@ -0,0 +1,5 @@
+use candle_einops_macros::einsum;
+
+fn main() {
+    let _ = einsum!("a b, b c, c d -> a d", (), (), (); path = [(0, 1)]);
+}
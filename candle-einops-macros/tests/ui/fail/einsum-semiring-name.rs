@@ -0,0 +1,5 @@
+use candle_einops_macros::einsum;
+
+fn main() {
+    let _ = einsum!(semiring = tropical, "b i, i j -> b j", (), ());
+}
@@ -1,5 +1,5 @@
 use candle_einops_macros::einops;
 
 fn main() {
-    let _ = einops!("a:3 b -> b a", ());
+    let _ = einops!("a b -> a b * c:2", ());
 }
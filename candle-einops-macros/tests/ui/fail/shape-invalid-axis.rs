@@ -0,0 +1,5 @@
+use candle_einops_macros::einops_shape;
+
+fn main() {
+    let _ = einops_shape!("b (h w)", ());
+}
@@ -0,0 +1,5 @@
+use candle_einops_macros::einops;
+
+fn main() {
+    let _ = einops!("b sum(c) -> b", (), ());
+}
@@ -0,0 +1,704 @@
+//! Einops expression grammar, such as `b (h w:2) c -> b sum(h) (w c)`.
+//!
+//! [`Expression::parse`] validates the whole expression, so every caller
+//! reports the same diagnostics before it plans anything.
+
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Int(String),
+    Ellipsis,
+    Arrow,
+    Colon,
+    Dot,
+    OpenParen,
+    CloseParen,
+    OpenBrace,
+    CloseBrace,
+    Other(char),
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.char_indices().peekable();
+    while let Some((start, character)) = chars.next() {
+        let token = match character {
+            character if character.is_whitespace() => continue,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '{' => Token::OpenBrace,
+            '}' => Token::CloseBrace,
+            ':' => Token::Colon,
+            '.' if chars.next_if(|&(_, next)| next == '.').is_some() => Token::Ellipsis,
+            '.' => Token::Dot,
+            '-' if chars.next_if(|&(_, next)| next == '>').is_some() => Token::Arrow,
+            character if character.is_ascii_digit() => {
+                let mut end = start + character.len_utf8();
+                while let Some((index, next)) = chars.next_if(|&(_, next)| next.is_ascii_digit()) {
+                    end = index + next.len_utf8();
+                }
+                Token::Int(pattern[start..end].to_string())
+            }
+            character if character.is_alphabetic() || character == '_' => {
+                let mut end = start + character.len_utf8();
+                while let Some((index, next)) =
+                    chars.next_if(|&(_, next)| next.is_alphanumeric() || next == '_')
+                {
+                    end = index + next.len_utf8();
+                }
+                // A lone underscore is a placeholder, not an identifier.
+                if &pattern[start..end] == "_" {
+                    Token::Other('_')
+                } else {
+                    Token::Ident(pattern[start..end].to_string())
+                }
+            }
+            character => Token::Other(character),
+        };
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// The operation of a reduction group such as `sum(h w)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reduction {
+    Min,
+    Max,
+    Sum,
+    Mean,
+    Prod,
+    LogSumExp,
+    Var,
+    Std,
+    Norm,
+    ArgMin,
+    ArgMax,
+    /// A `reduce(...)` group, applied by a caller-supplied reducer
+    Custom,
+    /// A `{op}(...)` group whose operation is the braced identifier or field
+    Braced(String),
+}
+
+impl Reduction {
+    /// Returns `true` for `argmin` and `argmax`, which yield indices.
+    pub fn is_index(&self) -> bool {
+        matches!(self, Reduction::ArgMin | Reduction::ArgMax)
+    }
+
+    /// Returns the reduction written as `name(...)`, if there is one.
+    ///
+    /// Only `min`, `max`, `sum`, `mean`, and `prod` are reserved; the later
+    /// names reduce only when `called`, so they remain usable as axis names.
+    fn keyword(name: &str, called: bool) -> Option<Self> {
+        let reduction = match name {
+            "min" => Reduction::Min,
+            "max" => Reduction::Max,
+            "sum" => Reduction::Sum,
+            "mean" => Reduction::Mean,
+            "prod" => Reduction::Prod,
+            _ if !called => return None,
+            "logsumexp" => Reduction::LogSumExp,
+            "var" => Reduction::Var,
+            "std" => Reduction::Std,
+            "norm" => Reduction::Norm,
+            "argmin" => Reduction::ArgMin,
+            "argmax" => Reduction::ArgMax,
+            "reduce" => Reduction::Custom,
+            _ => return None,
+        };
+        Some(reduction)
+    }
+}
+
+/// The length written for an axis.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AxisSize {
+    Literal(usize),
+    /// A braced identifier or field such as `{n}` or `{config.heads}`, whose
+    /// value is supplied where the expression is applied
+    Braced(String),
+}
+
+/// One axis of a decomposition group such as `(h w:2)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupAxis {
+    /// `None` for anonymous literal axes such as `max(2)`
+    pub name: Option<String>,
+    /// `None` for the one axis derived from the others
+    pub size: Option<AxisSize>,
+    pub reduction: Option<Reduction>,
+}
+
+/// One entry on the left of `->`.
+///
+/// Top-level reduction groups such as `sum(h w)` contribute one entry per
+/// axis.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InputAxis {
+    Named {
+        name: String,
+        /// Asserted length of a standalone `name:size` axis
+        size: Option<usize>,
+        reduction: Option<Reduction>,
+    },
+    Ellipsis {
+        reduction: Option<Reduction>,
+    },
+    /// A decomposition group; a squeezed `1` is an empty group
+    Group(Vec<GroupAxis>),
+}
+
+/// One axis on the right of `->`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutputAxis {
+    /// An axis kept from the left
+    Existing(String),
+    Ellipsis,
+    /// A repeated axis of the given length
+    New(AxisSize),
+}
+
+/// One output dimension, which is either one axis or a composed group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutputGroup {
+    Individual(OutputAxis),
+    Combined(Vec<OutputAxis>),
+}
+
+/// A validated einops expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expression {
+    pub decomposition: Vec<InputAxis>,
+    pub composition: Vec<OutputGroup>,
+    /// Whether a leading `axis *` on the right unbinds the output along its
+    /// first axis
+    pub split: bool,
+}
+
+impl Expression {
+    /// Parses and validates an einops expression.
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let tokens = tokenize(pattern);
+        let Some(arrow) = tokens.iter().position(|token| token == &Token::Arrow) else {
+            return Err("expected `->`".to_string());
+        };
+        let decomposition = parse_decomposition(Cursor {
+            tokens: &tokens[..arrow],
+        })?;
+        check_index_reductions(&decomposition)?;
+        let (right, split) = remove_split_marker(&tokens[arrow + 1..])?;
+        let composition = parse_composition(Cursor { tokens: &right }, &decomposition)?;
+        Ok(Self {
+            decomposition,
+            composition,
+            split,
+        })
+    }
+}
+
+enum ReducedAxis {
+    Named {
+        name: Option<String>,
+        size: Option<AxisSize>,
+    },
+    Ellipsis,
+}
+
+struct Cursor<'a> {
+    tokens: &'a [Token],
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.first()
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let (token, rest) = self.tokens.split_first()?;
+        self.tokens = rest;
+        Some(token)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn parse_usize(&mut self) -> Result<usize, String> {
+        match self.next() {
+            Some(Token::Int(literal)) => {
+                literal.parse::<usize>().map_err(|error| error.to_string())
+            }
+            _ => Err("expected integer literal".to_string()),
+        }
+    }
+
+    /// Parses `name` or `name:size`.
+    fn parse_identifier(&mut self) -> Result<(String, Option<usize>), String> {
+        let Some(Token::Ident(name)) = self.next() else {
+            return Err("expected identifier".to_string());
+        };
+        let size = if self.eat(&Token::Colon) {
+            Some(self.parse_usize()?)
+        } else {
+            None
+        };
+        Ok((name.clone(), size))
+    }
+
+    /// Parses `{name}` or `{value.field}`.
+    fn parse_braced(&mut self) -> Result<String, String> {
+        const EXPECTED: &str = "Only identifiers and fields are allowed inside '{}'";
+        self.next();
+        let mut path = match self.next() {
+            Some(Token::Ident(name)) => name.clone(),
+            _ => return Err(EXPECTED.to_string()),
+        };
+        while self.eat(&Token::Dot) {
+            match self.next() {
+                Some(Token::Ident(field) | Token::Int(field)) => {
+                    path.push('.');
+                    path.push_str(field);
+                }
+                _ => return Err(EXPECTED.to_string()),
+            }
+        }
+        if !self.eat(&Token::CloseBrace) {
+            return Err("Only one identifier or field is allowed inside '{}'".to_string());
+        }
+        Ok(path)
+    }
+
+    /// Collects the tokens up to the matching closing delimiter.
+    fn parenthesized(&mut self) -> Result<Cursor<'a>, String> {
+        self.next();
+        let mut depth = 0usize;
+        for (index, token) in self.tokens.iter().enumerate() {
+            match token {
+                Token::OpenParen => depth += 1,
+                Token::CloseParen if depth == 0 => {
+                    let (content, rest) = self.tokens.split_at(index);
+                    self.tokens = &rest[1..];
+                    return Ok(Cursor { tokens: content });
+                }
+                Token::CloseParen => depth -= 1,
+                _ => {}
+            }
+        }
+        Err("expected parentheses".to_string())
+    }
+
+    /// Returns `true` if a reduction group such as `sum(...)` or
+    /// `{op}(...)` starts here.
+    fn peek_reduce_fn(&self) -> bool {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                Reduction::keyword(name, self.tokens.get(1) == Some(&Token::OpenParen)).is_some()
+            }
+            Some(Token::OpenBrace) => self
+                .tokens
+                .iter()
+                .position(|token| token == &Token::CloseBrace)
+                .is_some_and(|close| self.tokens.get(close + 1) == Some(&Token::OpenParen)),
+            _ => false,
+        }
+    }
+
+    fn parse_reduce_fn(&mut self) -> Result<(Reduction, Vec<ReducedAxis>), String> {
+        let reduction = match self.peek() {
+            Some(Token::OpenBrace) => Reduction::Braced(self.parse_braced()?),
+            Some(Token::Ident(name)) => {
+                self.next();
+                Reduction::keyword(name, true)
+                    .ok_or_else(|| "Expected a reduction operation".to_string())?
+            }
+            _ => return Err("Expected a reduction operation".to_string()),
+        };
+        if self.peek() != Some(&Token::OpenParen) {
+            return Err("expected parentheses".to_string());
+        }
+        let mut content = self.parenthesized()?;
+        if content.is_empty() {
+            return Err("Reduction groups cannot be empty".to_string());
+        }
+
+        let mut axes = Vec::new();
+        while let Some(token) = content.peek() {
+            let axis = match token {
+                Token::Ident(_) => {
+                    let (name, size) = content.parse_identifier()?;
+                    ReducedAxis::Named {
+                        name: Some(name),
+                        size: size.map(AxisSize::Literal),
+                    }
+                }
+                Token::Ellipsis => {
+                    content.next();
+                    ReducedAxis::Ellipsis
+                }
+                Token::Int(_) => ReducedAxis::Named {
+                    name: None,
+                    size: Some(AxisSize::Literal(content.parse_usize()?)),
+                },
+                Token::OpenBrace => {
+                    let name = content.parse_braced()?;
+                    ReducedAxis::Named {
+                        size: Some(AxisSize::Braced(name.clone())),
+                        name: Some(name),
+                    }
+                }
+                _ => return Err("Unknown character introduced in the reduce operation".to_string()),
+            };
+            axes.push(axis);
+        }
+        Ok((reduction, axes))
+    }
+}
+
+fn parse_decomposition(mut input: Cursor) -> Result<Vec<InputAxis>, String> {
+    let mut decomposition = Vec::new();
+    while let Some(token) = input.peek() {
+        if input.peek_reduce_fn() {
+            let (reduction, axes) = input.parse_reduce_fn()?;
+            for axis in axes {
+                decomposition.push(match axis {
+                    ReducedAxis::Named {
+                        name: Some(name),
+                        size: None,
+                    } => InputAxis::Named {
+                        name,
+                        size: None,
+                        reduction: Some(reduction.clone()),
+                    },
+                    ReducedAxis::Named { .. } => {
+                        return Err(
+                            "Axis sizes are not allowed in top-level reductions".to_string()
+                        );
+                    }
+                    ReducedAxis::Ellipsis => InputAxis::Ellipsis {
+                        reduction: Some(reduction.clone()),
+                    },
+                });
+            }
+            continue;
+        }
+        let axis = match token {
+            Token::OpenParen => InputAxis::Group(parse_left_parenthesized(input.parenthesized()?)?),
+            Token::Ident(_) => {
+                let (name, size) = input.parse_identifier()?;
+                InputAxis::Named {
+                    name,
+                    size,
+                    reduction: None,
+                }
+            }
+            Token::Int(literal) => {
+                // '1' is the only literal allowed on the left, it squeezes the axis
+                if input.parse_usize()? != 1 {
+                    return Err(format!(
+                        "Literal Int {literal} not allowed on the left side"
+                    ));
+                }
+                InputAxis::Group(Vec::new())
+            }
+            Token::Ellipsis => {
+                input.next();
+                InputAxis::Ellipsis { reduction: None }
+            }
+            _ => {
+                return Err(
+                    "Unrecognized character found in the left side of the expression".to_string(),
+                );
+            }
+        };
+        decomposition.push(axis);
+    }
+    Ok(decomposition)
+}
+
+fn parse_left_parenthesized(mut content: Cursor) -> Result<Vec<GroupAxis>, String> {
+    const ELLIPSIS: &str = "Ignore symbol '..' not allowed inside brackets on the left";
+    if content.is_empty() {
+        return Err("Decomposition groups cannot be empty".to_string());
+    }
+
+    let mut group = Vec::new();
+    while let Some(token) = content.peek() {
+        if content.peek_reduce_fn() {
+            let (reduction, axes) = content.parse_reduce_fn()?;
+            for axis in axes {
+                let ReducedAxis::Named { name, size } = axis else {
+                    return Err(ELLIPSIS.to_string());
+                };
+                group.push(GroupAxis {
+                    name,
+                    size,
+                    reduction: Some(reduction.clone()),
+                });
+            }
+            continue;
+        }
+        let axis = match token {
+            Token::Ident(_) => {
+                let (name, size) = content.parse_identifier()?;
+                GroupAxis {
+                    name: Some(name),
+                    size: size.map(AxisSize::Literal),
+                    reduction: None,
+                }
+            }
+            Token::Ellipsis => return Err(ELLIPSIS.to_string()),
+            Token::Int(literal) => {
+                return Err(format!(
+                    "Anonymous integer {literal} is not allowed inside brackets on the left"
+                ));
+            }
+            Token::OpenBrace => {
+                let name = content.parse_braced()?;
+                GroupAxis {
+                    size: Some(AxisSize::Braced(name.clone())),
+                    name: Some(name),
+                    reduction: None,
+                }
+            }
+            _ => {
+                return Err(
+                    "Unknown character found inside the brackets of the left expression"
+                        .to_string(),
+                );
+            }
+        };
+        group.push(axis);
+    }
+
+    // We cannot have more than one dimension with unknown shape
+    if group.iter().filter(|axis| axis.size.is_none()).count() > 1 {
+        return Err("Shape information required to complete decomposition".to_string());
+    }
+    group
+        .iter()
+        .filter_map(|axis| match axis.size {
+            Some(AxisSize::Literal(size)) => Some(size),
+            _ => None,
+        })
+        .try_fold(1usize, |product, size| product.checked_mul(size))
+        .ok_or_else(|| "Decomposition size product overflows usize".to_string())?;
+    Ok(group)
+}
+
+/// Index reductions produce one index for all of their axes, so an
+/// expression may use only one kind and no other reductions.
+fn check_index_reductions(decomposition: &[InputAxis]) -> Result<(), String> {
+    let reductions = decomposition.iter().flat_map(|axis| match axis {
+        InputAxis::Named { reduction, .. } | InputAxis::Ellipsis { reduction } => {
+            vec![reduction]
+        }
+        InputAxis::Group(group) => group.iter().map(|axis| &axis.reduction).collect(),
+    });
+    let mut index_reduction = None;
+    let mut value_reduction = false;
+    for reduction in reductions.flatten() {
+        if !reduction.is_index() {
+            value_reduction = true;
+        } else if index_reduction
+            .replace(reduction)
+            .is_some_and(|kind| kind != reduction)
+        {
+            return Err("`argmax` and `argmin` cannot be combined in one expression".to_string());
+        }
+    }
+    if index_reduction.is_some() && value_reduction {
+        return Err("Index reductions cannot be combined with other reductions".to_string());
+    }
+    Ok(())
+}
+
+/// Removes a leading `axis *` split marker from the right side, keeping the
+/// split axis as the first output axis.
+fn remove_split_marker(tokens: &[Token]) -> Result<(Vec<Token>, bool), String> {
+    let is_marker = |token: &Token| token == &Token::Other('*');
+    let Some(marker) = tokens.iter().position(is_marker) else {
+        return Ok((tokens.to_vec(), false));
+    };
+    if tokens[marker + 1..].iter().any(is_marker) {
+        return Err("Split marker `*` may appear only once".to_string());
+    }
+    let leading_axis = match &tokens[..marker] {
+        [Token::Ident(_)] | [Token::Ident(_), Token::Colon, Token::Int(_)] => true,
+        [Token::OpenBrace, inner @ .., Token::CloseBrace] => !inner
+            .iter()
+            .any(|token| matches!(token, Token::OpenBrace | Token::CloseBrace)),
+        _ => false,
+    };
+    if !leading_axis {
+        return Err("Split marker `*` must follow the first output axis".to_string());
+    }
+    let mut tokens = tokens.to_vec();
+    tokens.remove(marker);
+    Ok((tokens, true))
+}
+
+fn parse_composition(
+    mut input: Cursor,
+    decomposition: &[InputAxis],
+) -> Result<Vec<OutputGroup>, String> {
+    let mut left_names = HashSet::new();
+    let mut positions = HashSet::new();
+    for axis in decomposition {
+        let names = match axis {
+            InputAxis::Named {
+                name, reduction, ..
+            } => vec![(name.as_str(), reduction.is_none())],
+            InputAxis::Ellipsis { reduction } => vec![("..", reduction.is_none())],
+            InputAxis::Group(group) => group
+                .iter()
+                .filter_map(|axis| Some((axis.name.as_deref()?, axis.reduction.is_none())))
+                .collect(),
+        };
+        for (name, kept) in names {
+            if !left_names.insert(name) {
+                if name == ".." {
+                    return Err("Ellipsis `..` appears more than once on the left".to_string());
+                }
+                return Err(format!("Axis `{name}` appears more than once on the left"));
+            }
+            // Reduced dimensions cannot be referenced on the right
+            if kept {
+                positions.insert(name);
+            }
+        }
+    }
+
+    let mut consumed = HashSet::new();
+    let mut parse_axis = |input: &mut Cursor| -> Result<OutputAxis, String> {
+        match input.peek() {
+            Some(Token::Ellipsis) => {
+                input.next();
+                if !consumed.insert("..".to_string()) {
+                    return Err("Ellipsis `..` appears more than once on the right".to_string());
+                }
+                if !positions.contains("..") {
+                    return Err(
+                        "Ellipsis `..` must appear on both sides of the expression".to_string()
+                    );
+                }
+                Ok(OutputAxis::Ellipsis)
+            }
+            Some(Token::Ident(_)) => {
+                let (name, size) = input.parse_identifier()?;
+                let axis = if positions.contains(name.as_str()) {
+                    if size.is_some() {
+                        return Err(format!(
+                            "Axis `{name}` cannot be assigned a size on the right"
+                        ));
+                    }
+                    OutputAxis::Existing(name.clone())
+                } else {
+                    // New identifiers represent repetition
+                    let size =
+                        size.ok_or_else(|| format!("New axis `{name}` requires an explicit size"))?;
+                    OutputAxis::New(AxisSize::Literal(size))
+                };
+                if !consumed.insert(name.clone()) {
+                    return Err(format!("Axis `{name}` appears more than once on the right"));
+                }
+                Ok(axis)
+            }
+            // Literal ints represent repetition
+            Some(Token::Int(_)) => Ok(OutputAxis::New(AxisSize::Literal(input.parse_usize()?))),
+            Some(Token::OpenBrace) => {
+                let name = input.parse_braced()?;
+                if !consumed.insert(name.clone()) {
+                    return Err(format!("Axis `{name}` appears more than once on the right"));
+                }
+                if positions.contains(name.as_str()) {
+                    Ok(OutputAxis::Existing(name))
+                } else {
+                    Ok(OutputAxis::New(AxisSize::Braced(name)))
+                }
+            }
+            _ => Err("Unrecognized character on the right side of the expression".to_string()),
+        }
+    };
+
+    let mut composition = Vec::new();
+    while !input.is_empty() {
+        if input.peek() == Some(&Token::OpenParen) {
+            let mut content = input.parenthesized()?;
+            if content.is_empty() {
+                return Err("Composition groups cannot be empty".to_string());
+            }
+            let mut group = Vec::new();
+            while !content.is_empty() {
+                group.push(parse_axis(&mut content)?);
+            }
+            composition.push(OutputGroup::Combined(group));
+        } else {
+            composition.push(OutputGroup::Individual(parse_axis(&mut input)?));
+        }
+    }
+
+    // We raise an error if the right side of the expression
+    // misses an identifier from the left side
+    let mut missing = positions
+        .iter()
+        .filter(|name| !consumed.contains(**name))
+        .copied()
+        .collect::<Vec<_>>();
+    missing.sort();
+    if !missing.is_empty() {
+        return Err(format!("Axes missing on the right: {}", missing.join(", ")));
+    }
+
+    Ok(composition)
+}
+
+/// Parses a list of named axes and parenthesized groups of named axes, such
+/// as one side of an `EinMix` pattern.
+pub fn parse_axis_groups(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let tokens = tokenize(text);
+    let mut input = Cursor { tokens: &tokens };
+    let mut groups = Vec::new();
+    while let Some(token) = input.peek() {
+        let group = match token {
+            Token::Ident(name) => {
+                input.next();
+                vec![name.clone()]
+            }
+            Token::OpenParen => {
+                let mut content = input.parenthesized()?;
+                let mut group = Vec::new();
+                while let Some(token) = content.next() {
+                    let Token::Ident(name) = token else {
+                        return Err(format!(
+                            "Only identifiers are allowed inside a group in `{text}`"
+                        ));
+                    };
+                    group.push(name.clone());
+                }
+                if group.is_empty() {
+                    return Err(format!("Empty groups are not allowed in `{text}`"));
+                }
+                group
+            }
+            _ => {
+                return Err(format!(
+                    "Only identifiers and parenthesized groups are allowed in `{text}`"
+                ));
+            }
+        };
+        groups.push(group);
+    }
+    Ok(groups)
+}
@@ -0,0 +1,173 @@
+//! Einops and einsum grammar shared by `candle-einops` and
+//! `candle-einops-macros`.
+//!
+//! Runtime patterns and equations are parsed by the same functions as
+//! `einops!` and `einsum!` literals, so both accept the same text and report
+//! the same diagnostics. Errors are messages that each caller wraps in its
+//! own error type. This crate is an implementation detail of `candle-einops`
+//! and has no stable API; depend on `candle-einops` instead.
+
+pub mod einops;
+
+/// Checks that `label` is a letter or `_` followed by letters, digits, or `_`.
+pub fn validate_label(label: &str) -> Result<(), String> {
+    let mut characters = label.chars();
+    let valid_start = characters
+        .next()
+        .is_some_and(|character| character == '_' || character.is_alphabetic());
+    if !valid_start || !characters.all(|character| character == '_' || character.is_alphanumeric())
+    {
+        return Err(format!("invalid einsum axis label `{label}`"));
+    }
+    Ok(())
+}
+
+/// How a label annotated as `mean(t)`, `max(t)`, or `min(t)` in an einsum
+/// output is reduced.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum LabelReduction {
+    Mean,
+    Max,
+    Min,
+}
+
+impl LabelReduction {
+    /// Returns the reduction written as `name(...)`, if there is one.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "mean" => Some(Self::Mean),
+            "max" => Some(Self::Max),
+            "min" => Some(Self::Min),
+            _ => None,
+        }
+    }
+
+    /// Returns the name the reduction is written with.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mean => "mean",
+            Self::Max => "max",
+            Self::Min => "min",
+        }
+    }
+}
+
+/// One output group such as `mean(t s)` and the labels it reduces.
+pub type ReductionGroup = (LabelReduction, Vec<String>);
+
+/// Removes the `mean(...)`, `max(...)`, and `min(...)` groups from an output
+/// axis list, returning the remaining text and the labels of each group.
+pub fn split_label_reductions(text: &str) -> Result<(String, Vec<ReductionGroup>), String> {
+    let mut remaining = text;
+    let mut kept = String::with_capacity(text.len());
+    let mut groups = Vec::new();
+    while let Some(open) = remaining.find('(') {
+        let before = &remaining[..open];
+        let name_start = before
+            .char_indices()
+            .rev()
+            .find(|(_, character)| character.is_whitespace())
+            .map_or(0, |(index, character)| index + character.len_utf8());
+        let name = &before[name_start..];
+        let reduction = LabelReduction::parse(name).ok_or_else(|| {
+            format!("einsum label reduction must be `mean`, `max`, or `min`, found `{name}`")
+        })?;
+        let inner = &remaining[open + 1..];
+        let close = match inner.find(['(', ')']) {
+            Some(close) if inner[close..].starts_with(')') => close,
+            Some(_) => return Err("einsum label reductions cannot be nested".to_string()),
+            None => return Err(format!("einsum `{name}(` must be closed by `)`")),
+        };
+        let labels = inner[..close]
+            .split_whitespace()
+            .map(|label| match label {
+                ".." => Err("einsum label reductions cannot contain `..`".to_string()),
+                label => validate_label(label).map(|()| label.to_owned()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if labels.is_empty() {
+            return Err(format!("einsum `{name}(...)` must name at least one label"));
+        }
+        groups.push((reduction, labels));
+        kept.push_str(&before[..name_start]);
+        kept.push(' ');
+        remaining = &inner[close + 1..];
+    }
+    if remaining.contains(')') {
+        return Err("einsum output contains `)` without a matching `(`".to_string());
+    }
+    kept.push_str(remaining);
+    Ok((kept, groups))
+}
+
+/// Rewrites a NumPy-style equation, where every character is a label and
+/// `...` is the ellipsis, into the whitespace-delimited grammar.
+///
+/// Without `->` the output lists, after any ellipsis, the labels that occur
+/// exactly once in the inputs in sorted order.
+pub fn rewrite_numpy_equation(text: &str) -> Result<String, String> {
+    let (input_text, output_text) = match text.split_once("->") {
+        Some((_, output)) if output.contains("->") => {
+            return Err("einsum equation contains more than one `->`".to_string());
+        }
+        Some((inputs, output)) => (inputs, Some(output)),
+        None => (text, None),
+    };
+    let inputs = input_text
+        .split(',')
+        .map(parse_numpy_subscripts)
+        .collect::<Result<Vec<_>, _>>()?;
+    let output = match output_text {
+        Some(output) if output.contains(',') => {
+            return Err("einsum output must be one axis list".to_string());
+        }
+        Some(output) => parse_numpy_subscripts(output)?,
+        None => {
+            let labels = inputs.iter().flatten().flatten().collect::<Vec<_>>();
+            let mut once = labels
+                .iter()
+                .filter(|label| labels.iter().filter(|other| other == label).count() == 1)
+                .map(|label| Some(**label))
+                .collect::<Vec<_>>();
+            once.sort();
+            if inputs.iter().flatten().any(Option::is_none) {
+                once.insert(0, None);
+            }
+            once
+        }
+    };
+
+    let axis_list = |subscripts: &[Option<char>]| {
+        subscripts
+            .iter()
+            .map(|label| label.map_or_else(|| "..".to_string(), String::from))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let inputs = inputs
+        .iter()
+        .map(|subscripts| axis_list(subscripts))
+        .collect::<Vec<_>>();
+    Ok(format!("{} -> {}", inputs.join(", "), axis_list(&output)))
+}
+
+/// Parses one NumPy subscript list, with `None` for the ellipsis.
+fn parse_numpy_subscripts(text: &str) -> Result<Vec<Option<char>>, String> {
+    let mut subscripts = Vec::new();
+    let mut characters = text.chars().filter(|character| !character.is_whitespace());
+    while let Some(character) = characters.next() {
+        match character {
+            '.' if characters.next() == Some('.') && characters.next() == Some('.') => {
+                subscripts.push(None)
+            }
+            '.' => return Err("einsum ellipsis must be written `...`".to_string()),
+            character if character.is_ascii_alphabetic() => subscripts.push(Some(character)),
+            character => {
+                return Err(format!(
+                    "invalid einsum subscript `{character}`; subscripts must be ASCII letters"
+                ));
+            }
+        }
+    }
+    Ok(subscripts)
+}
@@ -71,6 +71,28 @@ def _einsum_pattern(pattern: str, ranks: list[int]) -> str:
     return f"{','.join(expanded_inputs)} -> {right.replace('...', ' '.join(labels))}"
 
 
+def _logsumexp(tensor: np.ndarray, axes: tuple[int, ...]) -> np.ndarray:
+    """Max-shifted log-sum-exp that keeps all-infinite slices finite-safe."""
+
+    maximum = np.max(tensor, axis=axes, keepdims=True)
+    shift = np.where(np.isfinite(maximum), maximum, 0)
+    summed = np.sum(np.exp(tensor - shift), axis=axes, keepdims=True)
+    with np.errstate(divide="ignore"):
+        logarithm = np.log(summed)
+    return np.squeeze(logarithm + shift, axis=axes)
+
+
+# Reductions that einops does not name are passed as NumPy callables, which
+# einops invokes with the tuple of reduced axes. Variance is the population
+# variance, matching the Rust `var` and `std` reductions.
+CALLABLE_REDUCTIONS = {
+    "logsumexp": _logsumexp,
+    "var": lambda tensor, axes: np.var(tensor, axis=axes),
+    "std": lambda tensor, axes: np.std(tensor, axis=axes),
+    "norm": lambda tensor, axes: np.sqrt(np.sum(np.square(tensor), axis=axes)),
+}
+
+
 def evaluate_request(request: Mapping[str, Any]) -> dict[str, Any]:
     """Evaluate one request with einops, returning strict-JSON data."""
 
@@ -100,10 +122,11 @@ def evaluate_request(request: Mapping[str, Any]) -> dict[str, Any]:
             elif operation == "repeat":
                 result = einops.repeat(tensor, pattern, **axes_lengths)
             elif operation == "reduce":
+                reduction = request["reduction"]
                 result = einops.reduce(
                     tensor,
                     pattern,
-                    request["reduction"],
+                    CALLABLE_REDUCTIONS.get(reduction, reduction),
                     **axes_lengths,
                 )
             else:
@@ -57,6 +57,10 @@ enum Reduction {
     Min,
     Max,
     Product,
+    LogSumExp,
+    Variance,
+    StandardDeviation,
+    Norm,
 }
 
 impl Reduction {
@@ -67,6 +71,10 @@ impl Reduction {
             Self::Min => "min",
             Self::Max => "max",
             Self::Product => "prod",
+            Self::LogSumExp => "logsumexp",
+            Self::Variance => "var",
+            Self::StandardDeviation => "std",
+            Self::Norm => "norm",
         }
     }
 
@@ -170,7 +178,10 @@ fn reduction_strategy(
     max_elements: usize,
 ) -> BoxedStrategy<ReductionCase> {
     let extent = max_extent(max_elements);
-    let extents = if matches!(reduction, Reduction::Sum | Reduction::Product) {
+    let extents = if matches!(
+        reduction,
+        Reduction::Sum | Reduction::Product | Reduction::Norm
+    ) {
         (0..=extent, 0..=extent, 0..=extent).boxed()
     } else {
         (1..=extent, 1..=extent, 1..=extent).boxed()
@@ -279,6 +290,44 @@ fn rust_reduce(case: &ReductionCase, input: &Tensor) -> candle_core::Result<Tens
         (Reduction::Product, ReductionLayout::Grouped) => {
             einops!("a (prod(b) c:2) -> a c", input)
         }
+        (Reduction::LogSumExp, ReductionLayout::One) => einops!("a logsumexp(b) c -> a c", input),
+        (Reduction::LogSumExp, ReductionLayout::Consecutive) => {
+            einops!("a logsumexp(b c) -> a", input)
+        }
+        (Reduction::LogSumExp, ReductionLayout::All) => einops!("logsumexp(..) ->", input),
+        (Reduction::LogSumExp, ReductionLayout::Ellipsis) => {
+            einops!("a logsumexp(..) -> a", input)
+        }
+        (Reduction::LogSumExp, ReductionLayout::Grouped) => {
+            einops!("a (logsumexp(b) c:2) -> a c", input)
+        }
+        (Reduction::Variance, ReductionLayout::One) => einops!("a var(b) c -> a c", input),
+        (Reduction::Variance, ReductionLayout::Consecutive) => einops!("a var(b c) -> a", input),
+        (Reduction::Variance, ReductionLayout::All) => einops!("var(..) ->", input),
+        (Reduction::Variance, ReductionLayout::Ellipsis) => einops!("a var(..) -> a", input),
+        (Reduction::Variance, ReductionLayout::Grouped) => {
+            einops!("a (var(b) c:2) -> a c", input)
+        }
+        (Reduction::StandardDeviation, ReductionLayout::One) => {
+            einops!("a std(b) c -> a c", input)
+        }
+        (Reduction::StandardDeviation, ReductionLayout::Consecutive) => {
+            einops!("a std(b c) -> a", input)
+        }
+        (Reduction::StandardDeviation, ReductionLayout::All) => einops!("std(..) ->", input),
+        (Reduction::StandardDeviation, ReductionLayout::Ellipsis) => {
+            einops!("a std(..) -> a", input)
+        }
+        (Reduction::StandardDeviation, ReductionLayout::Grouped) => {
+            einops!("a (std(b) c:2) -> a c", input)
+        }
+        (Reduction::Norm, ReductionLayout::One) => einops!("a norm(b) c -> a c", input),
+        (Reduction::Norm, ReductionLayout::Consecutive) => einops!("a norm(b c) -> a", input),
+        (Reduction::Norm, ReductionLayout::All) => einops!("norm(..) ->", input),
+        (Reduction::Norm, ReductionLayout::Ellipsis) => einops!("a norm(..) -> a", input),
+        (Reduction::Norm, ReductionLayout::Grouped) => {
+            einops!("a (norm(b) c:2) -> a c", input)
+        }
     }
 }
 
@@ -418,6 +467,10 @@ fn randomized_reductions_match_locked_python_einops() {
             Reduction::Min,
             Reduction::Max,
             Reduction::Product,
+            Reduction::LogSumExp,
+            Reduction::Variance,
+            Reduction::StandardDeviation,
+            Reduction::Norm,
         ] {
             runner
                 .run(
@@ -1,8 +1,10 @@
 import io
 import json
+import math
 import sys
 import unittest
 from pathlib import Path
+from typing import Any
 
 import numpy as np
 from hypothesis import given, settings, strategies as st
@@ -65,6 +67,27 @@ class OracleContractTests(unittest.TestCase):
         self.assertEqual(reduced["shape"], [2])
         self.assertEqual(reduced["values"], [2.0, 6.0])
 
+    def test_statistical_reductions_use_numpy_callables(self) -> None:
+        def reduce(reduction: str, values: list[float]) -> dict[str, Any]:
+            return evaluate_request(
+                {
+                    "case_id": reduction,
+                    "operation": "reduce",
+                    "pattern": "rows columns -> rows",
+                    "reduction": reduction,
+                    "shape": [2, 2],
+                    "values": values,
+                    "axes_lengths": {},
+                }
+            )
+
+        self.assertEqual(reduce("var", [1.0, 3.0, 5.0, 9.0])["values"], [1.0, 4.0])
+        self.assertEqual(reduce("std", [1.0, 3.0, 5.0, 9.0])["values"], [1.0, 2.0])
+        self.assertEqual(reduce("norm", [3.0, 4.0, 0.0, 0.0])["values"], [5.0, 0.0])
+        logsumexp = reduce("logsumexp", [1000.0, 1000.0, -math.inf, -math.inf])
+        self.assertAlmostEqual(logsumexp["values"][0], 1000.0 + math.log(2.0))
+        self.assertEqual(logsumexp["values"][1], "-inf")
+
     def test_einsum_accepts_multiple_operands_and_omitted_ellipsis(self) -> None:
         matrix_product = evaluate_request(
             {
@@ -1,11 +1,11 @@
 use candle_core::{Result, Shape, Tensor};
 
-use crate::Operation;
+use crate::{IndexOperation, Operation, ReduceStep};
 
 #[derive(Debug)]
 struct ReductionRun {
     axes: Vec<usize>,
-    operation: Operation,
+    step: ReduceStep,
 }
 
 fn operations_are_fusible(left: Operation, right: Operation) -> bool {
@@ -15,16 +15,47 @@ fn operations_are_fusible(left: Operation, right: Operation) -> bool {
             | (Operation::Mean, Operation::Mean)
             | (Operation::Min, Operation::Min)
             | (Operation::Max, Operation::Max)
+            | (Operation::LogSumExp, Operation::LogSumExp)
+            | (Operation::Var, Operation::Var)
+            | (Operation::Std, Operation::Std)
+            | (Operation::Norm, Operation::Norm)
     )
 }
 
-fn plan_reduction_runs(axes_operations: &mut [(usize, Operation)]) -> Vec<ReductionRun> {
-    axes_operations.sort_by_key(|(axis, _)| *axis);
+fn steps_are_fusible(left: ReduceStep, right: ReduceStep) -> bool {
+    match (left, right) {
+        (ReduceStep::Operation(left), ReduceStep::Operation(right)) => {
+            operations_are_fusible(left, right)
+        }
+        (ReduceStep::Custom, ReduceStep::Custom) => true,
+        _ => false,
+    }
+}
+
+/// Steps computed over a set of axes in one call, so their runs may span
+/// non-adjacent axes.
+fn reduces_axis_sets(step: ReduceStep) -> bool {
+    match step {
+        ReduceStep::Operation(operation) => matches!(
+            operation,
+            Operation::Sum
+                | Operation::Mean
+                | Operation::LogSumExp
+                | Operation::Var
+                | Operation::Std
+                | Operation::Norm
+        ),
+        ReduceStep::Custom => true,
+    }
+}
+
+fn plan_reduction_runs(axes_steps: &mut [(usize, ReduceStep)]) -> Vec<ReductionRun> {
+    axes_steps.sort_by_key(|(axis, _)| *axis);
     let mut runs: Vec<ReductionRun> = Vec::new();
-    for &(axis, operation) in axes_operations.iter().rev() {
+    for &(axis, step) in axes_steps.iter().rev() {
         if let Some(run) = runs.last_mut()
-            && operations_are_fusible(run.operation, operation)
-            && (matches!(operation, Operation::Sum | Operation::Mean)
+            && steps_are_fusible(run.step, step)
+            && (reduces_axis_sets(step)
                 || run
                     .axes
                     .last()
@@ -34,18 +65,39 @@ fn plan_reduction_runs(axes_operations: &mut [(usize, Operation)]) -> Vec<Reduct
         } else {
             runs.push(ReductionRun {
                 axes: vec![axis],
-                operation,
+                step,
             });
         }
     }
     for run in &mut runs {
-        if matches!(run.operation, Operation::Sum | Operation::Mean) {
+        if reduces_axis_sets(run.step) {
             run.axes.reverse();
         }
     }
     runs
 }
 
+/// Returns the built-in operations of `axes_steps`, or `None` if any step
+/// needs a [`Reducer`].
+pub(crate) fn built_in_operations(
+    axes_steps: &[(usize, ReduceStep)],
+) -> Option<Vec<(usize, Operation)>> {
+    axes_steps
+        .iter()
+        .map(|&(axis, step)| match step {
+            ReduceStep::Operation(operation) => Some((axis, operation)),
+            ReduceStep::Custom => None,
+        })
+        .collect()
+}
+
+pub(crate) fn built_in_steps(axes_operations: &[(usize, Operation)]) -> Vec<(usize, ReduceStep)> {
+    axes_operations
+        .iter()
+        .map(|&(axis, operation)| (axis, operation.into()))
+        .collect()
+}
+
 #[cfg(test)]
 std::thread_local! {
     static BACKEND_REDUCTION_CALL_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
@@ -69,6 +121,40 @@ fn backend_reduction_call_count() -> usize {
     BACKEND_REDUCTION_CALL_COUNT.get()
 }
 
+/// A user-defined reduction for `reduce(...)` groups in einops patterns.
+///
+/// `reduce` receives the axes of one run in ascending order and must remove
+/// them, returning a tensor with the remaining axes in their original order.
+///
+/// ```
+/// use candle_core::{Device, Result, Tensor};
+/// use candle_einops::{Reducer, einops};
+///
+/// struct GeneralizedMean(f64);
+///
+/// impl Reducer for GeneralizedMean {
+///     fn reduce(&self, tensor: &Tensor, axes: &[usize]) -> Result<Tensor> {
+///         tensor.powf(self.0)?.mean(axes)?.powf(1. / self.0)
+///     }
+/// }
+///
+/// # fn main() -> Result<()> {
+/// let input = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+/// let output = einops!("b reduce(c) -> b", &input, &GeneralizedMean(3.))?;
+/// assert_eq!(output.dims(), &[2]);
+/// # Ok(())
+/// # }
+/// ```
+pub trait Reducer {
+    fn reduce(&self, tensor: &Tensor, axes: &[usize]) -> Result<Tensor>;
+}
+
+impl<R: Reducer + ?Sized> Reducer for &R {
+    fn reduce(&self, tensor: &Tensor, axes: &[usize]) -> Result<Tensor> {
+        (**self).reduce(tensor, axes)
+    }
+}
+
 /// Tensor operations used by [`crate::einops!`].
 ///
 /// Transformations return Candle [`Result`] values so backend failures retain
@@ -112,10 +198,52 @@ pub trait Backend {
         Backend::reshape(output, output_shape)
     }
     fn reduce_axes(self, axes_operations: &mut [(usize, Operation)]) -> Result<Self::Output>;
+    /// Reduces like [`Backend::reduce_axes`], running [`ReduceStep::Custom`]
+    /// axes through `reducer`, so custom and built-in reductions can mix.
+    ///
+    /// The default accepts only built-in operations, so third-party backends
+    /// only need this to support custom reducers.
+    fn reduce_axes_with(
+        self,
+        axes_steps: &mut [(usize, ReduceStep)],
+        _reducer: &dyn Reducer,
+    ) -> Result<Self::Output>
+    where
+        Self: Sized,
+    {
+        let Some(mut axes_operations) = built_in_operations(axes_steps) else {
+            candle_core::bail!("this backend does not support custom reducers")
+        };
+        self.reduce_axes(&mut axes_operations)
+    }
+    /// Replaces a group of axes with the `u32` index of its minimum or
+    /// maximum, as in `"b argmax(h w) -> b"`.
+    ///
+    /// `axes` lists the group in pattern order and the index is flat over the
+    /// group in that order. This is separate from [`Backend::reduce_axes`]
+    /// because the output dtype differs from the input. The default returns
+    /// an error, so third-party backends only need this for index reductions.
+    fn arg_reduce_axes(self, _axes: &[usize], _operation: IndexOperation) -> Result<Self::Output>
+    where
+        Self: Sized,
+    {
+        candle_core::bail!("this backend does not support index reductions")
+    }
     /// Inserts new axes as broadcast views.
     ///
     /// The returned tensor can be non-contiguous and can alias the input.
     fn add_axes(self, naxes: usize, pos2len: &[(usize, usize)]) -> Result<Self::Output>;
+    /// Splits a tensor along its leading axis for split outputs such as
+    /// `"b (three:3 d) -> three * b d"`.
+    ///
+    /// Tensor backends return views of the input. The default returns an
+    /// error, so third-party backends only need this to support splitting.
+    fn unbind(self) -> Result<Vec<Self::Output>>
+    where
+        Self: Sized,
+    {
+        candle_core::bail!("this backend does not support split outputs")
+    }
 }
 
 impl<T: AsRef<Tensor>> Backend for T {
@@ -159,67 +287,73 @@ impl<T: AsRef<Tensor>> Backend for T {
     }
 
     fn reduce_axes(self, axes_operations: &mut [(usize, Operation)]) -> Result<Self::Output> {
-        let mut output = self.as_ref().clone();
-        let mut occupied = vec![false; output.rank()];
+        reduce_tensor_axes(self.as_ref(), &mut built_in_steps(axes_operations), None)
+    }
+
+    fn reduce_axes_with(
+        self,
+        axes_steps: &mut [(usize, ReduceStep)],
+        reducer: &dyn Reducer,
+    ) -> Result<Self::Output> {
+        reduce_tensor_axes(self.as_ref(), axes_steps, Some(reducer))
+    }
 
-        for &(axis, _) in axes_operations.iter() {
+    fn arg_reduce_axes(self, axes: &[usize], operation: IndexOperation) -> Result<Self::Output> {
+        let input = self.as_ref();
+        let mut occupied = vec![false; input.rank()];
+        for &axis in axes {
             if axis >= occupied.len() {
                 candle_core::bail!(
-                    "reduce_axes: axis {axis} out of range for rank {}",
+                    "arg_reduce_axes: axis {axis} out of range for rank {}",
                     occupied.len()
                 )
             }
             if occupied[axis] {
-                candle_core::bail!("reduce_axes: duplicate axis {axis}")
+                candle_core::bail!("arg_reduce_axes: duplicate axis {axis}")
             }
             occupied[axis] = true;
         }
+        let Some(&first) = axes.first() else {
+            candle_core::bail!("arg_reduce_axes: expected at least one axis")
+        };
 
-        for run in plan_reduction_runs(axes_operations) {
-            output = match run.operation {
-                Operation::Min | Operation::Max if run.axes.len() > 1 => {
-                    if let Some(collapsed) =
-                        collapse_extrema_run(&output, &run.axes, run.operation)?
-                    {
-                        record_backend_reduction_call();
-                        collapsed
-                    } else {
-                        let mut reduced = output;
-                        for axis in run.axes {
-                            record_backend_reduction_call();
-                            reduced = match run.operation {
-                                Operation::Min => reduced.min(axis)?,
-                                Operation::Max => reduced.max(axis)?,
-                                _ => unreachable!("extrema run operation"),
-                            };
-                        }
-                        reduced
-                    }
-                }
-                Operation::Min => {
-                    record_backend_reduction_call();
-                    output.min(run.axes[0])?
-                }
-                Operation::Max => {
-                    record_backend_reduction_call();
-                    output.max(run.axes[0])?
-                }
-                Operation::Sum => {
-                    record_backend_reduction_call();
-                    output.sum(run.axes.as_slice())?
-                }
-                Operation::Mean => {
-                    record_backend_reduction_call();
-                    output.mean(run.axes.as_slice())?
-                }
-                Operation::Prod => {
-                    record_backend_reduction_call();
-                    reduce_product_axis(&output, run.axes[0])?
-                }
-            };
+        // Candle takes the index along one axis, so a group is first composed
+        // into a single axis, which is a view when its axes are adjacent
+        let (grouped, axis) = if axes.len() == 1 {
+            (input.clone(), first)
+        } else {
+            let position = (0..first).filter(|&axis| !occupied[axis]).count();
+            let mut permutation = (0..first)
+                .filter(|&axis| !occupied[axis])
+                .collect::<Vec<_>>();
+            permutation.extend_from_slice(axes);
+            permutation.extend((first..input.rank()).filter(|&axis| !occupied[axis]));
+            let mut group_lengths = vec![1; input.rank() - axes.len() + 1];
+            group_lengths[position] = axes.len();
+            let mut output_shape = permutation
+                .iter()
+                .map(|&axis| input.dims()[axis])
+                .collect::<Vec<_>>();
+            output_shape.splice(
+                position..position + axes.len(),
+                [checked_axis_product(input.dims(), axes)?],
+            );
+            let grouped = execute_tensor_permute_and_compose(
+                input,
+                &permutation,
+                &output_shape,
+                &group_lengths,
+            )?;
+            (grouped, position)
+        };
+        if grouped.dim(axis)? == 0 {
+            candle_core::bail!("arg_reduce_axes: cannot take an index over an empty group")
+        }
+        record_backend_reduction_call();
+        match operation {
+            IndexOperation::ArgMin => grouped.argmin(axis),
+            IndexOperation::ArgMax => grouped.argmax(axis),
         }
-
-        Ok(output)
     }
 
     fn add_axes(self, naxes: usize, pos2len: &[(usize, usize)]) -> Result<Self::Output> {
@@ -278,6 +412,154 @@ impl<T: AsRef<Tensor>> Backend for T {
 
         expanded.broadcast_as(Shape::from_dims(&final_shape))
     }
+
+    fn unbind(self) -> Result<Vec<Self::Output>> {
+        let input = self.as_ref();
+        let Some(&length) = input.dims().first() else {
+            candle_core::bail!("unbind: cannot split a rank 0 tensor")
+        };
+        // `Tensor::get` reshapes and would copy strided inputs
+        (0..length)
+            .map(|index| input.narrow(0, index, 1)?.squeeze(0))
+            .collect()
+    }
+}
+
+/// Applies every `(axis, step)` reduction of `axes_steps` to `input`.
+fn reduce_tensor_axes(
+    input: &Tensor,
+    axes_steps: &mut [(usize, ReduceStep)],
+    reducer: Option<&dyn Reducer>,
+) -> Result<Tensor> {
+    let mut output = input.clone();
+    let mut occupied = vec![false; output.rank()];
+
+    for &(axis, _) in axes_steps.iter() {
+        if axis >= occupied.len() {
+            candle_core::bail!(
+                "reduce_axes: axis {axis} out of range for rank {}",
+                occupied.len()
+            )
+        }
+        if occupied[axis] {
+            candle_core::bail!("reduce_axes: duplicate axis {axis}")
+        }
+        occupied[axis] = true;
+    }
+
+    for run in plan_reduction_runs(axes_steps) {
+        let operation = match run.step {
+            ReduceStep::Operation(operation) => operation,
+            ReduceStep::Custom => {
+                let Some(reducer) = reducer else {
+                    unreachable!("`reduce_axes` passes only built-in operations")
+                };
+                record_backend_reduction_call();
+                output = apply_reducer(reducer, &output, &run.axes)?;
+                continue;
+            }
+        };
+        output = match operation {
+            Operation::Min | Operation::Max if run.axes.len() > 1 => {
+                if let Some(collapsed) = collapse_extrema_run(&output, &run.axes, operation)? {
+                    record_backend_reduction_call();
+                    collapsed
+                } else {
+                    let mut reduced = output;
+                    for axis in run.axes {
+                        record_backend_reduction_call();
+                        reduced = match operation {
+                            Operation::Min => reduced.min(axis)?,
+                            Operation::Max => reduced.max(axis)?,
+                            _ => unreachable!("extrema run operation"),
+                        };
+                    }
+                    reduced
+                }
+            }
+            Operation::Min => {
+                record_backend_reduction_call();
+                output.min(run.axes[0])?
+            }
+            Operation::Max => {
+                record_backend_reduction_call();
+                output.max(run.axes[0])?
+            }
+            Operation::Sum => {
+                record_backend_reduction_call();
+                output.sum(run.axes.as_slice())?
+            }
+            Operation::Mean => {
+                record_backend_reduction_call();
+                output.mean(run.axes.as_slice())?
+            }
+            Operation::Prod => {
+                record_backend_reduction_call();
+                reduce_product_axis(&output, run.axes[0])?
+            }
+            Operation::LogSumExp => {
+                record_backend_reduction_call();
+                reduce_log_sum_exp(&output, &run.axes)?
+            }
+            Operation::Var => {
+                record_backend_reduction_call();
+                reduce_variance(&output, &run.axes)?
+            }
+            Operation::Std => {
+                record_backend_reduction_call();
+                reduce_variance(&output, &run.axes)?.sqrt()?
+            }
+            Operation::Norm => {
+                record_backend_reduction_call();
+                output.sqr()?.sum(run.axes.as_slice())?.sqrt()?
+            }
+        };
+    }
+
+    Ok(output)
+}
+
+fn apply_reducer(reducer: &dyn Reducer, input: &Tensor, axes: &[usize]) -> Result<Tensor> {
+    let output = reducer.reduce(input, axes)?;
+    let expected = input
+        .dims()
+        .iter()
+        .enumerate()
+        .filter(|(axis, _)| !axes.contains(axis))
+        .map(|(_, &extent)| extent)
+        .collect::<Vec<_>>();
+    if output.dims() != expected {
+        candle_core::bail!(
+            "custom reducer returned shape {:?} for axes {axes:?} of {:?}, expected {expected:?}",
+            output.dims(),
+            input.dims()
+        )
+    }
+    Ok(output)
+}
+
+/// Computes `log(sum(exp(x)))` over `axes` after shifting by the maximum.
+///
+/// Slices whose maximum is infinite are shifted by zero instead, so all
+/// `-inf` slices reduce to `-inf` and slices containing `inf` to `inf` rather
+/// than NaN. The shift cancels in the result, so it is detached and gradients
+/// flow only through the exponentials.
+pub(crate) fn reduce_log_sum_exp(input: &Tensor, axes: &[usize]) -> Result<Tensor> {
+    let mut maximum = input.detach();
+    for &axis in axes {
+        maximum = maximum.max_keepdim(axis)?;
+    }
+    let finite = maximum.abs()?.lt(f64::INFINITY)?;
+    let shift = finite.where_cond(&maximum, &maximum.zeros_like()?)?;
+    let summed = input.broadcast_sub(&shift)?.exp()?.sum(axes)?;
+    summed.log()?.add(&shift.reshape(summed.dims())?)
+}
+
+/// Computes the population variance over `axes` from explicit deviations,
+/// which avoids the cancellation of `E[x²] - E[x]²`.
+fn reduce_variance(input: &Tensor, axes: &[usize]) -> Result<Tensor> {
+    let mean = input.mean_keepdim(axes)?;
+    input.broadcast_sub(&mean)?.sqr()?.mean(axes)
 }
 
 fn reduce_product_axis(input: &Tensor, axis: usize) -> Result<Tensor> {
@@ -576,49 +858,88 @@ mod tests {
 
     #[test]
     fn plans_only_adjacent_homogeneous_sum_and_mean_runs() {
-        let mut sums = [
+        let sums = [
             (0, Operation::Sum),
             (1, Operation::Sum),
             (2, Operation::Sum),
         ];
-        let runs = plan_reduction_runs(&mut sums);
+        let runs = plan_reduction_runs(&mut built_in_steps(&sums));
         assert_eq!(runs.len(), 1);
         assert_eq!(
             runs[0].axes,
             [0, 1, 2],
             "fused dimensions must preserve physical stride order for GPU fast-reduce kernels"
         );
-        assert!(matches!(runs[0].operation, Operation::Sum));
+        assert!(matches!(
+            runs[0].step,
+            ReduceStep::Operation(Operation::Sum)
+        ));
 
-        let mut mixed = [
+        let mixed = [
             (0, Operation::Sum),
             (1, Operation::Max),
             (2, Operation::Sum),
             (3, Operation::Sum),
         ];
-        let runs = plan_reduction_runs(&mut mixed);
+        let runs = plan_reduction_runs(&mut built_in_steps(&mixed));
         assert_eq!(runs.len(), 3);
         assert_eq!(runs[0].axes, [2, 3]);
-        assert!(matches!(runs[0].operation, Operation::Sum));
+        assert!(matches!(
+            runs[0].step,
+            ReduceStep::Operation(Operation::Sum)
+        ));
         assert_eq!(runs[1].axes, [1]);
-        assert!(matches!(runs[1].operation, Operation::Max));
+        assert!(matches!(
+            runs[1].step,
+            ReduceStep::Operation(Operation::Max)
+        ));
         assert_eq!(runs[2].axes, [0]);
-        assert!(matches!(runs[2].operation, Operation::Sum));
+        assert!(matches!(
+            runs[2].step,
+            ReduceStep::Operation(Operation::Sum)
+        ));
 
-        let mut excluded = [
+        let excluded = [
             (0, Operation::Min),
             (1, Operation::Min),
             (2, Operation::Prod),
             (3, Operation::Prod),
         ];
-        let runs = plan_reduction_runs(&mut excluded);
+        let runs = plan_reduction_runs(&mut built_in_steps(&excluded));
         assert_eq!(
             runs.len(),
             3,
             "adjacent min may collapse; prod remains sequential"
         );
         assert_eq!(runs[2].axes, [1, 0]);
-        assert!(matches!(runs[2].operation, Operation::Min));
+        assert!(matches!(
+            runs[2].step,
+            ReduceStep::Operation(Operation::Min)
+        ));
+    }
+
+    #[test]
+    fn statistical_reductions_fuse_into_one_joint_run() -> Result<()> {
+        for operation in [
+            Operation::LogSumExp,
+            Operation::Var,
+            Operation::Std,
+            Operation::Norm,
+        ] {
+            let statistics = [(1, operation), (3, operation)];
+            let runs = plan_reduction_runs(&mut built_in_steps(&statistics));
+            assert_eq!(runs.len(), 1, "{operation:?}");
+            assert_eq!(runs[0].axes, [1, 3], "{operation:?}");
+        }
+
+        // A variance of variances differs from the joint variance, so the
+        // backend must reduce the run in a single call
+        let input = Tensor::new(&[[1f32, 2.], [3., 10.]], &candle_core::Device::Cpu)?;
+        reset_backend_reduction_call_count();
+        let variance = (&input).reduce_axes(&mut [(0, Operation::Var), (1, Operation::Var)])?;
+        assert_eq!(backend_reduction_call_count(), 1);
+        assert_eq!(variance.to_scalar::<f32>()?, 12.5);
+        Ok(())
     }
 
     #[test]
@@ -710,14 +1031,14 @@ mod tests {
             (
                 Tensor::new(
                     &[
-                        0.66984287f32,
+                        0.6698429f32,
                         0.52894678,
-                        0.85415958,
+                        0.8541596,
                         0.17721198,
-                        0.81804799,
-                        0.80991797,
-                        0.64868822,
-                        0.96697902,
+                        0.818048,
+                        0.809918,
+                        0.6486882,
+                        0.966979,
                         0.08047191,
                         0.46024353,
                         0.21955009,
@@ -727,10 +1048,10 @@ mod tests {
                         0.40949016,
                         0.21366165,
                         0.2357463,
-                        0.93699481,
+                        0.9369948,
                         0.64522596,
                         0.4383618,
-                        0.54871827,
+                        0.5487183,
                         0.87823442,
                         0.01261184,
                         0.90636503,
@@ -750,14 +1071,14 @@ mod tests {
             (
                 Tensor::new(
                     &[
-                        0.66984287f32,
+                        0.6698429f32,
                         0.52894678,
-                        0.85415958,
+                        0.8541596,
                         0.17721198,
-                        0.81804799,
-                        0.80991797,
-                        0.64868822,
-                        0.96697902,
+                        0.818048,
+                        0.809918,
+                        0.6486882,
+                        0.966979,
                         0.08047191,
                         0.46024353,
                         0.21955009,
@@ -767,10 +1088,10 @@ mod tests {
                         0.40949016,
                         0.21366165,
                         0.2357463,
-                        0.93699481,
+                        0.9369948,
                         0.64522596,
                         0.4383618,
-                        0.54871827,
+                        0.5487183,
                         0.87823442,
                         0.01261184,
                         0.90636503,
@@ -828,7 +1149,7 @@ mod tests {
     #[test]
     fn tch_add_axes() -> Result<()> {
         let tests = vec![(
-            Tensor::arange(0u8, 1 * 2 * 3, &Device::Cpu)?.reshape(&[1, 2, 3])?,
+            Tensor::arange(0u8, 2 * 3, &Device::Cpu)?.reshape(&[1, 2, 3])?,
             5,
             &[(0, 5), (3, 3)],
             Tensor::new(
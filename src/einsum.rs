@@ -1,10 +1,35 @@
-use std::cell::RefCell;
-use std::collections::VecDeque;
-
 use candle_core::{DType, Device, Result, Tensor};
 
 use crate::backend::execute_tensor_permute_and_compose;
 
+mod cache;
+mod equation;
+mod options;
+mod path;
+mod policy;
+mod prepared;
+mod reduction;
+mod semiring;
+mod strategy;
+
+pub use cache::{EinsumPlanCache, EinsumPlanCacheConfig, EinsumPlanCacheStats};
+#[cfg(feature = "nn")]
+pub(crate) use equation::Equation;
+pub use equation::{einsum, einsum_with};
+pub use options::EinsumOptions;
+pub use path::{EinsumPath, EinsumPathStep, einsum_path, einsum_path_with_strategy};
+#[doc(hidden)]
+pub use policy::execute_einsum_with_policy;
+pub use policy::{EinsumDevicePolicy, EinsumOperandPolicy, EinsumPromotion};
+pub use prepared::PreparedEinsumPlan;
+#[doc(hidden)]
+pub use reduction::{EinsumLabelReduction, reduce_einsum_labels};
+pub use semiring::EinsumSemiring;
+use semiring::SEMIRING_CHUNK_ELEMENTS;
+#[doc(hidden)]
+pub use semiring::execute_semiring_einsum;
+pub use strategy::EinsumStrategy;
+
 /// Validated compile-time plan for the unary explicit-output einsum slice.
 #[doc(hidden)]
 #[derive(Clone, Copy, Debug)]
@@ -12,6 +37,7 @@ pub struct UnaryEinsumSpec<'a> {
     input_rank: usize,
     output_rank: usize,
     permutation: &'a [usize],
+    accumulate: Option<DType>,
 }
 
 impl<'a> UnaryEinsumSpec<'a> {
@@ -22,8 +48,16 @@ impl<'a> UnaryEinsumSpec<'a> {
             input_rank,
             output_rank,
             permutation,
+            accumulate: None,
         }
     }
+
+    /// Sums floating-point operands narrower than `accumulate` in that dtype.
+    #[doc(hidden)]
+    pub const fn with_accumulation(mut self, accumulate: Option<DType>) -> Self {
+        self.accumulate = accumulate;
+        self
+    }
 }
 
 /// Executes a unary explicit-output einsum plan.
@@ -82,9 +116,10 @@ where
 
     if spec.output_rank < spec.input_rank {
         let reduction_axes = (spec.output_rank..spec.input_rank).collect::<Vec<_>>();
-        output = output
-            .sum(reduction_axes.as_slice())
-            .map_err(|error| error.context("einsum unary reduction"))?;
+        output = accumulating([&output], spec.accumulate, |[output]| {
+            output.sum(reduction_axes.as_slice())
+        })
+        .map_err(|error| error.context("einsum unary reduction"))?;
     }
 
     Ok(output)
@@ -104,6 +139,7 @@ pub struct BinaryEinsumSpec<'a> {
     batch_labels: &'a [&'a str],
     contracted_labels: &'a [&'a str],
     output_permutation: &'a [usize],
+    accumulate: Option<DType>,
 }
 
 /// One compile-time axis-list pattern containing at most one runtime ellipsis.
@@ -132,13 +168,26 @@ impl<'a> EinsumAxisPattern<'a> {
 pub struct EllipsisEinsumSpec<'a> {
     operands: &'a [EinsumAxisPattern<'a>],
     output: EinsumAxisPattern<'a>,
+    accumulate: Option<DType>,
 }
 
 impl<'a> EllipsisEinsumSpec<'a> {
     /// Constructs an ellipsis plan emitted by `candle-einops-macros`.
     #[doc(hidden)]
     pub const fn new(operands: &'a [EinsumAxisPattern<'a>], output: EinsumAxisPattern<'a>) -> Self {
-        Self { operands, output }
+        Self {
+            operands,
+            output,
+            accumulate: None,
+        }
+    }
+
+    /// Reduces floating-point operands narrower than `accumulate` in that
+    /// dtype at every pairwise contraction and final reduction.
+    #[doc(hidden)]
+    pub const fn with_accumulation(mut self, accumulate: Option<DType>) -> Self {
+        self.accumulate = accumulate;
+        self
     }
 }
 
@@ -169,8 +218,17 @@ impl<'a> BinaryEinsumSpec<'a> {
             batch_labels,
             contracted_labels,
             output_permutation,
+            accumulate: None,
         }
     }
+
+    /// Contracts floating-point operands narrower than `accumulate` in that
+    /// dtype.
+    #[doc(hidden)]
+    pub const fn with_accumulation(mut self, accumulate: Option<DType>) -> Self {
+        self.accumulate = accumulate;
+        self
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -265,6 +323,7 @@ where
         spec.input_ranks[0],
         spec.reduction_axes[0],
         spec.permutations[0],
+        spec.accumulate,
     )?;
     let right = prepare_operand(
         right,
@@ -272,6 +331,7 @@ where
         spec.input_ranks[1],
         spec.reduction_axes[1],
         spec.permutations[1],
+        spec.accumulate,
     )?;
 
     let left_expected_rank = spec
@@ -351,11 +411,13 @@ where
 
     let execution = if spec.contracted_rank == 0 {
         BinaryExecution::Multiply
+    } else if !matmul_supports(left.dtype()) {
+        BinaryExecution::General
     } else {
         requested_execution
     };
 
-    if execution == BinaryExecution::CanonicalMatmul {
+    if requested_execution == BinaryExecution::CanonicalMatmul && spec.contracted_rank != 0 {
         let canonical = spec.left_free_rank == 1
             && spec.contracted_rank == 1
             && spec.right_free_rank == 1
@@ -403,9 +465,10 @@ where
             materialize_broadcast_if_needed(&left, &left_shape, "einsum binary left broadcast")?;
         let right =
             materialize_broadcast_if_needed(&right, &right_shape, "einsum binary right broadcast")?;
-        let output = left
-            .matmul(&right)
-            .map_err(|error| error.context("einsum binary B/M/K/N matmul"))?;
+        let output = accumulating([&left, &right], spec.accumulate, |[left, right]| {
+            left.matmul(right)
+        })
+        .map_err(|error| error.context("einsum binary B/M/K/N matmul"))?;
         return apply_output_permutation(output, spec.output_permutation);
     }
 
@@ -427,15 +490,105 @@ where
         &[spec.batch_rank, spec.contracted_rank, spec.right_free_rank],
         "einsum binary right B/K/N reshape",
     )?;
-    let output = left
-        .matmul(&right)
-        .map_err(|error| error.context("einsum binary B/M/K/N matmul"))?;
+    let output = if matmul_supports(left.dtype()) {
+        accumulating([&left, &right], spec.accumulate, |[left, right]| {
+            left.matmul(right)
+        })
+        .map_err(|error| error.context("einsum binary B/M/K/N matmul"))?
+    } else {
+        semiring_contraction(
+            &left,
+            &right,
+            SEMIRING_CHUNK_ELEMENTS,
+            EinsumSemiring::SumProduct,
+        )?
+    };
     let output = output
         .reshape(canonical_output_shape)
         .map_err(|error| error.context("einsum binary canonical output reshape"))?;
     apply_output_permutation(output, spec.output_permutation)
 }
 
+/// Candle's matmul only covers these dtypes, so integer and 8-bit or narrower
+/// float contractions multiply and sum instead.
+fn matmul_supports(dtype: DType) -> bool {
+    matches!(dtype, DType::BF16 | DType::F16 | DType::F32 | DType::F64)
+}
+
+/// Runs the reduction `reduce` on `operands` widened to `accumulate`, and casts
+/// its result back, when `accumulate` is a float wider than their dtype.
+///
+/// Other operands, including integers, reach `reduce` unchanged, so only the
+/// sums and products that accumulate pay for the casts.
+fn accumulating<const N: usize>(
+    operands: [&Tensor; N],
+    accumulate: Option<DType>,
+    reduce: impl FnOnce([&Tensor; N]) -> Result<Tensor>,
+) -> Result<Tensor> {
+    let dtype = operands[0].dtype();
+    let Some(accumulate) = accumulate.filter(|accumulate| {
+        accumulate.is_float()
+            && dtype.is_float()
+            && dtype.size_in_bytes() < accumulate.size_in_bytes()
+    }) else {
+        return reduce(operands);
+    };
+    let widened = operands
+        .into_iter()
+        .map(|operand| operand.to_dtype(accumulate))
+        .collect::<Result<Vec<_>>>()
+        .map_err(|error| error.context("einsum accumulation upcast"))?;
+    reduce(std::array::from_fn(|index| &widened[index]))?
+        .to_dtype(dtype)
+        .map_err(|error| error.context("einsum result cast from the accumulation dtype"))
+}
+
+/// Contracts packed `[B, M, K]` and `[B, K, N]` operands by combining them
+/// with the semiring's ⊗ and reducing K with its ⊕.
+///
+/// K is split into chunks whose `[B, M, chunk, N]` products hold at most
+/// `chunk_elements` elements, or a single K slice when one slice is larger.
+fn semiring_contraction(
+    left: &Tensor,
+    right: &Tensor,
+    chunk_elements: usize,
+    semiring: EinsumSemiring,
+) -> Result<Tensor> {
+    let (b, m, k) = left
+        .dims3()
+        .map_err(|error| error.context("einsum binary chunked contraction left B/M/K"))?;
+    let n = right
+        .dim(2)
+        .map_err(|error| error.context("einsum binary chunked contraction right B/K/N"))?;
+    if k == 0 {
+        return semiring.fill(&[b, m, n], left.dtype(), left.device());
+    }
+    let slice = b.saturating_mul(m).saturating_mul(n).max(1);
+    let chunk = (chunk_elements / slice).clamp(1, k);
+    let mut output: Option<Tensor> = None;
+    for start in (0..k).step_by(chunk) {
+        let len = chunk.min(k - start);
+        let partial = left
+            .narrow(2, start, len)
+            .and_then(|left| left.unsqueeze(3))
+            .and_then(|left| {
+                right
+                    .narrow(1, start, len)
+                    .and_then(|right| right.unsqueeze(1))
+                    .and_then(|right| semiring.combine(&left, &right))
+            })
+            .and_then(|product| semiring.reduce(&product, &[2]))
+            .map_err(|error| error.context("einsum binary chunked contraction"))?;
+        output = Some(match output {
+            Some(output) => semiring
+                .accumulate(&output, &partial)
+                .map_err(|error| error.context("einsum binary chunked accumulation"))?,
+            None => partial,
+        });
+    }
+    Ok(output.expect("a nonempty K yields at least one chunk"))
+}
+
 fn graph_preserving_zero(left: &Tensor, right: &Tensor, shape: &[usize]) -> Result<Tensor> {
     let left = left
         .flatten_all()
@@ -481,7 +634,8 @@ where
         .collect::<Vec<_>>();
     execute_unary_einsum(
         &normalized,
-        UnaryEinsumSpec::new(input_axes.len(), output_axes.len(), &permutation),
+        UnaryEinsumSpec::new(input_axes.len(), output_axes.len(), &permutation)
+            .with_accumulation(spec.accumulate),
     )
 }
 
@@ -518,7 +672,14 @@ where
     let (right, right_axes) = normalize_repeated_axes(right, right_axes, 1)?;
     let output_axes = expand_axis_pattern(spec.output, maximum_capture, false);
     validate_expanded_output(&[&left_axes, &right_axes], &output_axes)?;
-    execute_expanded_binary(&left, &right, &left_axes, &right_axes, &output_axes)
+    execute_expanded_binary(
+        &left,
+        &right,
+        &left_axes,
+        &right_axes,
+        &output_axes,
+        spec.accumulate,
+    )
 }
 
 /// Converts one generated operand binding to the tensor reference used by the
@@ -538,9 +699,38 @@ pub fn execute_nary_einsum(operands: &[&Tensor], spec: EllipsisEinsumSpec<'_>) -
         .map(|(tensor, _)| tensor)
 }
 
+/// Contracts an arbitrary number of operands in a caller-specified order.
+///
+/// Each step names two positions in the live operand list, whose intermediate
+/// is appended, as in `opt_einsum`.
+#[doc(hidden)]
+pub fn execute_nary_einsum_with_path(
+    operands: &[&Tensor],
+    spec: EllipsisEinsumSpec<'_>,
+    path: &[(usize, usize)],
+) -> Result<Tensor> {
+    execute_nary_einsum_internal(operands, spec, NaryExecutionStrategy::Path(path))
+        .map(|(tensor, _)| tensor)
+}
+
+/// Contracts an arbitrary number of operands in the order found by `strategy`.
+pub(crate) fn execute_nary_einsum_with_strategy(
+    operands: &[&Tensor],
+    spec: EllipsisEinsumSpec<'_>,
+    strategy: EinsumStrategy,
+) -> Result<Tensor> {
+    let strategy = match strategy {
+        EinsumStrategy::Greedy => NaryExecutionStrategy::Selected,
+        strategy => NaryExecutionStrategy::Searched(strategy),
+    };
+    execute_nary_einsum_internal(operands, spec, strategy).map(|(tensor, _)| tensor)
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
-enum NaryExecutionStrategy {
+enum NaryExecutionStrategy<'p> {
     Selected,
+    Searched(EinsumStrategy),
+    Path(&'p [(usize, usize)]),
     #[cfg(test)]
     StreamingGreedy,
 }
@@ -565,6 +755,15 @@ struct NaryIntermediateTrace {
 fn prepare_nary_einsum<'a>(
     operands: &[&Tensor],
     spec: EllipsisEinsumSpec<'a>,
+) -> Result<(Vec<PlannedOperand<'a>>, Vec<ExpandedAxis<'a>>)> {
+    prepare_nary_einsum_with(operands, spec, normalize_repeated_axes)
+}
+
+/// Prepares the operands, extracting repeated-label diagonals with `diagonal`.
+fn prepare_nary_einsum_with<'a>(
+    operands: &[&Tensor],
+    spec: EllipsisEinsumSpec<'a>,
+    diagonal: impl Fn(Tensor, Vec<ExpandedAxis<'a>>, usize) -> Result<(Tensor, Vec<ExpandedAxis<'a>>)>,
 ) -> Result<(Vec<PlannedOperand<'a>>, Vec<ExpandedAxis<'a>>)> {
     if operands.is_empty() {
         candle_core::bail!("invalid n-ary einsum plan: at least one operand is required")
@@ -607,7 +806,7 @@ fn prepare_nary_einsum<'a>(
         let normalized =
             normalize_ellipsis_operand(operand, *pattern, capture, maximum_capture, index)?;
         let axes = expand_axis_pattern(*pattern, maximum_capture, true);
-        let (tensor, axes) = normalize_repeated_axes(normalized, axes, index)?;
+        let (tensor, axes) = diagonal(normalized, axes, index)?;
         planned.push(PlannedOperand {
             tensor,
             axes,
@@ -737,7 +936,7 @@ pub fn benchmark_binary_graph_estimate(
 fn execute_nary_einsum_internal<'a>(
     operands: &[&Tensor],
     spec: EllipsisEinsumSpec<'a>,
-    strategy: NaryExecutionStrategy,
+    strategy: NaryExecutionStrategy<'_>,
 ) -> Result<(Tensor, NaryExecutionTrace)> {
     let (mut planned, output_axes) = prepare_nary_einsum(operands, spec)?;
     let global_axis_order = stable_axis_order(&planned);
@@ -754,6 +953,15 @@ fn execute_nary_einsum_internal<'a>(
     let greedy_cache_key = calibrated_greedy
         .then(|| nary_plan_cache_key(&prepared_nary_metadata(&planned), &output_axes));
     let cached_greedy = greedy_cache_key.as_ref().and_then(cached_nary_sequence);
+    let searched = match strategy {
+        NaryExecutionStrategy::Searched(search) => Some(strategy::search_nary_sequence(
+            &prepared_nary_metadata(&planned),
+            &output_axes,
+            search,
+        )?),
+        NaryExecutionStrategy::Path(path) => Some(path::path_member_sequence(planned.len(), path)?),
+        _ => None,
+    };
     let mut trace = NaryExecutionTrace::default();
     match decision {
         NaryPlannerDecision::Exact(plan) => {
@@ -792,6 +1000,7 @@ fn execute_nary_einsum_internal<'a>(
                     &left.axes,
                     &right.axes,
                     &step.output_axes,
+                    spec.accumulate,
                 )?;
                 debug_assert_eq!(axes, step.output_axes);
                 planned.insert(
@@ -814,7 +1023,11 @@ fn execute_nary_einsum_internal<'a>(
         }
         NaryPlannerDecision::Greedy(_) => {
             trace.used_cached_greedy = cached_greedy.is_some();
-            let mut cached_steps = cached_greedy.as_deref().unwrap_or_default().iter();
+            let mut cached_steps = searched
+                .as_deref()
+                .or(cached_greedy.as_deref())
+                .unwrap_or_default()
+                .iter();
             while planned.len() > 1 {
                 let selected = if let Some(&(left_members, right_members)) = cached_steps.next() {
                     let left = planned
@@ -847,6 +1060,7 @@ fn execute_nary_einsum_internal<'a>(
                     &left.axes,
                     &right.axes,
                     &selected.output_axes,
+                    spec.accumulate,
                 )?;
                 trace.member_sequence.push((left.members, right.members));
                 planned.insert(
@@ -888,7 +1102,8 @@ fn execute_nary_einsum_internal<'a>(
         .collect::<Vec<_>>();
     let tensor = execute_unary_einsum(
         &final_operand.tensor,
-        UnaryEinsumSpec::new(final_operand.axes.len(), output_axes.len(), &permutation),
+        UnaryEinsumSpec::new(final_operand.axes.len(), output_axes.len(), &permutation)
+            .with_accumulation(spec.accumulate),
     )?;
     trace.final_permutations = 1;
     Ok((tensor, trace))
@@ -898,7 +1113,7 @@ fn execute_nary_einsum_internal<'a>(
 fn execute_nary_einsum_for_test<'a>(
     operands: &[&Tensor],
     spec: EllipsisEinsumSpec<'a>,
-    strategy: NaryExecutionStrategy,
+    strategy: NaryExecutionStrategy<'_>,
 ) -> Result<(Tensor, NaryExecutionTrace)> {
     execute_nary_einsum_internal(operands, spec, strategy)
 }
@@ -909,6 +1124,7 @@ fn execute_expanded_binary(
     left_axes: &[ExpandedAxis<'_>],
     right_axes: &[ExpandedAxis<'_>],
     output_axes: &[ExpandedAxis<'_>],
+    accumulate: Option<DType>,
 ) -> Result<Tensor> {
     let graph = classify_expanded_binary_graph(
         BinaryGraphOperand {
@@ -957,7 +1173,8 @@ fn execute_expanded_binary(
             &batch_labels,
             &contracted_labels,
             &plan.output_permutation,
-        ),
+        )
+        .with_accumulation(accumulate),
     )
 }
 
@@ -967,6 +1184,7 @@ fn execute_expanded_binary_canonical<'a>(
     left_axes: &[ExpandedAxis<'a>],
     right_axes: &[ExpandedAxis<'a>],
     retained_axes: &[ExpandedAxis<'a>],
+    accumulate: Option<DType>,
 ) -> Result<(Tensor, Vec<ExpandedAxis<'a>>)> {
     let plan = classify_expanded_binary(left_axes, right_axes, retained_axes);
     let canonical = plan
@@ -976,7 +1194,8 @@ fn execute_expanded_binary_canonical<'a>(
         .chain(&plan.right_free)
         .copied()
         .collect::<Vec<_>>();
-    let tensor = execute_expanded_binary(left, right, left_axes, right_axes, &canonical)?;
+    let tensor =
+        execute_expanded_binary(left, right, left_axes, right_axes, &canonical, accumulate)?;
     Ok((tensor, canonical))
 }
 
@@ -1111,7 +1330,7 @@ enum NaryPlannerDecision<'a> {
     Exact(NaryContractionPlan<'a>),
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 struct NaryPlanCacheOperand {
     axes: Vec<String>,
     dims: Vec<usize>,
@@ -1121,22 +1340,12 @@ struct NaryPlanCacheOperand {
 
 type NaryMemberPair = (u64, u64);
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 struct NaryPlanCacheKey {
     operands: Vec<NaryPlanCacheOperand>,
     output: Vec<String>,
 }
 
-struct NaryCachedPlan {
-    key: NaryPlanCacheKey,
-    sequence: Vec<NaryMemberPair>,
-}
-
-thread_local! {
-    static NARY_PLAN_CACHE: RefCell<VecDeque<NaryCachedPlan>> =
-        const { RefCell::new(VecDeque::new()) };
-}
-
 fn nary_plan_cache_key(
     operands: &[NaryPlannerMetadata<'_>],
     output: &[ExpandedAxis<'_>],
@@ -1167,31 +1376,16 @@ fn nary_plan_cache_key(
 }
 
 fn cached_nary_sequence(key: &NaryPlanCacheKey) -> Option<Vec<NaryMemberPair>> {
-    NARY_PLAN_CACHE.with(|cache| {
-        cache
-            .borrow()
-            .iter()
-            .find(|candidate| candidate.key == *key)
-            .map(|candidate| candidate.sequence.clone())
-    })
+    cache::lookup(key)
 }
 
 fn cache_nary_sequence(key: NaryPlanCacheKey, sequence: Vec<NaryMemberPair>) {
-    NARY_PLAN_CACHE.with(|cache| {
-        let mut cache = cache.borrow_mut();
-        if cache.iter().any(|candidate| candidate.key == key) {
-            return;
-        }
-        if cache.len() == 16 {
-            cache.pop_front();
-        }
-        cache.push_back(NaryCachedPlan { key, sequence });
-    });
+    cache::insert(key, sequence);
 }
 
 #[cfg(test)]
 fn clear_nary_plan_cache_for_test() {
-    NARY_PLAN_CACHE.with(|cache| cache.borrow_mut().clear());
+    cache::clear_thread_for_test();
 }
 
 fn model_axis_extent(operand: &NaryPlannerMetadata<'_>, axis: ExpandedAxis<'_>) -> Option<usize> {
@@ -1476,65 +1670,99 @@ fn model_exact_search<'a>(
     final_output: &[ExpandedAxis<'a>],
     global: &[ExpandedAxis<'a>],
 ) -> Result<NaryContractionPlan<'a>> {
+    if !(3..=4).contains(&operands.len()) {
+        candle_core::bail!("exact n-ary planner supports arity 3 through 4")
+    }
+    model_branch_search(operands, final_output, global, None, None)
+}
+
+/// Depth-first search over pair orders, visiting the cheapest pairs first.
+///
+/// Every metric only grows along an order, so a partial order scoring above
+/// the best complete one is pruned without changing the result. A `width`
+/// limits each step to that many pairs, and `bound` seeds the best plan.
+fn model_branch_search<'a>(
+    operands: &[NaryPlannerMetadata<'a>],
+    final_output: &[ExpandedAxis<'a>],
+    global: &[ExpandedAxis<'a>],
+    width: Option<usize>,
+    bound: Option<NaryContractionPlan<'a>>,
+) -> Result<NaryContractionPlan<'a>> {
+    struct Search<'s, 'a> {
+        output: &'s [ExpandedAxis<'a>],
+        global: &'s [ExpandedAxis<'a>],
+        width: usize,
+        best: Option<NaryContractionPlan<'a>>,
+    }
+
+    fn masks(steps: &[NaryPlanStep<'_>]) -> Vec<NaryMemberPair> {
+        steps.iter().map(|step| step.members).collect()
+    }
+
     fn visit<'a>(
+        search: &mut Search<'_, 'a>,
         state: Vec<NaryPlannerMetadata<'a>>,
         steps: Vec<NaryPlanStep<'a>>,
         metrics: NaryPlanMetrics,
-        output: &[ExpandedAxis<'a>],
-        global: &[ExpandedAxis<'a>],
-        best: &mut Option<NaryContractionPlan<'a>>,
     ) -> Result<()> {
         if state.len() == 1 {
             let mut metrics = metrics;
             metrics.score = model_score(&metrics)?;
             let candidate = NaryContractionPlan { steps, metrics };
-            let masks = |plan: &NaryContractionPlan<'a>| {
-                plan.steps
-                    .iter()
-                    .map(|step| step.members)
-                    .collect::<Vec<_>>()
-            };
-            if best.as_ref().is_none_or(|current| {
-                (candidate.metrics.score, masks(&candidate))
-                    < (current.metrics.score, masks(current))
+            if search.best.as_ref().is_none_or(|current| {
+                (candidate.metrics.score, masks(&candidate.steps))
+                    < (current.metrics.score, masks(&current.steps))
             }) {
-                *best = Some(candidate);
+                search.best = Some(candidate);
             }
             return Ok(());
         }
+        let mut pairs = Vec::new();
         for left in 0..state.len() - 1 {
             for right in left + 1..state.len() {
                 let (estimate, pair_output) =
-                    model_pair_details(&state, left, right, output, global)?;
-                let mut next_state = state.clone();
-                let mut next_metrics = metrics.clone();
-                model_accumulate(&mut next_metrics, &state, &estimate)?;
-                let mut next_steps = steps.clone();
-                next_steps.push(NaryPlanStep {
-                    members: (state[left].members, state[right].members),
-                    output_axes: pair_output.axes.iter().map(|(axis, _)| *axis).collect(),
-                    estimate,
-                });
-                model_apply_pair(&mut next_state, left, right, pair_output);
-                visit(next_state, next_steps, next_metrics, output, global, best)?;
+                    model_pair_details(&state, left, right, search.output, search.global)?;
+                pairs.push((left, right, estimate, pair_output));
+            }
+        }
+        pairs.sort_by_key(|(left, right, estimate, _)| {
+            (estimate.output_elements, estimate.flops, *left, *right)
+        });
+        for (left, right, estimate, pair_output) in pairs.into_iter().take(search.width) {
+            let mut next_metrics = metrics.clone();
+            model_accumulate(&mut next_metrics, &state, &estimate)?;
+            let partial_score = model_score(&next_metrics)?;
+            if search
+                .best
+                .as_ref()
+                .is_some_and(|best| partial_score > best.metrics.score)
+            {
+                continue;
             }
+            let mut next_state = state.clone();
+            let mut next_steps = steps.clone();
+            next_steps.push(NaryPlanStep {
+                members: (state[left].members, state[right].members),
+                output_axes: pair_output.axes.iter().map(|(axis, _)| *axis).collect(),
+                estimate,
+            });
+            model_apply_pair(&mut next_state, left, right, pair_output);
+            visit(search, next_state, next_steps, next_metrics)?;
         }
         Ok(())
     }
-    if !(3..=4).contains(&operands.len()) {
-        candle_core::bail!("exact n-ary planner supports arity 3 through 4")
-    }
+
     let metrics = model_initial_metrics(operands)?;
-    let mut best = None;
-    visit(
-        operands.to_vec(),
-        Vec::new(),
-        metrics,
-        final_output,
+    let mut search = Search {
+        output: final_output,
         global,
-        &mut best,
-    )?;
-    best.ok_or_else(|| candle_core::Error::msg("exact n-ary planner found no plan"))
+        width: width.unwrap_or(usize::MAX),
+        best: bound,
+    };
+    visit(&mut search, operands.to_vec(), Vec::new(), metrics)?;
+    search
+        .best
+        .ok_or_else(|| candle_core::Error::msg("exact n-ary planner found no plan"))
 }
 
 fn select_layout_aware_plan<'a>(
@@ -2995,6 +3223,7 @@ fn prepare_operand(
     input_rank: usize,
     reduction_axes: &[usize],
     permutation: &[usize],
+    accumulate: Option<DType>,
 ) -> Result<Tensor> {
     let mut reduced = vec![false; input_rank];
     for &axis in reduction_axes {
@@ -3014,9 +3243,10 @@ fn prepare_operand(
     let operand = if reduction_axes.is_empty() {
         operand.clone()
     } else {
-        operand.sum(reduction_axes).map_err(|error| {
-            error.context(format!("einsum operand {operand_index} pre-reduction"))
-        })?
+        accumulating([operand], accumulate, |[operand]| {
+            operand.sum(reduction_axes)
+        })
+        .map_err(|error| error.context(format!("einsum operand {operand_index} pre-reduction")))?
     };
     if permutation.iter().copied().eq(0..remaining_rank) {
         Ok(operand)
@@ -3600,6 +3830,154 @@ mod tests {
         ));
     }
 
+    fn search_for_test(
+        operands: &[NaryPlannerMetadata<'static>],
+        output: &[&'static str],
+        strategy: EinsumStrategy,
+    ) -> Result<NaryContractionPlan<'static>> {
+        let output = output
+            .iter()
+            .copied()
+            .map(ExpandedAxis::Named)
+            .collect::<Vec<_>>();
+        let sequence = strategy::search_nary_sequence(operands, &output, strategy)?;
+        let mut global = Vec::new();
+        for operand in operands {
+            for &(axis, _) in &operand.axes {
+                if !global.contains(&axis) {
+                    global.push(axis);
+                }
+            }
+        }
+        model_sequence_plan(operands, &output, &global, &sequence)
+    }
+
+    #[test]
+    fn searched_strategies_share_the_model_and_never_lose_to_greedy() -> Result<()> {
+        let extents = [9, 9, 8, 20, 32, 12, 30];
+        let labels = ["a", "b", "c", "d", "e", "f", "g"];
+        let chain = (0..6)
+            .map(|ordinal| {
+                planner_meta(
+                    ordinal,
+                    &[
+                        (labels[ordinal], extents[ordinal]),
+                        (labels[ordinal + 1], extents[ordinal + 1]),
+                    ],
+                    NaryLayoutEstimate::Contiguous,
+                )
+            })
+            .collect::<Vec<_>>();
+        let plan = |strategy| search_for_test(&chain, &["a", "g"], strategy);
+        let greedy = plan(EinsumStrategy::Greedy)?;
+        let optimal = plan(EinsumStrategy::Optimal)?;
+        let narrow = plan(EinsumStrategy::BranchBound { width: 2 })?;
+        let dynamic = plan(EinsumStrategy::DynamicProgramming {
+            max_intermediate_elements: None,
+        })?;
+        assert_eq!(greedy.metrics.score, 25_636);
+        assert_eq!(narrow.metrics.score, 24_756);
+        assert_eq!(optimal.metrics.score, 23_412);
+        assert_eq!(dynamic.metrics.score, optimal.metrics.score);
+
+        let global = labels.map(ExpandedAxis::Named);
+        let output = [ExpandedAxis::Named("a"), ExpandedAxis::Named("g")];
+        let unpruned = model_branch_search(&chain, &output, &global, None, None)?;
+        assert_eq!(optimal, unpruned);
+        assert_eq!(plan(EinsumStrategy::BranchBound { width: 15 })?, optimal);
+        assert_eq!(plan(EinsumStrategy::Optimal)?, optimal);
+        Ok(())
+    }
+
+    #[test]
+    fn dynamic_programming_caps_intermediates_and_connects_disjoint_operands() -> Result<()> {
+        let capped = |cap| {
+            search_for_test(
+                &matrix_chain_metadata([30, 35, 15, 5, 10], None),
+                &["a", "e"],
+                EinsumStrategy::DynamicProgramming {
+                    max_intermediate_elements: Some(cap),
+                },
+            )
+        };
+        let plan = capped(175)?;
+        assert!(
+            plan.steps[..plan.steps.len() - 1]
+                .iter()
+                .all(|step| step.estimate.output_elements <= 175)
+        );
+        let error = capped(174).unwrap_err().to_string();
+        assert!(
+            error.contains("intermediates stay within 174 elements"),
+            "{error}"
+        );
+
+        let disjoint = vec![
+            planner_meta(0, &[("i", 4), ("j", 3)], NaryLayoutEstimate::Contiguous),
+            planner_meta(1, &[("k", 2)], NaryLayoutEstimate::Contiguous),
+            planner_meta(2, &[("j", 3), ("l", 5)], NaryLayoutEstimate::Contiguous),
+        ];
+        let plan = search_for_test(
+            &disjoint,
+            &["i", "k", "l"],
+            EinsumStrategy::DynamicProgramming {
+                max_intermediate_elements: None,
+            },
+        )?;
+        assert_eq!(
+            plan.steps
+                .iter()
+                .map(|step| step.members)
+                .collect::<Vec<_>>(),
+            [(1, 4), (5, 2)]
+        );
+
+        let error = search_for_test(&disjoint, &["i"], EinsumStrategy::BranchBound { width: 0 });
+        assert!(error.unwrap_err().to_string().contains("at least 1"));
+        Ok(())
+    }
+
+    #[test]
+    fn caller_paths_replace_selection_and_skip_the_plan_cache() -> Result<()> {
+        clear_nary_plan_cache_for_test();
+        let device = Device::Cpu;
+        let patterns = [
+            EinsumAxisPattern::new(&["a", "b"], None),
+            EinsumAxisPattern::new(&["b", "c"], None),
+            EinsumAxisPattern::new(&["c", "d"], None),
+            EinsumAxisPattern::new(&["d", "e"], None),
+        ];
+        let output = EinsumAxisPattern::new(&["a", "e"], None);
+        let values = [(2, 3), (3, 4), (4, 2), (2, 5)]
+            .map(|shape| Tensor::ones(shape, DType::F32, &device))
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        let operands = values.iter().collect::<Vec<_>>();
+        let spec = nary_spec(&patterns, output);
+        let (_, trace) = execute_nary_einsum_for_test(
+            &operands,
+            spec,
+            NaryExecutionStrategy::Path(&[(2, 3), (0, 1), (0, 1)]),
+        )?;
+        assert!(!trace.used_cached_greedy);
+        assert_eq!(trace.member_sequence, [(4, 8), (1, 2), (3, 12)]);
+        let metadata = prepared_nary_metadata(&prepare_nary_einsum(&operands, spec)?.0);
+        let key = nary_plan_cache_key(
+            &metadata,
+            &[ExpandedAxis::Named("a"), ExpandedAxis::Named("e")],
+        );
+        assert!(cached_nary_sequence(&key).is_none());
+
+        let error = execute_nary_einsum_for_test(
+            &operands,
+            spec,
+            NaryExecutionStrategy::Path(&[(0, 1), (0, 1)]),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("2 steps, but 4 operands need 3"));
+        Ok(())
+    }
+
     fn nary_spec<'a>(
         operands: &'a [EinsumAxisPattern<'a>],
         output: EinsumAxisPattern<'a>,
@@ -3754,4 +4132,86 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn widest_promotion_meets_in_a_type_holding_both() {
+        let cases = [
+            (DType::F32, DType::BF16, DType::F32),
+            (DType::F16, DType::BF16, DType::F32),
+            (DType::F16, DType::F64, DType::F64),
+            (DType::U8, DType::F16, DType::F16),
+            (DType::U8, DType::BF16, DType::BF16),
+            (DType::I16, DType::BF16, DType::F32),
+            (DType::I32, DType::F32, DType::F64),
+            (DType::I64, DType::F16, DType::F64),
+            (DType::U8, DType::U32, DType::U32),
+            (DType::U8, DType::I16, DType::I16),
+            (DType::U32, DType::I32, DType::I64),
+            (DType::U32, DType::I16, DType::I64),
+            (DType::I16, DType::U8, DType::I16),
+            (DType::I32, DType::I64, DType::I64),
+        ];
+        for (left, right, expected) in cases {
+            assert_eq!(
+                policy::promote_dtypes(left, right),
+                expected,
+                "{left:?} {right:?}"
+            );
+            assert_eq!(
+                policy::promote_dtypes(right, left),
+                expected,
+                "{right:?} {left:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn chunked_sum_product_contraction_matches_matmul_values_and_gradients() -> Result<()> {
+        let left = Var::from_tensor(
+            &Tensor::arange(0f64, 30., &Device::Cpu)?
+                .affine(0.5, -3.)?
+                .reshape((2, 3, 5))?,
+        )?;
+        let right = Var::from_tensor(
+            &Tensor::arange(0f64, 20., &Device::Cpu)?
+                .affine(-0.25, 2.)?
+                .reshape((2, 5, 2))?,
+        )?;
+        let expected = left.matmul(right.as_tensor())?;
+        let expected_gradients = expected.sum_all()?.backward()?;
+        // Each K slice holds 12 products, so 24 elements split K into three chunks
+        for chunk_elements in [0, 24, usize::MAX] {
+            let actual =
+                semiring_contraction(&left, &right, chunk_elements, EinsumSemiring::SumProduct)?;
+            assert_eq!(
+                actual.flatten_all()?.to_vec1::<f64>()?,
+                expected.flatten_all()?.to_vec1::<f64>()?
+            );
+            let gradients = actual.sum_all()?.backward()?;
+            for variable in [&left, &right] {
+                assert_eq!(
+                    gradients
+                        .get(variable.as_tensor())
+                        .unwrap()
+                        .flatten_all()?
+                        .to_vec1::<f64>()?,
+                    expected_gradients
+                        .get(variable.as_tensor())
+                        .unwrap()
+                        .flatten_all()?
+                        .to_vec1::<f64>()?
+                );
+            }
+        }
+
+        let counts = Tensor::arange(0u32, 6, &Device::Cpu)?.reshape((1, 2, 3))?;
+        let product = semiring_contraction(
+            &counts,
+            &counts.transpose(1, 2)?,
+            1,
+            EinsumSemiring::SumProduct,
+        )?;
+        assert_eq!(product.to_vec3::<u32>()?, [[[5, 14], [14, 50]]]);
+        Ok(())
+    }
 }
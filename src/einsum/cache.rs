@@ -0,0 +1,265 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError, RwLock};
+
+use candle_core::Result;
+
+use super::{NaryMemberPair, NaryPlanCacheKey};
+
+const DEFAULT_CAPACITY: usize = 16;
+
+/// Where greedy contraction orders for repeated n-ary einsums are kept.
+///
+/// Pass it to [`EinsumPlanCache::configure`]. Capacities count cached orders;
+/// when a cache is full, the oldest order is evicted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum EinsumPlanCacheConfig {
+    /// Each thread keeps its own `capacity` orders, without locking unless
+    /// the configuration changed since the thread last used the cache.
+    ///
+    /// This is the default, with a capacity of 16.
+    ThreadLocal { capacity: usize },
+    /// All threads share `capacity` orders, split evenly across `shards`
+    /// independently locked parts selected by the equation and shapes.
+    ///
+    /// At most `capacity` shards are created, so the parts never hold more
+    /// than `capacity` orders in total.
+    ///
+    /// Suits thread pools whose workers evaluate the same equations, where a
+    /// thread-local cache plans every equation once per thread.
+    Shared { capacity: usize, shards: usize },
+    /// Orders are planned on every call and never stored.
+    Disabled,
+}
+
+impl Default for EinsumPlanCacheConfig {
+    fn default() -> Self {
+        Self::ThreadLocal {
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+/// Counters of the n-ary plan cache, summed over every thread.
+///
+/// Returned by [`EinsumPlanCache::stats`]. A disabled cache records nothing.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct EinsumPlanCacheStats {
+    /// Lookups that reused a cached order.
+    pub hits: u64,
+    /// Lookups that found no order and planned one.
+    pub misses: u64,
+    /// Orders dropped to make room for newer ones.
+    pub evictions: u64,
+}
+
+/// The process-wide cache of n-ary einsum contraction orders.
+///
+/// [`einsum()`](crate::einsum()) and [`einsum!`](crate::einsum!) cache the
+/// greedy order chosen for an equation, its operand shapes, and their
+/// layouts, so repeated calls skip pair selection. Explicit strategies,
+/// caller-specified paths, and [`PreparedEinsumPlan`](crate::PreparedEinsumPlan)
+/// never use the cache.
+///
+/// ```
+/// use candle_core::Result;
+/// use candle_einops::{EinsumPlanCache, EinsumPlanCacheConfig};
+///
+/// # fn main() -> Result<()> {
+/// EinsumPlanCache::configure(EinsumPlanCacheConfig::Shared {
+///     capacity: 256,
+///     shards: 8,
+/// })?;
+/// assert_eq!(EinsumPlanCache::stats().hits, 0);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct EinsumPlanCache {
+    _private: (),
+}
+
+impl EinsumPlanCache {
+    /// Replaces the cache configuration, dropping every cached order and
+    /// resetting the counters.
+    ///
+    /// Returns an error for a shared cache without shards.
+    pub fn configure(config: EinsumPlanCacheConfig) -> Result<()> {
+        let shards = match config {
+            EinsumPlanCacheConfig::Shared { shards: 0, .. } => {
+                candle_core::bail!("einsum plan cache needs at least one shard")
+            }
+            EinsumPlanCacheConfig::Shared { capacity, shards } => shards.min(capacity.max(1)),
+            _ => 0,
+        };
+        let mut state = STATE.write().unwrap_or_else(PoisonError::into_inner);
+        state.config = config;
+        GENERATION.fetch_add(1, Ordering::Release);
+        state.shards = (0..shards).map(|_| Mutex::new(Entries::new())).collect();
+        for counter in [&HITS, &MISSES, &EVICTIONS] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Returns the current configuration.
+    pub fn config() -> EinsumPlanCacheConfig {
+        STATE.read().unwrap_or_else(PoisonError::into_inner).config
+    }
+
+    /// Drops every cached order, including those of other threads, and keeps
+    /// the configuration and counters.
+    pub fn clear() {
+        let state = STATE.write().unwrap_or_else(PoisonError::into_inner);
+        GENERATION.fetch_add(1, Ordering::Release);
+        for shard in &state.shards {
+            shard
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entries
+                .clear();
+        }
+    }
+
+    /// Returns the counters since the last [`configure`](Self::configure).
+    pub fn stats() -> EinsumPlanCacheStats {
+        EinsumPlanCacheStats {
+            hits: HITS.load(Ordering::Relaxed),
+            misses: MISSES.load(Ordering::Relaxed),
+            evictions: EVICTIONS.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct CacheState {
+    config: EinsumPlanCacheConfig,
+    shards: Vec<Mutex<Entries>>,
+}
+
+struct Entries {
+    entries: VecDeque<(NaryPlanCacheKey, Vec<NaryMemberPair>)>,
+}
+
+/// This thread's entries with a snapshot of the configuration they were
+/// cached under, refreshed when `GENERATION` moves on.
+struct ThreadEntries {
+    generation: u64,
+    config: EinsumPlanCacheConfig,
+    entries: Entries,
+}
+
+impl Entries {
+    const fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+        }
+    }
+
+    fn get(&self, key: &NaryPlanCacheKey) -> Option<Vec<NaryMemberPair>> {
+        self.entries
+            .iter()
+            .find(|(candidate, _)| candidate == key)
+            .map(|(_, sequence)| sequence.clone())
+    }
+
+    fn insert(&mut self, key: NaryPlanCacheKey, sequence: Vec<NaryMemberPair>, capacity: usize) {
+        if capacity == 0 || self.entries.iter().any(|(candidate, _)| *candidate == key) {
+            return;
+        }
+        while self.entries.len() >= capacity {
+            self.entries.pop_front();
+            EVICTIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        self.entries.push_back((key, sequence));
+    }
+}
+
+static STATE: RwLock<CacheState> = RwLock::new(CacheState {
+    config: EinsumPlanCacheConfig::ThreadLocal {
+        capacity: DEFAULT_CAPACITY,
+    },
+    shards: Vec::new(),
+});
+/// Bumped under the `STATE` write lock by `configure` and `clear`, so each
+/// thread drops its entries and rereads the configuration on its next use.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+static EVICTIONS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static LOCAL: RefCell<ThreadEntries> = const {
+        RefCell::new(ThreadEntries {
+            generation: 0,
+            config: EinsumPlanCacheConfig::ThreadLocal {
+                capacity: DEFAULT_CAPACITY,
+            },
+            entries: Entries::new(),
+        })
+    };
+}
+
+/// Runs `operation` on the entries that hold `key` under the current
+/// configuration, with their capacity, or returns `None` when disabled.
+fn with_entries<T>(
+    key: &NaryPlanCacheKey,
+    operation: impl FnOnce(&mut Entries, usize) -> T,
+) -> Option<T> {
+    let generation = GENERATION.load(Ordering::Acquire);
+    let config = LOCAL.with(|local| {
+        let mut local = local.borrow_mut();
+        if local.generation != generation {
+            let state = STATE.read().unwrap_or_else(PoisonError::into_inner);
+            local.generation = GENERATION.load(Ordering::Relaxed);
+            local.config = state.config;
+            local.entries.entries.clear();
+        }
+        local.config
+    });
+    match config {
+        EinsumPlanCacheConfig::ThreadLocal { capacity } => {
+            Some(LOCAL.with(|local| operation(&mut local.borrow_mut().entries, capacity)))
+        }
+        EinsumPlanCacheConfig::Shared { .. } => {
+            let state = STATE.read().unwrap_or_else(PoisonError::into_inner);
+            // A concurrent `configure` may have replaced the shared cache
+            let EinsumPlanCacheConfig::Shared { capacity, .. } = state.config else {
+                return None;
+            };
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            let shards = state.shards.len();
+            let index = (hasher.finish() % shards as u64) as usize;
+            let mut entries = state.shards[index]
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            // The first `capacity % shards` shards hold one extra order
+            let shard_capacity = capacity / shards + usize::from(index < capacity % shards);
+            Some(operation(&mut entries, shard_capacity))
+        }
+        EinsumPlanCacheConfig::Disabled => None,
+    }
+}
+
+pub(super) fn lookup(key: &NaryPlanCacheKey) -> Option<Vec<NaryMemberPair>> {
+    let sequence = with_entries(key, |entries, _| entries.get(key))?;
+    let counter = if sequence.is_some() { &HITS } else { &MISSES };
+    counter.fetch_add(1, Ordering::Relaxed);
+    sequence
+}
+
+pub(super) fn insert(key: NaryPlanCacheKey, sequence: Vec<NaryMemberPair>) {
+    let probe = key.clone();
+    with_entries(&probe, |entries, capacity| {
+        entries.insert(key, sequence, capacity)
+    });
+}
+
+/// Drops this thread's entries without touching other threads, so parallel
+/// tests stay independent.
+#[cfg(test)]
+pub(super) fn clear_thread_for_test() {
+    LOCAL.with(|local| local.borrow_mut().entries.entries.clear());
+}
@@ -0,0 +1,319 @@
+use std::collections::HashSet;
+
+use candle_core::{DType, Result, Tensor};
+use candle_einops_syntax::{rewrite_numpy_equation, split_label_reductions, validate_label};
+
+use super::{
+    EinsumAxisPattern, EinsumLabelReduction, EinsumOperandPolicy, EinsumOptions, EinsumSemiring,
+    EinsumStrategy, EllipsisEinsumSpec, execute_binary_ellipsis_einsum, execute_einsum_with_policy,
+    execute_nary_einsum_with_path, execute_nary_einsum_with_strategy, execute_semiring_einsum,
+    execute_unary_ellipsis_einsum, path, reduce_einsum_labels,
+};
+
+/// One runtime-parsed axis list containing at most one ellipsis.
+#[derive(Clone, Debug)]
+pub(crate) struct AxisList {
+    pub(crate) labels: Vec<String>,
+    pub(crate) ellipsis_position: Option<usize>,
+}
+
+impl AxisList {
+    fn parse(text: &str, kind: &str) -> Result<Self> {
+        let mut labels = Vec::new();
+        let mut ellipsis_position = None;
+        for label in text.split_whitespace() {
+            if label == ".." {
+                if ellipsis_position.replace(labels.len()).is_some() {
+                    candle_core::bail!("einsum {kind} contains more than one `..`")
+                }
+            } else {
+                labels.push(parse_label(label)?);
+            }
+        }
+        Ok(Self {
+            labels,
+            ellipsis_position,
+        })
+    }
+
+    fn pattern<'a>(&self, labels: &'a [&'a str]) -> EinsumAxisPattern<'a> {
+        EinsumAxisPattern::new(labels, self.ellipsis_position)
+    }
+}
+
+/// An explicit-output einsum equation parsed at runtime.
+///
+/// The grammar and diagnostics match the equations accepted by `einsum!`.
+#[derive(Clone, Debug)]
+pub(crate) struct Equation {
+    pub(crate) operands: Vec<AxisList>,
+    pub(crate) output: AxisList,
+    /// Labels annotated as `mean(t)`, `max(t)`, or `min(t)` in the output,
+    /// in the order they are reduced.
+    pub(crate) reductions: Vec<(String, EinsumLabelReduction)>,
+}
+
+impl Equation {
+    pub(crate) fn parse(text: &str) -> Result<Self> {
+        if text.matches("->").count() != 1 {
+            candle_core::bail!("einsum equation requires exactly one explicit `->`")
+        }
+        let (input_text, output_text) = text
+            .split_once("->")
+            .ok_or_else(|| candle_core::Error::msg("missing einsum output"))?;
+        if output_text.contains(',') {
+            candle_core::bail!("einsum output must be one whitespace-delimited axis list")
+        }
+        let operands = input_text
+            .split(',')
+            .map(|input| AxisList::parse(input, "operand axis list"))
+            .collect::<Result<Vec<_>>>()?;
+
+        let (output_text, groups) =
+            split_label_reductions(output_text).map_err(candle_core::Error::msg)?;
+        let output = AxisList::parse(&output_text, "output axis list")?;
+        if output.ellipsis_position.is_some()
+            && !operands
+                .iter()
+                .any(|operand| operand.ellipsis_position.is_some())
+        {
+            candle_core::bail!("einsum output `..` requires an input `..`")
+        }
+        let mut output_names = HashSet::with_capacity(output.labels.len());
+        for label in &output.labels {
+            if !output_names.insert(label) {
+                candle_core::bail!("duplicate einsum output label `{label}`")
+            }
+            if !operands
+                .iter()
+                .any(|operand| operand.labels.contains(label))
+            {
+                candle_core::bail!("einsum output label `{label}` does not occur in the input")
+            }
+        }
+        let mut reductions = Vec::<(String, EinsumLabelReduction)>::new();
+        for (reduction, labels) in groups {
+            for label in labels {
+                if output.labels.contains(&label) {
+                    candle_core::bail!("einsum label `{label}` is both kept and reduced")
+                }
+                if reductions.iter().any(|(reduced, _)| *reduced == label) {
+                    candle_core::bail!("einsum label `{label}` is reduced more than once")
+                }
+                if !operands
+                    .iter()
+                    .any(|operand| operand.labels.contains(&label))
+                {
+                    candle_core::bail!("einsum reduced label `{label}` does not occur in the input")
+                }
+                reductions.push((label, reduction));
+            }
+        }
+
+        Ok(Self {
+            operands,
+            output,
+            reductions,
+        })
+    }
+
+    /// Parses a NumPy-style equation, where every character is a label and
+    /// `...` is the ellipsis, by rewriting it into the whitespace-delimited
+    /// grammar.
+    pub(crate) fn parse_numpy(text: &str) -> Result<Self> {
+        Self::parse(&rewrite_numpy_equation(text).map_err(candle_core::Error::msg)?)
+    }
+
+    /// Builds the borrowed plan consumed by the shared lowerings.
+    ///
+    /// Reduced labels follow the output labels, so the lowerings keep them
+    /// through every contraction for [`reduce_labels`](Self::reduce_labels).
+    pub(crate) fn with_spec<T>(&self, execute: impl FnOnce(EllipsisEinsumSpec<'_>) -> T) -> T {
+        let operand_labels = self
+            .operands
+            .iter()
+            .map(|operand| {
+                operand
+                    .labels
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let output_labels = self
+            .output
+            .labels
+            .iter()
+            .chain(self.reductions.iter().map(|(label, _)| label))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let patterns = self
+            .operands
+            .iter()
+            .zip(&operand_labels)
+            .map(|(operand, labels)| operand.pattern(labels))
+            .collect::<Vec<_>>();
+        execute(EllipsisEinsumSpec::new(
+            &patterns,
+            self.output.pattern(&output_labels),
+        ))
+    }
+
+    /// Applies the output's label reductions to a result of
+    /// [`with_spec`](Self::with_spec), averaging in `accumulate` when given.
+    pub(crate) fn reduce_labels(
+        &self,
+        output: Tensor,
+        accumulate: Option<DType>,
+    ) -> Result<Tensor> {
+        if self.reductions.is_empty() {
+            return Ok(output);
+        }
+        let reductions = self
+            .reductions
+            .iter()
+            .map(|(label, reduction)| (label.as_str(), *reduction))
+            .collect::<Vec<_>>();
+        reduce_einsum_labels(output, &reductions, accumulate)
+    }
+
+    pub(crate) fn check_operand_count(&self, operands: usize) -> Result<()> {
+        if operands != self.operands.len() {
+            candle_core::bail!(
+                "einsum equation has {} inputs but received {} operands",
+                self.operands.len(),
+                operands
+            )
+        }
+        Ok(())
+    }
+}
+
+fn parse_label(label: &str) -> Result<String> {
+    validate_label(label).map_err(candle_core::Error::msg)?;
+    Ok(label.to_owned())
+}
+
+/// Evaluates an explicit-output einsum equation parsed at runtime.
+///
+/// The equation uses the same grammar as [`einsum!`](crate::einsum!):
+/// whitespace-delimited named labels, one comma-separated axis list per
+/// operand, exactly one `->`, at most one `..` per axis list, repeated
+/// labels for diagonals, and `mean(t)`, `max(t)`, or `min(t)` output groups
+/// for labels reduced after the contraction instead of summed. Malformed
+/// equations and operand-count mismatches return the macro's diagnostics as
+/// Candle errors. [`einsum_with`] takes the macro's options.
+///
+/// ```
+/// use candle_core::{Device, Result, Tensor};
+///
+/// # fn main() -> Result<()> {
+/// let left = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], &Device::Cpu)?;
+/// let right = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], &Device::Cpu)?;
+/// let equation = String::from("row inner, inner column -> row column");
+/// let product = candle_einops::einsum(&equation, &[&left, &right])?;
+/// assert_eq!(product.to_vec2::<f32>()?, [[22., 28.], [49., 64.]]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn einsum(equation: &str, operands: &[&Tensor]) -> Result<Tensor> {
+    einsum_with(equation, operands, EinsumOptions::default())
+}
+
+/// Evaluates a runtime einsum equation with `options`.
+///
+/// Options that cannot be combined, such as a path with a semiring other than
+/// [`EinsumSemiring::SumProduct`], return an error, as do paths and
+/// accumulation dtypes that do not fit the operands.
+///
+/// ```
+/// use candle_core::{DType, Device, Result, Tensor};
+/// use candle_einops::{EinsumOptions, EinsumSemiring, EinsumStrategy, einsum_with};
+///
+/// # fn main() -> Result<()> {
+/// let shapes = [(30, 35), (35, 15), (15, 5), (5, 10), (10, 20)];
+/// let matrices = shapes
+///     .into_iter()
+///     .map(|shape| Tensor::ones(shape, DType::F32, &Device::Cpu))
+///     .collect::<Result<Vec<_>>>()?;
+/// let operands = matrices.iter().collect::<Vec<_>>();
+/// let options = EinsumOptions {
+///     strategy: EinsumStrategy::Optimal,
+///     ..EinsumOptions::default()
+/// };
+/// let product = einsum_with("a b, b c, c d, d e, e f -> a f", &operands, options)?;
+/// assert_eq!(product.dims(), &[30, 20]);
+///
+/// let scores = Tensor::new(&[[0f32, -1.], [-2., 1.]], &Device::Cpu)?;
+/// let transitions = Tensor::new(&[[0f32, 2.], [1., -3.]], &Device::Cpu)?;
+/// let options = EinsumOptions {
+///     numpy: true,
+///     semiring: EinsumSemiring::MaxPlus,
+///     ..EinsumOptions::default()
+/// };
+/// let best = einsum_with("bi,ij", &[&scores, &transitions], options)?;
+/// assert_eq!(best.to_vec2::<f32>()?, [[0., 2.], [2., 0.]]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn einsum_with(
+    equation: &str,
+    operands: &[&Tensor],
+    options: EinsumOptions<'_>,
+) -> Result<Tensor> {
+    let EinsumOptions {
+        numpy,
+        strategy,
+        path,
+        accumulate,
+        policy,
+        semiring,
+    } = options;
+    let equation = if numpy {
+        Equation::parse_numpy(equation)?
+    } else {
+        Equation::parse(equation)?
+    };
+    equation.check_operand_count(operands.len())?;
+    if let Some(path) = path {
+        if strategy != EinsumStrategy::Greedy {
+            candle_core::bail!("einsum options `path` and `strategy` cannot be combined")
+        }
+        path::path_member_sequence(operands.len(), path)?;
+    }
+    if semiring != EinsumSemiring::SumProduct
+        && (path.is_some() || strategy != EinsumStrategy::Greedy)
+    {
+        candle_core::bail!(
+            "einsum options `path` and `strategy` only apply to the sum-product semiring"
+        )
+    }
+    if let Some(accumulate) = accumulate
+        && !accumulate.is_float()
+    {
+        candle_core::bail!(
+            "einsum accumulation dtype must be floating point, received {accumulate:?}"
+        )
+    }
+    let execute = |operands: &[&Tensor]| {
+        let output = equation.with_spec(|spec| {
+            let spec = spec.with_accumulation(accumulate);
+            match (operands, path) {
+                _ if semiring != EinsumSemiring::SumProduct => {
+                    execute_semiring_einsum(operands, spec, semiring)
+                }
+                ([operand], _) => execute_unary_ellipsis_einsum(*operand, spec),
+                ([left, right], _) => execute_binary_ellipsis_einsum(*left, *right, spec),
+                (operands, Some(path)) => execute_nary_einsum_with_path(operands, spec, path),
+                (operands, None) => execute_nary_einsum_with_strategy(operands, spec, strategy),
+            }
+        })?;
+        equation.reduce_labels(output, accumulate)
+    };
+    // The default policy converts nothing, so it skips the conversion pass
+    if policy == EinsumOperandPolicy::default() {
+        execute(operands)
+    } else {
+        execute_einsum_with_policy(operands, policy, execute)
+    }
+}
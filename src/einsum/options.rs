@@ -0,0 +1,76 @@
+use candle_core::DType;
+
+use super::{EinsumOperandPolicy, EinsumSemiring, EinsumStrategy};
+
+/// The runtime form of the [`einsum!`](crate::einsum!) options.
+///
+/// Pass them to [`einsum_with`](crate::einsum_with). The default evaluates
+/// like [`einsum()`](crate::einsum()), so set only the fields that differ:
+///
+/// ```
+/// use candle_core::{DType, Device, Result, Tensor};
+/// use candle_einops::{EinsumOptions, einsum_with};
+///
+/// # fn main() -> Result<()> {
+/// let values = Tensor::ones(4096, DType::F16, &Device::Cpu)?;
+/// let options = EinsumOptions {
+///     accumulate: Some(DType::F32),
+///     ..EinsumOptions::default()
+/// };
+/// let sum = einsum_with("i, i ->", &[&values, &values], options)?;
+/// assert_eq!(sum.to_dtype(DType::F32)?.to_scalar::<f32>()?, 4096.);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct EinsumOptions<'a> {
+    /// Parses the equation in NumPy syntax, such as `"bij,bjk->bik"`.
+    ///
+    /// Every character is a label and `...` is the ellipsis. Without `->`,
+    /// the output is the ellipsis, if any, followed by the labels that occur
+    /// exactly once in the inputs in sorted order, as in `numpy.einsum`. The
+    /// macro form is `einsum!(numpy "...", ...)`.
+    pub numpy: bool,
+    /// Orders the pairwise contractions of three or more operands.
+    ///
+    /// The exhaustive strategies spend more planning time to find cheaper
+    /// orders for long chains and tensor networks, and return an error when
+    /// the search cannot run, such as a
+    /// [`DynamicProgramming`](EinsumStrategy::DynamicProgramming) size cap
+    /// that no order satisfies.
+    pub strategy: EinsumStrategy,
+    /// Contracts the operands in this order instead of planning one.
+    ///
+    /// Each step names two positions in the current operand list; both are
+    /// removed and their intermediate is appended, as in `opt_einsum` and the
+    /// [`operands`](crate::EinsumPathStep::operands) of an
+    /// [`einsum_path`](crate::einsum_path). A path for `n` operands has
+    /// `n - 1` steps, and one that does not fit the operands, or is combined
+    /// with a [`strategy`](Self::strategy) other than the default, returns an
+    /// error. The macro form is `; path = [(1, 2), (0, 1)]`.
+    pub path: Option<&'a [(usize, usize)]>,
+    /// Sums floating-point operands narrower than this dtype in it.
+    ///
+    /// Without it an einsum never casts, so an `f16` or `bf16` contraction
+    /// accumulates in that dtype. With `DType::F32`, each matmul, label sum,
+    /// and `mean` widens its inputs to `f32` and casts its result back, so
+    /// intermediates keep the operand dtype and permutations, diagonals, and
+    /// broadcast products never cast. Integer operands and operands at least
+    /// as wide are evaluated without casting, and a dtype that is not
+    /// floating point is an error. The macro form is `; accumulate = f32`.
+    pub accumulate: Option<DType>,
+    /// Converts the operands once, before planning, instead of rejecting
+    /// operands of different dtypes or devices.
+    ///
+    /// A device operand index outside the operands is an error. The macro
+    /// form is `; promote = widest, device = operand(0)`.
+    pub policy: EinsumOperandPolicy,
+    /// Combines operands with the semiring's ⊗ and reduces labels with its ⊕.
+    ///
+    /// [`EinsumSemiring::MaxPlus`] computes `max_i(a + w)` where the default
+    /// computes `sum_i(a * w)`. Other semirings never use GEMM and order
+    /// pairs by their combined element count, so they return an error with a
+    /// [`path`](Self::path) or a non-default [`strategy`](Self::strategy).
+    /// The macro form is `einsum!(semiring = max_plus, "...", ...)`.
+    pub semiring: EinsumSemiring,
+}
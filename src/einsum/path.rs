@@ -0,0 +1,375 @@
+use std::fmt;
+
+use candle_core::{DType, Device, Result, Tensor};
+
+use super::equation::Equation;
+use super::{
+    EinsumStrategy, ExpandedAxis, NaryLayoutEstimate, NaryMemberPair, PlannedOperand,
+    model_sequence_plan, pair_axis_extent, prepare_nary_einsum_with, prepared_nary_metadata,
+    select_nary_pair_with_order, stable_axis_order, strategy, validate_repeated_extents,
+};
+
+/// Contraction order and cost estimates for an einsum equation.
+///
+/// Returned by [`einsum_path`] without executing any tensor operation. Each
+/// step contracts two operands of the current list; like `opt_einsum`, both
+/// are removed and their intermediate is appended at the end, so the
+/// [`operands`](EinsumPathStep::operands) positions describe a complete path.
+/// Costs come from the planner's model and assume contiguous inputs: FLOPs
+/// count multiply-adds and sizes count elements, so multiply by the dtype size
+/// for bytes.
+///
+/// The [`Display`](fmt::Display) form is a table in the style of
+/// `opt_einsum.contract_path`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EinsumPath {
+    equation: String,
+    inputs: Vec<Vec<String>>,
+    output: Vec<String>,
+    steps: Vec<EinsumPathStep>,
+    flops: u128,
+    peak_elements: u128,
+    score: u128,
+}
+
+/// One pairwise contraction of an [`EinsumPath`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EinsumPathStep {
+    operands: (usize, usize),
+    labels: Vec<String>,
+    flops: u128,
+    elements: u128,
+}
+
+impl EinsumPath {
+    /// Returns the contractions in execution order.
+    pub fn steps(&self) -> &[EinsumPathStep] {
+        &self.steps
+    }
+
+    /// Returns the estimated FLOPs of every step together.
+    pub fn flops(&self) -> u128 {
+        self.flops
+    }
+
+    /// Returns the element count of the largest intermediate, or 0 without
+    /// steps.
+    pub fn largest_intermediate(&self) -> u128 {
+        self.steps
+            .iter()
+            .map(|step| step.elements)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the most elements held at once by the inputs and intermediates
+    /// still live during a step.
+    pub fn peak_elements(&self) -> u128 {
+        self.peak_elements
+    }
+
+    /// Returns the weighted cost the strategies minimize.
+    pub fn score(&self) -> u128 {
+        self.score
+    }
+}
+
+impl EinsumPathStep {
+    /// Returns the positions of the contracted pair in the current operand list.
+    pub fn operands(&self) -> (usize, usize) {
+        self.operands
+    }
+
+    /// Returns the labels of the intermediate, with ellipsis axes written
+    /// `..[i]`.
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Returns the estimated FLOPs of this contraction.
+    pub fn flops(&self) -> u128 {
+        self.flops
+    }
+
+    /// Returns the element count of the intermediate.
+    pub fn elements(&self) -> u128 {
+        self.elements
+    }
+}
+
+impl fmt::Display for EinsumPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rule = "-".repeat(80);
+        writeln!(f, "  Complete contraction:  {}", self.equation)?;
+        writeln!(f, "  Optimized FLOP count:  {:.3e}", self.flops)?;
+        writeln!(
+            f,
+            "  Largest intermediate:  {:.3e} elements",
+            self.largest_intermediate()
+        )?;
+        writeln!(f, "    Peak live elements:  {:.3e}", self.peak_elements)?;
+        writeln!(f, "{rule}")?;
+        writeln!(
+            f,
+            "{:>10}  {:>10}  {:>10}  {:<30}  remaining",
+            "operands", "FLOPs", "elements", "current"
+        )?;
+        write!(f, "{rule}")?;
+        let mut live = self.inputs.clone();
+        for step in &self.steps {
+            let (left, right) = step.operands;
+            let right_labels = live.remove(right);
+            let left_labels = live.remove(left);
+            live.push(step.labels.clone());
+            let current = format!(
+                "{}, {} -> {}",
+                left_labels.join(" "),
+                right_labels.join(" "),
+                step.labels.join(" ")
+            );
+            let remaining = format!(
+                "{} -> {}",
+                live.iter()
+                    .map(|labels| labels.join(" "))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.output.join(" ")
+            );
+            write!(
+                f,
+                "\n{:>10}  {:>10.3e}  {:>10.3e}  {current:<30}  {remaining}",
+                format!("({left}, {right})"),
+                step.flops,
+                step.elements
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Plans an einsum equation for operands of the given shapes without
+/// executing it.
+///
+/// The path is the order [`einsum()`](crate::einsum()) and
+/// [`einsum!`](crate::einsum!) plan for contiguous operands of these shapes,
+/// with the planner's cost estimates for each step. Strided or broadcast
+/// operands are costed from their layouts, so evaluation may order them
+/// differently. Invalid equations, operand counts, and shapes return the same
+/// errors as evaluation.
+///
+/// ```
+/// use candle_core::Result;
+///
+/// # fn main() -> Result<()> {
+/// let path = candle_einops::einsum_path(
+///     "a b, b c, c d -> a d",
+///     &[&[100, 2], &[2, 100], &[100, 2]],
+/// )?;
+/// let pairs = path.steps().iter().map(|step| step.operands()).collect::<Vec<_>>();
+/// assert_eq!(pairs, [(1, 2), (0, 1)]);
+/// assert_eq!(path.steps()[0].labels(), ["b", "d"]);
+/// assert_eq!(path.largest_intermediate(), 200);
+/// println!("{path}");
+/// # Ok(())
+/// # }
+/// ```
+pub fn einsum_path(equation: &str, shapes: &[&[usize]]) -> Result<EinsumPath> {
+    einsum_path_with_strategy(equation, shapes, EinsumStrategy::Greedy)
+}
+
+/// Plans an einsum equation like [`einsum_path`], ordering the contractions
+/// as [`einsum_with`](crate::einsum_with) would with `strategy`.
+pub fn einsum_path_with_strategy(
+    equation: &str,
+    shapes: &[&[usize]],
+    strategy: EinsumStrategy,
+) -> Result<EinsumPath> {
+    let parsed = Equation::parse(equation)?;
+    parsed.check_operand_count(shapes.len())?;
+    let placeholders = shapes
+        .iter()
+        .map(|shape| placeholder(shape))
+        .collect::<Result<Vec<_>>>()?;
+    let operands = placeholders.iter().collect::<Vec<_>>();
+    parsed.with_spec(|spec| {
+        let (planned, output_axes) = prepare_nary_einsum_with(&operands, spec, unique_axes)?;
+        let mut metadata = prepared_nary_metadata(&planned);
+        for operand in &mut metadata {
+            operand.layout = NaryLayoutEstimate::Contiguous;
+        }
+        let inputs = planned
+            .iter()
+            .map(|operand| labels(&operand.axes))
+            .collect::<Vec<_>>();
+        let sequence = match strategy {
+            EinsumStrategy::Greedy => streaming_greedy_pairs(planned, &output_axes)?
+                .into_iter()
+                .map(|pair| pair.members)
+                .collect(),
+            strategy => strategy::search_nary_sequence(&metadata, &output_axes, strategy)?,
+        };
+        let mut global = Vec::new();
+        for operand in &metadata {
+            for &(axis, _) in &operand.axes {
+                if !global.contains(&axis) {
+                    global.push(axis);
+                }
+            }
+        }
+        let plan = model_sequence_plan(&metadata, &output_axes, &global, &sequence)?;
+
+        // Positions follow `opt_einsum`, which appends each intermediate
+        let mut live = metadata
+            .iter()
+            .map(|operand| operand.members)
+            .collect::<Vec<_>>();
+        let steps = plan
+            .steps
+            .iter()
+            .map(|step| {
+                let position = |members| live.iter().position(|&live| live == members).unwrap();
+                let (left, right) = (position(step.members.0), position(step.members.1));
+                let operands = (left.min(right), left.max(right));
+                live.remove(operands.1);
+                live.remove(operands.0);
+                live.push(step.members.0 | step.members.1);
+                EinsumPathStep {
+                    operands,
+                    labels: labels(&step.output_axes),
+                    flops: step.estimate.flops,
+                    elements: step.estimate.output_elements,
+                }
+            })
+            .collect();
+        Ok(EinsumPath {
+            equation: equation.trim().to_string(),
+            inputs,
+            output: labels(&output_axes),
+            steps,
+            flops: plan.metrics.flops,
+            peak_elements: plan.metrics.peak_live_elements,
+            score: plan.metrics.score,
+        })
+    })
+}
+
+/// Converts `opt_einsum` path positions into the member pairs that execution
+/// replays, checking every step against the live operand count.
+pub(super) fn path_member_sequence(
+    operands: usize,
+    path: &[(usize, usize)],
+) -> Result<Vec<NaryMemberPair>> {
+    if operands > u64::BITS as usize {
+        candle_core::bail!(
+            "einsum path supports at most {} operands, received {operands}",
+            u64::BITS
+        )
+    }
+    let expected = operands.saturating_sub(1);
+    if path.len() != expected {
+        candle_core::bail!(
+            "einsum path has {} steps, but {operands} operands need {expected}",
+            path.len()
+        )
+    }
+    let mut live = (0..operands)
+        .map(|index| 1_u64 << index)
+        .collect::<Vec<_>>();
+    let mut sequence = Vec::with_capacity(path.len());
+    for (step, &(first, second)) in path.iter().enumerate() {
+        if first.max(second) >= live.len() {
+            candle_core::bail!(
+                "einsum path step {step} ({first}, {second}) is out of range for {} operands",
+                live.len()
+            )
+        }
+        if first == second {
+            candle_core::bail!("einsum path step {step} contracts operand {first} with itself")
+        }
+        let (first, second) = (first.min(second), first.max(second));
+        let right = live.remove(second);
+        let left = live.remove(first);
+        // Execution keeps operands ordered by their earliest input
+        let pair = if left.trailing_zeros() < right.trailing_zeros() {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        sequence.push(pair);
+        live.push(left | right);
+    }
+    Ok(sequence)
+}
+
+/// A zero-stride view with the given shape, so planning reads extents without
+/// allocating operands.
+pub(super) fn placeholder(shape: &[usize]) -> Result<Tensor> {
+    Tensor::zeros((), DType::F32, &Device::Cpu)?.broadcast_as(shape)
+}
+
+/// Keeps the first occurrence of each repeated label, as diagonal extraction
+/// does, without touching the placeholder's data.
+pub(super) fn unique_axes<'a>(
+    operand: Tensor,
+    axes: Vec<ExpandedAxis<'a>>,
+    operand_index: usize,
+) -> Result<(Tensor, Vec<ExpandedAxis<'a>>)> {
+    validate_repeated_extents(operand.dims(), &axes, operand_index)?;
+    let mut unique = Vec::new();
+    let mut dims = Vec::new();
+    for (&axis, &extent) in axes.iter().zip(operand.dims()) {
+        if !unique.contains(&axis) {
+            unique.push(axis);
+            dims.push(extent);
+        }
+    }
+    Ok((placeholder(&dims)?, unique))
+}
+
+fn labels(axes: &[ExpandedAxis<'_>]) -> Vec<String> {
+    axes.iter().map(ExpandedAxis::display_name).collect()
+}
+
+/// One pair chosen by greedy execution, at its positions in the executor's
+/// operand list, where the intermediate replaces the left operand.
+pub(super) struct StreamedPair<'a> {
+    pub(super) left: usize,
+    pub(super) right: usize,
+    pub(super) members: NaryMemberPair,
+    pub(super) output_axes: Vec<ExpandedAxis<'a>>,
+}
+
+/// Replays the pair selection of greedy execution on placeholder operands.
+pub(super) fn streaming_greedy_pairs<'a>(
+    mut planned: Vec<PlannedOperand<'a>>,
+    output_axes: &[ExpandedAxis<'a>],
+) -> Result<Vec<StreamedPair<'a>>> {
+    let global_axis_order = stable_axis_order(&planned);
+    let mut pairs = Vec::with_capacity(planned.len().saturating_sub(1));
+    while planned.len() > 1 {
+        let selected = select_nary_pair_with_order(&planned, output_axes, &global_axis_order)?;
+        let right = planned.remove(selected.right);
+        let left = planned.remove(selected.left);
+        let dims = selected
+            .output_axes
+            .iter()
+            .map(|&axis| pair_axis_extent(&left, &right, axis))
+            .collect::<Result<Vec<_>>>()?;
+        pairs.push(StreamedPair {
+            left: selected.left,
+            right: selected.right,
+            members: (left.members, right.members),
+            output_axes: selected.output_axes.clone(),
+        });
+        planned.insert(
+            selected.left,
+            PlannedOperand {
+                tensor: placeholder(&dims)?,
+                axes: selected.output_axes,
+                stable_ordinal: left.stable_ordinal.min(right.stable_ordinal),
+                members: left.members | right.members,
+            },
+        );
+    }
+    Ok(pairs)
+}
@@ -0,0 +1,164 @@
+use candle_core::{DType, Device, Result, Tensor};
+
+/// How an einsum reconciles operands of different dtypes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum EinsumPromotion {
+    /// Operands of different dtypes are an error.
+    #[default]
+    Strict,
+    /// Every operand is cast to the narrowest dtype that holds every operand
+    /// dtype.
+    ///
+    /// A wider type wins over a narrower one, and types of the same width that
+    /// cannot hold each other meet in the next wider type: `f16` with `bf16`
+    /// gives `f32`, and `u32` with `i32` gives `i64`. Integers meet floats in
+    /// the narrowest float, at least as wide, that holds every integer value,
+    /// so `u8` with `f16` gives `f16` but `i32` with `f16` or `f32` gives
+    /// `f64`; `i64` takes `f64`, which holds its values up to 2^53. Candle
+    /// dtypes without conversions to the result return an error.
+    Widest,
+}
+
+/// Which device an einsum runs on when its operands are on different devices.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum EinsumDevicePolicy {
+    /// Operands on different devices are an error.
+    #[default]
+    Strict,
+    /// Every operand is moved to the device of the operand at this index.
+    Operand(usize),
+}
+
+/// Conversions an einsum applies to its operands before planning.
+///
+/// The default is strict: operands must already share a dtype and a device.
+/// Set a policy in [`EinsumOptions`](crate::EinsumOptions), or write it as
+/// `; promote = widest, device = operand(0)` after the operands of
+/// [`einsum!`](crate::einsum!).
+///
+/// Each operand is converted at most once, before planning, so the planner,
+/// the plan cache and every pairwise step see operands that already agree and
+/// no step repeats a conversion.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct EinsumOperandPolicy {
+    pub promote: EinsumPromotion,
+    pub device: EinsumDevicePolicy,
+}
+
+/// Returns the smallest dtype that `left` and `right` both promote to.
+pub(super) fn promote_dtypes(left: DType, right: DType) -> DType {
+    if left == right {
+        return left;
+    }
+    match (left.is_float(), right.is_float()) {
+        (true, false) => return float_holding(left, right),
+        (false, true) => return float_holding(right, left),
+        _ => {}
+    }
+    let signed = |dtype: DType| matches!(dtype, DType::I16 | DType::I32 | DType::I64);
+    let (wide, narrow) = if left.size_in_bytes() >= right.size_in_bytes() {
+        (left, right)
+    } else {
+        (right, left)
+    };
+    if wide.size_in_bytes() > narrow.size_in_bytes() && (!signed(narrow) || signed(wide)) {
+        return wide;
+    }
+    // Neither type holds the other, so move up to the next width
+    match (left.is_float(), wide.size_in_bytes()) {
+        (true, ..=2) => DType::F32,
+        (true, _) => DType::F64,
+        (false, ..=2) => DType::I32,
+        (false, _) => DType::I64,
+    }
+}
+
+/// Returns the narrowest float at least as wide as `float` that holds every
+/// value of the integer dtype `int`, or `f64` when none does.
+fn float_holding(float: DType, int: DType) -> DType {
+    // Bits of integer magnitude each dtype represents exactly
+    let exact_bits = |dtype: DType| match dtype {
+        DType::U8 | DType::BF16 => 8,
+        DType::F16 => 11,
+        DType::I16 => 15,
+        DType::F32 => 24,
+        DType::I32 => 31,
+        DType::U32 => 32,
+        DType::F64 => 53,
+        DType::I64 => 63,
+        _ => 0,
+    };
+    [float, DType::F32, DType::F64]
+        .into_iter()
+        .find(|&candidate| {
+            candidate.size_in_bytes() >= float.size_in_bytes()
+                && exact_bits(candidate) >= exact_bits(int)
+        })
+        .unwrap_or(DType::F64)
+}
+
+/// Evaluates `execute` on the operands converted once according to `policy`.
+///
+/// Operands that already have the target dtype and device are passed through
+/// unchanged. With strict parts of the policy, mismatches reach the lowering,
+/// which reports them.
+#[doc(hidden)]
+pub fn execute_einsum_with_policy(
+    operands: &[&Tensor],
+    policy: EinsumOperandPolicy,
+    execute: impl FnOnce(&[&Tensor]) -> Result<Tensor>,
+) -> Result<Tensor> {
+    let device: Option<&Device> = match policy.device {
+        EinsumDevicePolicy::Strict => None,
+        EinsumDevicePolicy::Operand(index) => Some(
+            operands
+                .get(index)
+                .ok_or_else(|| {
+                    candle_core::Error::msg(format!(
+                        "einsum device operand {index} is out of range for {} operands",
+                        operands.len()
+                    ))
+                })?
+                .device(),
+        ),
+    };
+    let dtype = match policy.promote {
+        EinsumPromotion::Strict => None,
+        EinsumPromotion::Widest => operands
+            .iter()
+            .map(|operand| operand.dtype())
+            .reduce(promote_dtypes),
+    };
+    let converted = operands
+        .iter()
+        .enumerate()
+        .map(|(index, operand)| {
+            let moved = match device {
+                Some(device) if !operand.device().same_device(device) => {
+                    Some(operand.to_device(device).map_err(|error| {
+                        error.context(format!("einsum operand {index} transfer"))
+                    })?)
+                }
+                _ => None,
+            };
+            // Promotion only widens, so casting after the transfer moves fewer bytes
+            match (dtype, moved) {
+                (Some(dtype), moved) if operand.dtype() != dtype => moved
+                    .as_ref()
+                    .unwrap_or(operand)
+                    .to_dtype(dtype)
+                    .map(Some)
+                    .map_err(|error| {
+                        error.context(format!("einsum operand {index} promotion to {dtype:?}"))
+                    }),
+                (_, moved) => Ok(moved),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let operands = operands
+        .iter()
+        .zip(&converted)
+        .map(|(operand, converted)| converted.as_ref().unwrap_or(operand))
+        .collect::<Vec<_>>();
+    execute(&operands)
+}
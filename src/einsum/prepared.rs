@@ -0,0 +1,458 @@
+use std::cell::RefCell;
+
+use candle_core::{Device, Result, Tensor};
+
+use super::equation::Equation;
+use super::path::{placeholder, streaming_greedy_pairs, unique_axes};
+use super::{
+    BinaryEinsumSpec, BinaryExecution, EinsumLabelReduction, ExpandedAxis, ExpandedBinaryPlan,
+    PreparedDiagonalPlan, UnaryEinsumSpec, classify_expanded_binary, execute_binary_with,
+    execute_unary_einsum, normalize_repeated_axes, original_flat_gather_offsets,
+    prepare_nary_einsum_with, reduce_einsum_labels, resolve_extent,
+};
+
+/// An einsum equation lowered once for fixed operand shapes on one device.
+///
+/// [`new`](Self::new) does everything that depends only on the equation and
+/// the shapes: ellipsis alignment, diagonal gather indices uploaded to the
+/// device, a greedy contraction order, and the reductions, permutations, and
+/// GEMM lowering of every pairwise contraction. [`execute`](Self::execute)
+/// checks the operands' shapes and device and then only runs tensor
+/// operations, which suits inference loops that evaluate the same equation on
+/// every call.
+///
+/// The order is chosen from the shapes alone, so it can differ from the one
+/// [`einsum()`](crate::einsum()) picks, which also weighs the operands'
+/// layouts and dtype and may search exhaustively. The result is the same up
+/// to rounding.
+///
+/// The plan does not depend on the dtype, but the operands of one call must
+/// share it. Non-contiguous operands are accepted and copied where the
+/// lowering needs contiguous storage. Contractions always sum products in the
+/// operand dtype: prepared plans have no wider accumulation dtype and no
+/// semirings, so use [`einsum_with`](crate::einsum_with) for those.
+///
+/// ```
+/// use candle_core::{DType, Device, Result, Tensor};
+/// use candle_einops::PreparedEinsumPlan;
+///
+/// # fn main() -> Result<()> {
+/// let device = Device::Cpu;
+/// let plan = PreparedEinsumPlan::new(
+///     "b i i, b i j, j k -> b k",
+///     &[&[2, 3, 3], &[2, 3, 4], &[4, 5]],
+///     &device,
+/// )?;
+/// assert_eq!(plan.output_shape(), &[2, 5]);
+///
+/// let x = Tensor::ones((2, 3, 3), DType::F32, &device)?;
+/// let y = Tensor::ones((2, 3, 4), DType::F32, &device)?;
+/// let w = Tensor::ones((4, 5), DType::F32, &device)?;
+/// let output = plan.execute(&[&x, &y, &w])?;
+/// assert_eq!(output.to_vec2::<f32>()?, [[12f32; 5]; 2]);
+///
+/// let wrong = Tensor::ones((4, 6), DType::F32, &device)?;
+/// assert!(plan.execute(&[&x, &y, &wrong]).is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PreparedEinsumPlan {
+    device: Device,
+    input_shapes: Vec<Vec<usize>>,
+    operands: Vec<PreparedOperand>,
+    steps: Vec<PreparedBinaryStep>,
+    final_permutation: Option<PreparedPermutation>,
+    reductions: Vec<(String, EinsumLabelReduction)>,
+    output_shape: Vec<usize>,
+}
+
+/// Per-operand normalization ahead of the first contraction.
+#[derive(Debug)]
+struct PreparedOperand {
+    ellipsis_position: usize,
+    missing_ellipsis_axes: usize,
+    diagonal: PreparedDiagonal,
+}
+
+#[derive(Debug)]
+enum PreparedDiagonal {
+    None,
+    Gather(PreparedDiagonalPlan),
+    /// Offsets beyond the `u32` range use the runtime per-label extraction,
+    /// keyed by the axis ids of the normalized operand.
+    Sequential(Vec<usize>),
+}
+
+/// One pairwise contraction at its positions in the live operand list, where
+/// the intermediate replaces the left operand.
+#[derive(Debug)]
+struct PreparedBinaryStep {
+    left: usize,
+    right: usize,
+    input_ranks: [usize; 2],
+    reductions: [Vec<usize>; 2],
+    permutations: [Vec<usize>; 2],
+    batch_labels: Vec<String>,
+    left_free_rank: usize,
+    contracted_labels: Vec<String>,
+    right_free_rank: usize,
+    output_permutation: Vec<usize>,
+    execution: BinaryExecution,
+}
+
+#[derive(Debug)]
+struct PreparedPermutation {
+    input_rank: usize,
+    output_rank: usize,
+    permutation: Vec<usize>,
+}
+
+impl PreparedEinsumPlan {
+    /// Lowers `equation` for operands of exactly `shapes` on `device`.
+    ///
+    /// Invalid equations, operand counts, and shapes return the errors
+    /// [`einsum()`](crate::einsum()) would.
+    pub fn new(equation: &str, shapes: &[&[usize]], device: &Device) -> Result<Self> {
+        let parsed = Equation::parse(equation)?;
+        parsed.check_operand_count(shapes.len())?;
+        let placeholders = shapes
+            .iter()
+            .map(|shape| placeholder(shape))
+            .collect::<Result<Vec<_>>>()?;
+        let placeholders = placeholders.iter().collect::<Vec<_>>();
+        parsed.with_spec(|spec| {
+            let normalized = RefCell::new(Vec::with_capacity(shapes.len()));
+            let (planned, output_axes) =
+                prepare_nary_einsum_with(&placeholders, spec, |operand, axes, index| {
+                    normalized
+                        .borrow_mut()
+                        .push((operand.dims().to_vec(), axes.clone()));
+                    unique_axes(operand, axes, index)
+                })?;
+            let operands = normalized
+                .into_inner()
+                .into_iter()
+                .enumerate()
+                .map(|(index, (dims, axes))| {
+                    let pattern = &parsed.operands[index];
+                    Ok(PreparedOperand {
+                        ellipsis_position: pattern.ellipsis_position.unwrap_or(0),
+                        missing_ellipsis_axes: dims.len() - shapes[index].len(),
+                        diagonal: prepare_diagonal(&dims, &axes, index, device)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut output_shape = Vec::with_capacity(output_axes.len());
+            for axis in &output_axes {
+                let mut extent = None;
+                for operand in &planned {
+                    if let Some(position) = operand.axes.iter().position(|other| other == axis) {
+                        let other = operand.tensor.dims()[position];
+                        extent = Some(match extent {
+                            Some(extent) => resolve_extent(&axis.display_name(), extent, other)?,
+                            None => other,
+                        });
+                    }
+                }
+                output_shape.push(extent.expect("validated einsum output axis"));
+            }
+
+            let (steps, final_permutation) = match planned.len() {
+                1 => {
+                    let permutation = final_permutation(&planned[0].axes, &output_axes);
+                    (Vec::new(), Some(permutation))
+                }
+                2 => {
+                    let plan =
+                        classify_expanded_binary(&planned[0].axes, &planned[1].axes, &output_axes);
+                    let step =
+                        PreparedBinaryStep::new(0, 1, [&planned[0].axes, &planned[1].axes], plan);
+                    (vec![step], None)
+                }
+                _ => {
+                    let mut live = planned
+                        .iter()
+                        .map(|operand| operand.axes.clone())
+                        .collect::<Vec<_>>();
+                    let pairs = streaming_greedy_pairs(planned, &output_axes)?;
+                    let mut steps = Vec::with_capacity(pairs.len());
+                    for pair in pairs {
+                        let right = live.remove(pair.right);
+                        let left = live.remove(pair.left);
+                        let plan = classify_expanded_binary(&left, &right, &pair.output_axes);
+                        steps.push(PreparedBinaryStep::new(
+                            pair.left,
+                            pair.right,
+                            [&left, &right],
+                            plan,
+                        ));
+                        live.insert(pair.left, pair.output_axes);
+                    }
+                    let permutation = final_permutation(&live[0], &output_axes);
+                    (steps, Some(permutation))
+                }
+            };
+            // Reduced labels trail the output axes until `execute` reduces them
+            output_shape.truncate(output_shape.len() - parsed.reductions.len());
+            Ok(Self {
+                device: device.clone(),
+                input_shapes: shapes.iter().map(|shape| shape.to_vec()).collect(),
+                operands,
+                steps,
+                final_permutation,
+                reductions: parsed.reductions.clone(),
+                output_shape,
+            })
+        })
+    }
+
+    /// Evaluates the prepared equation.
+    ///
+    /// The operands must have exactly the prepared shapes, live on the
+    /// prepared device, and share one dtype.
+    pub fn execute(&self, operands: &[&Tensor]) -> Result<Tensor> {
+        if operands.len() != self.input_shapes.len() {
+            candle_core::bail!(
+                "prepared einsum plan expects {} operands, received {}",
+                self.input_shapes.len(),
+                operands.len()
+            )
+        }
+        for (index, (operand, shape)) in operands.iter().zip(&self.input_shapes).enumerate() {
+            if operand.dims() != shape.as_slice() {
+                candle_core::bail!(
+                    "prepared einsum plan expects operand {index} with shape {shape:?}, received {:?}",
+                    operand.dims()
+                )
+            }
+            if !operand.device().same_device(&self.device) {
+                candle_core::bail!(
+                    "prepared einsum plan is on {:?}, but operand {index} is on {:?}",
+                    self.device,
+                    operand.device()
+                )
+            }
+            if operand.dtype() != operands[0].dtype() {
+                candle_core::bail!(
+                    "einsum operands have different dtypes: operand 0 {:?}, operand {index} {:?}",
+                    operands[0].dtype(),
+                    operand.dtype()
+                )
+            }
+        }
+
+        let mut live = operands
+            .iter()
+            .zip(&self.operands)
+            .enumerate()
+            .map(|(index, (operand, prepared))| prepared.normalize(operand, index))
+            .collect::<Result<Vec<_>>>()?;
+        for step in &self.steps {
+            let right = live.remove(step.right);
+            let left = live.remove(step.left);
+            live.insert(step.left, step.execute(&left, &right)?);
+        }
+        let output = live
+            .pop()
+            .expect("a prepared einsum plan retains one operand");
+        let output = match &self.final_permutation {
+            Some(final_permutation) => execute_unary_einsum(
+                &output,
+                UnaryEinsumSpec::new(
+                    final_permutation.input_rank,
+                    final_permutation.output_rank,
+                    &final_permutation.permutation,
+                ),
+            )?,
+            None => output,
+        };
+        let reductions = self
+            .reductions
+            .iter()
+            .map(|(label, reduction)| (label.as_str(), *reduction))
+            .collect::<Vec<_>>();
+        reduce_einsum_labels(output, &reductions, None)
+    }
+
+    /// Exact operand shapes the plan accepts.
+    #[must_use]
+    pub fn input_shapes(&self) -> &[Vec<usize>] {
+        &self.input_shapes
+    }
+
+    /// Shape of every result.
+    #[must_use]
+    pub fn output_shape(&self) -> &[usize] {
+        &self.output_shape
+    }
+
+    /// Device holding the prepared indices, which the operands must share.
+    #[must_use]
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+}
+
+impl PreparedOperand {
+    fn normalize(&self, operand: &Tensor, index: usize) -> Result<Tensor> {
+        let mut normalized = operand.clone();
+        for _ in 0..self.missing_ellipsis_axes {
+            normalized = normalized
+                .unsqueeze(self.ellipsis_position)
+                .map_err(|error| {
+                    error.context(format!("einsum operand {index} ellipsis right-alignment"))
+                })?;
+        }
+        match &self.diagonal {
+            PreparedDiagonal::None => Ok(normalized),
+            PreparedDiagonal::Gather(plan) => normalized
+                .contiguous()
+                .and_then(|normalized| plan.execute(&normalized))
+                .map_err(|error| error.context(format!("einsum operand {index} diagonal"))),
+            PreparedDiagonal::Sequential(axis_ids) => {
+                let axes = axis_ids
+                    .iter()
+                    .copied()
+                    .map(ExpandedAxis::Ellipsis)
+                    .collect();
+                normalize_repeated_axes(normalized, axes, index).map(|(tensor, _)| tensor)
+            }
+        }
+    }
+}
+
+impl PreparedBinaryStep {
+    fn new(
+        left: usize,
+        right: usize,
+        inputs: [&[ExpandedAxis<'_>]; 2],
+        plan: ExpandedBinaryPlan<'_>,
+    ) -> Self {
+        let identity = |permutation: &[usize]| permutation.iter().copied().eq(0..permutation.len());
+        // The same choice `einsum!` makes for binary equations at compile time
+        let execution = if plan.contracted.is_empty() {
+            BinaryExecution::Multiply
+        } else if plan.left_reductions.is_empty()
+            && plan.right_reductions.is_empty()
+            && identity(&plan.left_permutation)
+            && identity(&plan.right_permutation)
+            && plan.left_free.len() == 1
+            && plan.contracted.len() == 1
+            && plan.right_free.len() == 1
+        {
+            BinaryExecution::CanonicalMatmul
+        } else {
+            BinaryExecution::General
+        };
+        let labels = |axes: &[ExpandedAxis<'_>]| {
+            axes.iter()
+                .map(ExpandedAxis::display_name)
+                .collect::<Vec<_>>()
+        };
+        Self {
+            left,
+            right,
+            input_ranks: inputs.map(<[_]>::len),
+            batch_labels: labels(&plan.batch),
+            left_free_rank: plan.left_free.len(),
+            contracted_labels: labels(&plan.contracted),
+            right_free_rank: plan.right_free.len(),
+            reductions: [plan.left_reductions, plan.right_reductions],
+            permutations: [plan.left_permutation, plan.right_permutation],
+            output_permutation: plan.output_permutation,
+            execution,
+        }
+    }
+
+    fn execute(&self, left: &Tensor, right: &Tensor) -> Result<Tensor> {
+        let batch_labels = self
+            .batch_labels
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let contracted_labels = self
+            .contracted_labels
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        execute_binary_with(
+            left,
+            right,
+            BinaryEinsumSpec::new(
+                self.input_ranks,
+                [&self.reductions[0], &self.reductions[1]],
+                [&self.permutations[0], &self.permutations[1]],
+                self.batch_labels.len(),
+                self.left_free_rank,
+                self.contracted_labels.len(),
+                self.right_free_rank,
+                &batch_labels,
+                &contracted_labels,
+                &self.output_permutation,
+            ),
+            self.execution,
+        )
+    }
+}
+
+/// Builds the gather for an operand with repeated labels, on the normalized
+/// shape that still lists every occurrence.
+fn prepare_diagonal(
+    dims: &[usize],
+    axes: &[ExpandedAxis<'_>],
+    operand_index: usize,
+    device: &Device,
+) -> Result<PreparedDiagonal> {
+    let mut unique = Vec::new();
+    let axis_ids = axes
+        .iter()
+        .map(|axis| match unique.iter().position(|other| other == axis) {
+            Some(id) => id,
+            None => {
+                unique.push(*axis);
+                unique.len() - 1
+            }
+        })
+        .collect::<Vec<_>>();
+    if unique.len() == axes.len() {
+        return Ok(PreparedDiagonal::None);
+    }
+    let Some((output_shape, offsets)) = original_flat_gather_offsets(dims, axes, operand_index)?
+    else {
+        return Ok(PreparedDiagonal::Sequential(axis_ids));
+    };
+    let index_count = offsets.len();
+    let indices = Tensor::from_vec(offsets, index_count, device).map_err(|error| {
+        error.context(format!(
+            "einsum operand {operand_index} device-local prepared diagonal indices"
+        ))
+    })?;
+    Ok(PreparedDiagonal::Gather(PreparedDiagonalPlan {
+        input_shape: dims.to_vec(),
+        axis_ids,
+        output_shape,
+        indices,
+    }))
+}
+
+fn final_permutation(
+    axes: &[ExpandedAxis<'_>],
+    output_axes: &[ExpandedAxis<'_>],
+) -> PreparedPermutation {
+    let permutation = output_axes
+        .iter()
+        .chain(axes.iter().filter(|axis| !output_axes.contains(axis)))
+        .map(|axis| {
+            axes.iter()
+                .position(|candidate| candidate == axis)
+                .expect("validated final einsum output axis")
+        })
+        .collect();
+    PreparedPermutation {
+        input_rank: axes.len(),
+        output_rank: output_axes.len(),
+        permutation,
+    }
+}
@@ -0,0 +1,51 @@
+use candle_core::{DType, Result, Tensor};
+
+use super::accumulating;
+
+/// How a label annotated as `mean(t)`, `max(t)`, or `min(t)` in an einsum
+/// output is reduced, shared with the macros that name its variants.
+#[doc(hidden)]
+pub use candle_einops_syntax::LabelReduction as EinsumLabelReduction;
+
+/// Reduces the trailing axes of an einsum result, one per entry of
+/// `reductions`, in the order they are written.
+///
+/// The lowerings keep annotated labels as trailing output axes, so every
+/// contraction over other labels has already run when they are reduced. A
+/// `mean` sums in `accumulate` when it is wider than the output.
+#[doc(hidden)]
+pub fn reduce_einsum_labels(
+    output: Tensor,
+    reductions: &[(&str, EinsumLabelReduction)],
+    accumulate: Option<DType>,
+) -> Result<Tensor> {
+    let axis = output
+        .rank()
+        .checked_sub(reductions.len())
+        .expect("einsum output retains every reduced label");
+    reductions
+        .iter()
+        .try_fold(output, |output, &(label, reduction)| {
+            let name = reduction.name();
+            match reduction {
+                EinsumLabelReduction::Mean if !output.dtype().is_float() => candle_core::bail!(
+                    "einsum mean over label `{label}` requires floating-point values, received {:?}",
+                    output.dtype()
+                ),
+                EinsumLabelReduction::Max | EinsumLabelReduction::Min
+                    if output.dims()[axis] == 0 =>
+                {
+                    candle_core::bail!("einsum {name} over label `{label}` has no elements")
+                }
+                _ => {}
+            }
+            match reduction {
+                EinsumLabelReduction::Mean => {
+                    accumulating([&output], accumulate, |[output]| output.mean(axis))
+                }
+                EinsumLabelReduction::Max => output.max(axis),
+                EinsumLabelReduction::Min => output.min(axis),
+            }
+            .map_err(|error| error.context(format!("einsum {name} over label `{label}`")))
+        })
+}
@@ -0,0 +1,275 @@
+use candle_core::{DType, Device, Result, Tensor};
+
+use crate::backend::reduce_log_sum_exp;
+
+use super::{
+    EllipsisEinsumSpec, ExpandedAxis, PairEstimate, PlannedOperand, accumulating,
+    apply_output_permutation, classify_expanded_binary, estimate_pair_with_order,
+    execute_nary_einsum, pack_canonical_operand, pair_axis_extent, prepare_nary_einsum,
+    semiring_contraction, stable_axis_order,
+};
+
+/// Products materialized at once by a semiring contraction.
+pub(super) const SEMIRING_CHUNK_ELEMENTS: usize = 1 << 20;
+
+/// The pair of operations an einsum combines operands (⊗) and reduces labels
+/// (⊕) with.
+///
+/// Set it in [`EinsumOptions`](crate::EinsumOptions), or write it as a
+/// `semiring = max_plus` prefix of [`einsum!`](crate::einsum!). Every semiring
+/// other than [`SumProduct`](Self::SumProduct) adds operands and requires a
+/// floating-point dtype. A label reduced over zero elements yields the
+/// semiring's ⊕ identity.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum EinsumSemiring {
+    /// Multiplies operands and sums labels, the ordinary einsum.
+    #[default]
+    SumProduct,
+    /// Adds operands and takes the maximum over labels, as in Viterbi
+    /// decoding. The identity is `-inf`.
+    MaxPlus,
+    /// Adds operands and takes the minimum over labels, as in shortest paths.
+    /// The identity is `+inf`.
+    MinPlus,
+    /// Adds log-space operands and reduces labels with a stable log-sum-exp,
+    /// as in CRF forward passes. The identity is `-inf`.
+    LogSumExp,
+}
+
+impl EinsumSemiring {
+    fn identity(self) -> f64 {
+        match self {
+            Self::SumProduct => 0.,
+            Self::MaxPlus | Self::LogSumExp => f64::NEG_INFINITY,
+            Self::MinPlus => f64::INFINITY,
+        }
+    }
+
+    /// A tensor of `shape` holding the ⊕ identity.
+    pub(super) fn fill(self, shape: &[usize], dtype: DType, device: &Device) -> Result<Tensor> {
+        Tensor::new(self.identity(), device)
+            .and_then(|identity| identity.to_dtype(dtype))
+            .and_then(|identity| identity.broadcast_as(shape))
+            .map_err(|error| error.context(format!("einsum {self:?} identity")))
+    }
+
+    /// Combines broadcast-compatible operands with ⊗.
+    pub(super) fn combine(self, left: &Tensor, right: &Tensor) -> Result<Tensor> {
+        match self {
+            Self::SumProduct => left.broadcast_mul(right),
+            _ => left.broadcast_add(right),
+        }
+    }
+
+    /// Reduces `axes` of `tensor` with ⊕, removing them.
+    pub(super) fn reduce(self, tensor: &Tensor, axes: &[usize]) -> Result<Tensor> {
+        if axes.is_empty() {
+            return Ok(tensor.clone());
+        }
+        if axes.iter().any(|&axis| tensor.dims()[axis] == 0) {
+            let dims = tensor
+                .dims()
+                .iter()
+                .enumerate()
+                .filter(|(axis, _)| !axes.contains(axis))
+                .map(|(_, &extent)| extent)
+                .collect::<Vec<_>>();
+            return self.fill(&dims, tensor.dtype(), tensor.device());
+        }
+        let mut descending = axes.to_vec();
+        descending.sort_unstable_by(|left, right| right.cmp(left));
+        match self {
+            Self::SumProduct => tensor.sum(axes),
+            Self::MaxPlus => descending
+                .iter()
+                .try_fold(tensor.clone(), |tensor, &axis| tensor.max(axis)),
+            Self::MinPlus => descending
+                .iter()
+                .try_fold(tensor.clone(), |tensor, &axis| tensor.min(axis)),
+            Self::LogSumExp => reduce_log_sum_exp(tensor, axes),
+        }
+    }
+
+    /// Merges two partial results of the same shape with ⊕.
+    pub(super) fn accumulate(self, left: &Tensor, right: &Tensor) -> Result<Tensor> {
+        match self {
+            Self::SumProduct => left + right,
+            Self::MaxPlus => left.maximum(right),
+            Self::MinPlus => left.minimum(right),
+            Self::LogSumExp => self.reduce(&Tensor::stack(&[left, right], 0)?, &[0]),
+        }
+    }
+}
+
+/// Normalizes the operands and contracts them pairwise in `semiring`.
+///
+/// The sum-product semiring uses the ordinary einsum lowering.
+#[doc(hidden)]
+pub fn execute_semiring_einsum(
+    operands: &[&Tensor],
+    spec: EllipsisEinsumSpec<'_>,
+    semiring: EinsumSemiring,
+) -> Result<Tensor> {
+    if semiring == EinsumSemiring::SumProduct {
+        return execute_nary_einsum(operands, spec);
+    }
+    let (mut planned, output_axes) = prepare_nary_einsum(operands, spec)?;
+    let dtype = planned[0].tensor.dtype();
+    if !dtype.is_float() {
+        candle_core::bail!(
+            "einsum {semiring:?} semiring requires floating-point operands, received {dtype:?}"
+        )
+    }
+    // Maxima and minima are exact in any dtype, so only log-sum-exp widens
+    let accumulate = spec
+        .accumulate
+        .filter(|_| semiring == EinsumSemiring::LogSumExp);
+    let global_axis_order = stable_axis_order(&planned);
+    while planned.len() > 1 {
+        let selected = select_semiring_pair(&planned, &output_axes, &global_axis_order)?;
+        let right = planned.remove(selected.right);
+        let left = planned.remove(selected.left);
+        let tensor = contract_pair(&left, &right, &selected.output_axes, semiring, accumulate)?;
+        planned.insert(
+            selected.left,
+            PlannedOperand {
+                tensor,
+                axes: selected.output_axes,
+                stable_ordinal: left.stable_ordinal.min(right.stable_ordinal),
+                members: left.members | right.members,
+            },
+        );
+    }
+    let last = planned
+        .pop()
+        .expect("non-empty semiring plan retains one operand");
+    let reduced = last
+        .axes
+        .iter()
+        .enumerate()
+        .filter(|(_, axis)| !output_axes.contains(axis))
+        .map(|(position, _)| position)
+        .collect::<Vec<_>>();
+    let remaining = last
+        .axes
+        .iter()
+        .filter(|axis| output_axes.contains(axis))
+        .collect::<Vec<_>>();
+    let output = accumulating([&last.tensor], accumulate, |[tensor]| {
+        semiring.reduce(tensor, &reduced)
+    })
+    .map_err(|error| error.context(format!("einsum {semiring:?} final reduction")))?;
+    let permutation = output_axes
+        .iter()
+        .map(|axis| {
+            remaining
+                .iter()
+                .position(|candidate| *candidate == axis)
+                .expect("validated semiring output axis")
+        })
+        .collect::<Vec<_>>();
+    apply_output_permutation(output, &permutation)
+}
+
+/// Picks the pair with the fewest combined elements, then the smallest
+/// intermediate.
+///
+/// A semiring step materializes its broadcast products instead of calling a
+/// GEMM, so their count rather than the intermediate size dominates its cost.
+fn select_semiring_pair<'a>(
+    operands: &[PlannedOperand<'a>],
+    final_output: &[ExpandedAxis<'a>],
+    global_axis_order: &[ExpandedAxis<'a>],
+) -> Result<PairEstimate<'a>> {
+    let mut best: Option<(PairEstimate<'a>, _)> = None;
+    for left in 0..operands.len() - 1 {
+        for right in left + 1..operands.len() {
+            let candidate =
+                estimate_pair_with_order(operands, left, right, final_output, global_axis_order)?;
+            let key = (
+                candidate.flops,
+                candidate.output_elements,
+                operands[left].stable_ordinal,
+                operands[right].stable_ordinal,
+                left,
+                right,
+            );
+            if best.as_ref().is_none_or(|(_, best)| key < *best) {
+                best = Some((candidate, key));
+            }
+        }
+    }
+    best.map(|(pair, _)| pair)
+        .ok_or_else(|| candle_core::Error::msg("semiring einsum planner found no operand pair"))
+}
+
+/// Contracts two operands into `output_axes` over the shared labels they drop,
+/// reducing in `accumulate` when it is wider than the operands.
+fn contract_pair<'a>(
+    left: &PlannedOperand<'a>,
+    right: &PlannedOperand<'a>,
+    output_axes: &[ExpandedAxis<'a>],
+    semiring: EinsumSemiring,
+    accumulate: Option<DType>,
+) -> Result<Tensor> {
+    let plan = classify_expanded_binary(&left.axes, &right.axes, output_axes);
+    let extents = |axes: &[ExpandedAxis<'a>]| {
+        axes.iter()
+            .map(|&axis| pair_axis_extent(left, right, axis))
+            .collect::<Result<Vec<_>>>()
+    };
+    let batch = extents(&plan.batch)?;
+    let left_free = extents(&plan.left_free)?;
+    let contracted = extents(&plan.contracted)?;
+    let right_free = extents(&plan.right_free)?;
+    let [b, m, k, n] = [&batch, &left_free, &contracted, &right_free]
+        .map(|extents| extents.iter().product::<usize>());
+
+    let canonical = |operand: &Tensor,
+                     reductions: &[usize],
+                     permutation: &[usize],
+                     shape: Vec<usize>,
+                     packed: [usize; 3],
+                     groups: [usize; 3],
+                     context: &'static str| {
+        let reduced = accumulating([operand], accumulate, |[operand]| {
+            semiring.reduce(operand, reductions)
+        })
+        .and_then(|reduced| {
+            if permutation.iter().copied().eq(0..permutation.len()) {
+                Ok(reduced)
+            } else {
+                reduced.permute(permutation)
+            }
+        })
+        .and_then(|canonical| canonical.broadcast_as(shape))
+        .map_err(|error| error.context(format!("{context} ({semiring:?} preparation)")))?;
+        pack_canonical_operand(&reduced, &packed, &groups, context)
+    };
+    let left_tensor = canonical(
+        &left.tensor,
+        &plan.left_reductions,
+        &plan.left_permutation,
+        [batch.as_slice(), &left_free, &contracted].concat(),
+        [b, m, k],
+        [batch.len(), left_free.len(), contracted.len()],
+        "einsum semiring left B/M/K reshape",
+    )?;
+    let right_tensor = canonical(
+        &right.tensor,
+        &plan.right_reductions,
+        &plan.right_permutation,
+        [batch.as_slice(), &contracted, &right_free].concat(),
+        [b, k, n],
+        [batch.len(), contracted.len(), right_free.len()],
+        "einsum semiring right B/K/N reshape",
+    )?;
+    let output = accumulating(
+        [&left_tensor, &right_tensor],
+        accumulate,
+        |[left, right]| semiring_contraction(left, right, SEMIRING_CHUNK_ELEMENTS, semiring),
+    )?
+    .reshape([batch.as_slice(), &left_free, &right_free].concat())
+    .map_err(|error| error.context(format!("einsum {semiring:?} output reshape")))?;
+    apply_output_permutation(output, &plan.output_permutation)
+}
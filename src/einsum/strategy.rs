@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use candle_core::Result;
+
+use super::{
+    ExpandedAxis, NaryContractionPlan, NaryMemberPair, NaryPlanMetrics, NaryPlannerMetadata,
+    model_branch_search, model_greedy_plan, model_pair_details, model_score, model_sequence_plan,
+};
+
+/// Contraction order search used for einsums with three or more operands.
+///
+/// Every strategy scores candidate orders with the same cost model, which
+/// weighs FLOPs, copies, intermediate sizes, peak live elements and kernel
+/// submissions, and breaks equal scores by operand order, so the chosen order
+/// is deterministic. Unary and binary equations have a single lowering and
+/// ignore the strategy. Set it in [`EinsumOptions`](crate::EinsumOptions).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum EinsumStrategy {
+    /// Repeatedly contracts the pair with the smallest intermediate.
+    ///
+    /// This is what [`einsum!`](crate::einsum!) and [`einsum()`](crate::einsum())
+    /// use. Planning is quadratic per step, and the chosen order is kept in
+    /// the [`EinsumPlanCache`](crate::EinsumPlanCache) for repeated shapes.
+    #[default]
+    Greedy,
+    /// Searches every pairwise order for the lowest score.
+    ///
+    /// Partial orders that already cost more than the best complete one are
+    /// pruned, but the search still grows factorially with the operand count;
+    /// it is practical up to about eight operands.
+    Optimal,
+    /// Builds the cheapest contraction of every subset of operands from its
+    /// cheapest splits.
+    ///
+    /// Only splits that share an axis are considered while any exist, and no
+    /// intermediate other than the final result may exceed
+    /// `max_intermediate_elements`. The subset table grows exponentially, so at
+    /// most 16 operands are supported.
+    DynamicProgramming {
+        max_intermediate_elements: Option<usize>,
+    },
+    /// Searches depth first through the `width` cheapest pairs at each step,
+    /// starting from the greedy order and pruning by its score.
+    ///
+    /// The result is never worse than [`Greedy`](Self::Greedy), and a `width`
+    /// covering every pair makes it [`Optimal`](Self::Optimal).
+    BranchBound { width: usize },
+}
+
+const MAX_DYNAMIC_PROGRAMMING_OPERANDS: usize = 16;
+
+/// Plans the member sequence for an explicitly requested search strategy.
+pub(super) fn search_nary_sequence<'a>(
+    operands: &[NaryPlannerMetadata<'a>],
+    final_output: &[ExpandedAxis<'a>],
+    strategy: EinsumStrategy,
+) -> Result<Vec<NaryMemberPair>> {
+    if operands.len() > u64::BITS as usize {
+        candle_core::bail!(
+            "einsum {strategy:?} strategy supports at most {} operands, received {}",
+            u64::BITS,
+            operands.len()
+        )
+    }
+    let mut global = Vec::new();
+    for operand in operands {
+        for &(axis, _) in &operand.axes {
+            if !global.contains(&axis) {
+                global.push(axis);
+            }
+        }
+    }
+    let plan = match strategy {
+        EinsumStrategy::Greedy => model_greedy_plan(operands, final_output, &global)?,
+        EinsumStrategy::Optimal => {
+            let greedy = model_greedy_plan(operands, final_output, &global)?;
+            model_branch_search(operands, final_output, &global, None, Some(greedy))?
+        }
+        EinsumStrategy::BranchBound { width } => {
+            if width == 0 {
+                candle_core::bail!("einsum branch and bound width must be at least 1")
+            }
+            let greedy = model_greedy_plan(operands, final_output, &global)?;
+            model_branch_search(operands, final_output, &global, Some(width), Some(greedy))?
+        }
+        EinsumStrategy::DynamicProgramming {
+            max_intermediate_elements,
+        } => model_dynamic_programming(operands, final_output, &global, max_intermediate_elements)?,
+    };
+    Ok(plan.steps.iter().map(|step| step.members).collect())
+}
+
+struct SubsetPlan<'a> {
+    score: u128,
+    operand: NaryPlannerMetadata<'a>,
+    split: Option<NaryMemberPair>,
+}
+
+fn model_dynamic_programming<'a>(
+    operands: &[NaryPlannerMetadata<'a>],
+    final_output: &[ExpandedAxis<'a>],
+    global: &[ExpandedAxis<'a>],
+    max_intermediate_elements: Option<usize>,
+) -> Result<NaryContractionPlan<'a>> {
+    if operands.len() > MAX_DYNAMIC_PROGRAMMING_OPERANDS {
+        candle_core::bail!(
+            "einsum dynamic programming supports at most {MAX_DYNAMIC_PROGRAMMING_OPERANDS} \
+             operands, received {}",
+            operands.len()
+        )
+    }
+    let full = operands
+        .iter()
+        .fold(0_u64, |members, operand| members | operand.members);
+    // Outer products are only formed when the operands do not connect without them
+    let subsets = subset_plans(
+        operands,
+        final_output,
+        global,
+        max_intermediate_elements,
+        false,
+    )?;
+    let subsets = if subsets.contains_key(&full) {
+        subsets
+    } else {
+        subset_plans(
+            operands,
+            final_output,
+            global,
+            max_intermediate_elements,
+            true,
+        )?
+    };
+    if !subsets.contains_key(&full) {
+        candle_core::bail!(
+            "einsum has no contraction order whose intermediates stay within {} elements",
+            max_intermediate_elements.unwrap_or(usize::MAX)
+        )
+    }
+    let mut sequence = Vec::with_capacity(operands.len() - 1);
+    subset_sequence(&subsets, full, &mut sequence);
+    model_sequence_plan(operands, final_output, global, &sequence)
+}
+
+fn subset_plans<'a>(
+    operands: &[NaryPlannerMetadata<'a>],
+    final_output: &[ExpandedAxis<'a>],
+    global: &[ExpandedAxis<'a>],
+    max_intermediate_elements: Option<usize>,
+    outer_products: bool,
+) -> Result<HashMap<u64, SubsetPlan<'a>>> {
+    let full = operands
+        .iter()
+        .fold(0_u64, |members, operand| members | operand.members);
+    let mut subsets = operands
+        .iter()
+        .map(|operand| {
+            let plan = SubsetPlan {
+                score: 0,
+                operand: operand.clone(),
+                split: None,
+            };
+            (operand.members, plan)
+        })
+        .collect::<HashMap<_, _>>();
+    // Every proper submask is numerically smaller, so ascending order visits
+    // both halves of a split before the subset they form
+    for members in 1..=full {
+        if members & full != members || members.count_ones() < 2 {
+            continue;
+        }
+        let lowest = members & members.wrapping_neg();
+        let mut best: Option<SubsetPlan<'a>> = None;
+        let mut left = (members - 1) & members;
+        while left != 0 {
+            let right = members ^ left;
+            if left & lowest != 0
+                && let (Some(left_plan), Some(right_plan)) =
+                    (subsets.get(&left), subsets.get(&right))
+                && (outer_products || shares_axis(&left_plan.operand, &right_plan.operand))
+            {
+                let mut state = vec![left_plan.operand.clone(), right_plan.operand.clone()];
+                state.extend(
+                    operands
+                        .iter()
+                        .filter(|operand| operand.members & members == 0)
+                        .cloned(),
+                );
+                let (estimate, output) = model_pair_details(&state, 0, 1, final_output, global)?;
+                let within_cap = members == full
+                    || max_intermediate_elements
+                        .is_none_or(|cap| estimate.output_elements <= cap as u128);
+                if within_cap {
+                    let score = model_score(&NaryPlanMetrics {
+                        flops: estimate.flops,
+                        intermediate_elements: estimate.output_elements,
+                        output_elements: estimate.output_elements,
+                        copy_bytes: estimate.copy_bytes,
+                        peak_live_elements: 0,
+                        submissions: estimate.submissions,
+                        score: 0,
+                    })?
+                    .checked_add(left_plan.score)
+                    .and_then(|score| score.checked_add(right_plan.score))
+                    .ok_or_else(|| {
+                        candle_core::Error::msg("n-ary score estimate overflows u128")
+                    })?;
+                    if best
+                        .as_ref()
+                        .is_none_or(|best| (score, left) < (best.score, best.split.unwrap().0))
+                    {
+                        best = Some(SubsetPlan {
+                            score,
+                            operand: output,
+                            split: Some((left, right)),
+                        });
+                    }
+                }
+            }
+            left = (left - 1) & members;
+        }
+        if let Some(best) = best {
+            subsets.insert(members, best);
+        }
+    }
+    Ok(subsets)
+}
+
+fn shares_axis(left: &NaryPlannerMetadata<'_>, right: &NaryPlannerMetadata<'_>) -> bool {
+    left.axes
+        .iter()
+        .any(|(axis, _)| right.axes.iter().any(|(candidate, _)| candidate == axis))
+}
+
+/// Emits the splits below `members` children first, so each pair is live when
+/// it is contracted.
+fn subset_sequence(
+    subsets: &HashMap<u64, SubsetPlan<'_>>,
+    members: u64,
+    sequence: &mut Vec<NaryMemberPair>,
+) {
+    if let Some((left, right)) = subsets[&members].split {
+        subset_sequence(subsets, left, sequence);
+        subset_sequence(subsets, right, sequence);
+        sequence.push((left, right));
+    }
+}
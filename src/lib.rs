@@ -2,8 +2,10 @@
 //!
 //! The [`einops!`] macro combines rearrange, reduce, repeat, composition, and
 //! decomposition operations. [`einsum!`] provides explicit-output,
-//! arbitrary-arity Einstein summation. Backend failures are returned as Candle
-//! errors.
+//! arbitrary-arity Einstein summation, and [`einops_shape!`] reads named axis
+//! lengths, which [`einops_with_sizes!`] also returns alongside its output.
+//! Backend failures are returned as Candle errors. [`Pattern`] and
+//! [`einsum()`] parse the same expressions and equations at runtime.
 //!
 //! Einsum equations require exactly one `->`, use whitespace-delimited named
 //! axes, and have one comma-separated input list per operand. Axes omitted from
@@ -83,15 +85,22 @@
 //!
 //! ## Dtypes, devices, and gradients
 //!
-//! `einsum!` never casts operands or transfers them between devices. Every
-//! operand in a multi-operand equation must have the same dtype and reside on
-//! the same device; mismatches return a contextual [`candle_core::Error`].
+//! By default, `einsum!` never casts operands or transfers them between
+//! devices. Every operand in a multi-operand equation must have the same dtype
+//! and reside on the same device; mismatches return a contextual
+//! [`candle_core::Error`]. The `promote = widest` and `device = operand(i)`
+//! options, or the `policy` of [`EinsumOptions`], convert mismatched operands
+//! once before planning, and `accumulate = f32`, or its `accumulate`, sums
+//! half-precision operands in a wider float type.
 //! Unary permutations preserve every dtype supported by the corresponding
 //! Candle operation. Binary equations without contracted labels use Candle
 //! multiplication, including its integer and BF16 support. True contractions
-//! lower through Candle matrix multiplication and therefore inherit its dtype
-//! and device support: unsupported combinations return an error rather than
-//! being silently converted.
+//! of `f16`, `bf16`, `f32`, and `f64` operands lower through Candle matrix
+//! multiplication and therefore inherit its device support: unsupported
+//! combinations return an error rather than being silently converted. Candle
+//! has no matmul for other dtypes, so integer and 8-bit float contractions
+//! multiply and sum in the operand dtype instead, in chunks of the contracted
+//! axes that bound the broadcast product to about a million elements.
 //!
 //! Einsum execution is assembled from tracked public Candle operations, so
 //! floating-point inputs participate in Candle autograd. Accelerator execution
@@ -117,13 +126,27 @@ extern crate self as candle_einops;
 
 mod backend;
 mod einsum;
+#[cfg(feature = "nn")]
+pub mod nn;
+mod pack;
+mod pattern;
+mod sizes;
+mod stack;
 
 /// The result type returned by [`einops!`] and [`Backend`] transformations.
 pub use candle_core::Result;
-pub use candle_einops_macros::{einops, einsum};
+pub use candle_einops_macros::{einops, einops_shape, einops_with_sizes, einsum};
 
-pub use backend::Backend;
-pub use einsum::PreparedDiagonalPlan;
+pub use backend::{Backend, Reducer};
+pub use einsum::{
+    EinsumDevicePolicy, EinsumOperandPolicy, EinsumOptions, EinsumPath, EinsumPathStep,
+    EinsumPlanCache, EinsumPlanCacheConfig, EinsumPlanCacheStats, EinsumPromotion, EinsumSemiring,
+    EinsumStrategy, PreparedDiagonalPlan, PreparedEinsumPlan, einsum, einsum_path,
+    einsum_path_with_strategy, einsum_with,
+};
+pub use pack::{PackedShapes, pack, unpack};
+pub use pattern::{Pattern, rearrange, reduce, repeat};
+pub use sizes::Sizes;
 
 /// Implementation details used by macros generated for this crate.
 ///
@@ -136,11 +159,18 @@ pub mod __private {
         benchmark_nary_planner_selects_exact, benchmark_pack_canonical_operand,
     };
     pub use crate::einsum::{
-        BinaryEinsumSpec, EinsumAxisPattern, EllipsisEinsumSpec, UnaryEinsumSpec,
+        BinaryEinsumSpec, EinsumAxisPattern, EinsumDevicePolicy, EinsumLabelReduction,
+        EinsumOperandPolicy, EinsumPromotion, EinsumSemiring, EllipsisEinsumSpec, UnaryEinsumSpec,
         einsum_operand_ref, execute_binary_einsum, execute_binary_ellipsis_einsum,
-        execute_binary_multiply, execute_canonical_binary_einsum, execute_nary_einsum,
-        execute_unary_einsum, execute_unary_ellipsis_einsum,
+        execute_binary_multiply, execute_canonical_binary_einsum, execute_einsum_with_policy,
+        execute_nary_einsum, execute_nary_einsum_with_path, execute_semiring_einsum,
+        execute_unary_einsum, execute_unary_ellipsis_einsum, reduce_einsum_labels,
     };
+    pub use crate::stack::{
+        BackendInputKind, EinopsInput, ListInput, ListInputKind, PassThroughInput, StackedTensors,
+        TensorInputKind, TensorList,
+    };
+    pub use candle_core::DType;
 }
 
 /// Specifies the operation used to reduce an axis
@@ -156,4 +186,43 @@ pub enum Operation {
     Mean,
     /// Multiply all elements
     Prod,
+    /// Take the logarithm of the summed exponentials, shifted by the maximum
+    /// for numerical stability
+    LogSumExp,
+    /// Get the population variance, computed in two passes
+    Var,
+    /// Get the population standard deviation, computed in two passes
+    Std,
+    /// Take the Euclidean (L2) norm
+    Norm,
+}
+
+/// Specifies how [`Backend::reduce_axes_with`] reduces an axis
+///
+/// Patterns produce [`ReduceStep::Custom`] for `reduce(...)` groups, so
+/// custom and built-in reductions can mix in one call.
+#[derive(Copy, Clone, Debug)]
+pub enum ReduceStep {
+    /// Reduce with a built-in operation
+    Operation(Operation),
+    /// Apply the [`Reducer`] passed to [`Backend::reduce_axes_with`]
+    Custom,
+}
+
+impl From<Operation> for ReduceStep {
+    fn from(operation: Operation) -> Self {
+        ReduceStep::Operation(operation)
+    }
+}
+
+/// Specifies the index returned when reducing a group of axes
+///
+/// Index reductions return `u32` tensors. When a group spans several axes,
+/// the index is flat over the group in the order its axes are written.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IndexOperation {
+    /// Take the index of the minimum value
+    ArgMin,
+    /// Take the index of the maximum value
+    ArgMax,
 }
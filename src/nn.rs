@@ -0,0 +1,598 @@
+//! Layers implementing [`candle_nn::Module`], for use in model definitions
+//! such as [`candle_nn::Sequential`].
+//!
+//! The layers parse their pattern once, when they are built, and apply it
+//! through [`Pattern::apply`] on every call, so they behave like the
+//! equivalent [`einops!`](crate::einops) expression. [`EinMix`] adds a
+//! learned contraction whose weight is shaped by its pattern, and
+//! [`RearrangeBackend`] converts checkpoint tensors to a model's layout as
+//! they are loaded.
+//!
+//! ```
+//! use candle_core::{DType, Device, Result, Tensor};
+//! use candle_einops::nn::{Rearrange, Reduce};
+//! use candle_nn::Module;
+//!
+//! # fn main() -> Result<()> {
+//! let patches = Rearrange::new(
+//!     "b c (h {p1}) (w {p2}) -> b (h w) (p1 p2 c)",
+//!     &[("p1", 4), ("p2", 4)],
+//! )?;
+//! let pool = Reduce::new("b n mean(d) -> b n", &[])?;
+//! let model = candle_nn::seq().add(patches).add(pool);
+//!
+//! let images = Tensor::zeros((2, 3, 16, 16), DType::F32, &Device::Cpu)?;
+//! assert_eq!(model.forward(&images)?.dims(), &[2, 16]);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+
+use candle_core::{DType, Device, Result, Shape, Tensor};
+use candle_nn::var_builder::SimpleBackend;
+use candle_nn::{Init, Module, VarBuilder};
+
+use crate::Pattern;
+use crate::einsum::{Equation, execute_binary_ellipsis_einsum};
+use crate::pattern::parse_axis_groups;
+
+/// A layer that rearranges its input with a runtime pattern.
+///
+/// Like [`rearrange`](crate::rearrange), the pattern must not reduce or
+/// repeat.
+#[derive(Clone, Debug)]
+pub struct Rearrange {
+    pattern: Pattern,
+    sizes: Vec<(String, usize)>,
+}
+
+impl Rearrange {
+    /// Parses `pattern`, with `sizes` supplying the length of every braced
+    /// `{name}` axis.
+    pub fn new(pattern: &str, sizes: &[(&str, usize)]) -> Result<Self> {
+        let pattern = Pattern::parse(pattern)?;
+        if pattern.reduces() {
+            candle_core::bail!("`Rearrange` patterns cannot reduce axes; use `Reduce`")
+        }
+        if pattern.repeats() {
+            candle_core::bail!("`Rearrange` patterns cannot repeat axes")
+        }
+        Ok(Self {
+            sizes: layer_sizes(&pattern, sizes)?,
+            pattern,
+        })
+    }
+
+    /// Returns the pattern applied by this layer.
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+}
+
+impl candle_nn::Module for Rearrange {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        apply_layer(&self.pattern, &self.sizes, xs)
+    }
+}
+
+impl fmt::Display for Rearrange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rearrange({})", self.pattern)
+    }
+}
+
+/// A layer that reduces its input with a runtime pattern such as
+/// `"b c mean(h w) -> b c"`.
+///
+/// Like [`reduce`](crate::reduce), the pattern may also rearrange the
+/// retained axes, but must not repeat.
+#[derive(Clone, Debug)]
+pub struct Reduce {
+    pattern: Pattern,
+    sizes: Vec<(String, usize)>,
+}
+
+impl Reduce {
+    /// Parses `pattern`, with `sizes` supplying the length of every braced
+    /// `{name}` axis.
+    pub fn new(pattern: &str, sizes: &[(&str, usize)]) -> Result<Self> {
+        let pattern = Pattern::parse(pattern)?;
+        if pattern.repeats() {
+            candle_core::bail!("`Reduce` patterns cannot repeat axes")
+        }
+        Ok(Self {
+            sizes: layer_sizes(&pattern, sizes)?,
+            pattern,
+        })
+    }
+
+    /// Returns the pattern applied by this layer.
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+}
+
+impl candle_nn::Module for Reduce {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        apply_layer(&self.pattern, &self.sizes, xs)
+    }
+}
+
+impl fmt::Display for Reduce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Reduce({})", self.pattern)
+    }
+}
+
+/// A linear layer whose weight and output are described by einops patterns,
+/// like `EinMix` in Python einops.
+///
+/// The input is decomposed by the left side of `pattern`, contracted with a
+/// weight whose axes are listed in `weight_shape`, offset by an optional bias
+/// over the output axes listed in `bias_shape`, and composed by the right
+/// side. Input axes that are in the weight but not in the output are summed.
+/// Patterns contain named axes and parenthesized groups; `sizes` gives the
+/// length of every weight and bias axis and of any axis needed to decompose
+/// the input.
+///
+/// The weight and bias are fetched from `vb` as `weight` and `bias`. New
+/// variables are initialized uniformly in `±sqrt(3 / fan_in)` and
+/// `±sqrt(1 / fan_in)`, where `fan_in` is the product of the contracted axes.
+///
+/// ```
+/// use candle_core::{DType, Device, Result, Tensor};
+/// use candle_einops::nn::EinMix;
+/// use candle_nn::{Module, VarBuilder, VarMap};
+///
+/// # fn main() -> Result<()> {
+/// let varmap = VarMap::new();
+/// let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+/// let project = EinMix::new(
+///     "b t c -> b t (h d)",
+///     "c h d",
+///     Some("h d"),
+///     &[("c", 32), ("h", 4), ("d", 8)],
+///     vb.pp("qkv"),
+/// )?;
+/// assert_eq!(project.weight().dims(), &[32, 4, 8]);
+///
+/// let tokens = Tensor::zeros((2, 10, 32), DType::F32, &Device::Cpu)?;
+/// assert_eq!(project.forward(&tokens)?.dims(), &[2, 10, 32]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct EinMix {
+    source: String,
+    // Decomposes the input into the axes of the contraction
+    pre: Option<Pattern>,
+    equation: Equation,
+    // Composes the contraction output into the requested groups
+    post: Option<Pattern>,
+    weight: Tensor,
+    bias: Option<Tensor>,
+    sizes: Vec<(String, usize)>,
+}
+
+impl EinMix {
+    /// Builds the layer, creating or loading its weight and bias through `vb`.
+    pub fn new(
+        pattern: &str,
+        weight_shape: &str,
+        bias_shape: Option<&str>,
+        sizes: &[(&str, usize)],
+        vb: VarBuilder,
+    ) -> Result<Self> {
+        let Some((left_text, right_text)) = pattern.split_once("->") else {
+            candle_core::bail!("expected `->`")
+        };
+        if right_text.contains("->") {
+            candle_core::bail!("EinMix pattern `{pattern}` contains more than one `->`")
+        }
+        let left = parse_axis_groups(left_text)?;
+        let right = parse_axis_groups(right_text)?;
+        let left_names = unique_axes(&left, "on the left")?;
+        let right_names = unique_axes(&right, "on the right")?;
+        let weight_names = flat_axes(weight_shape, "weight shape")?;
+        let size = |name: &str| {
+            sizes
+                .iter()
+                .find(|(candidate, _)| *candidate == name)
+                .map(|&(_, size)| size)
+        };
+
+        report_axes(
+            right_names
+                .iter()
+                .filter(|name| !left_names.contains(name) && !weight_names.contains(name)),
+            "Axes on the right are not in the input or weight",
+        )?;
+        report_axes(
+            weight_names
+                .iter()
+                .filter(|name| !left_names.contains(name) && !right_names.contains(name)),
+            "Weight axes are not in the pattern",
+        )?;
+        report_axes(
+            sizes
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .filter(|name| !left_names.contains(name) && !weight_names.contains(name)),
+            "Sizes are not used by the pattern or weight",
+        )?;
+        let weight_dims = sized_axes(&weight_names, &size, "weight")?;
+
+        let fan_in = weight_names
+            .iter()
+            .zip(&weight_dims)
+            .filter(|(name, _)| !right_names.contains(name))
+            .map(|(_, size)| *size)
+            .product::<usize>()
+            .max(1) as f64;
+        let weight_bound = (3. / fan_in).sqrt();
+        let weight = vb.get_with_hints(
+            weight_dims,
+            "weight",
+            Init::Uniform {
+                lo: -weight_bound,
+                up: weight_bound,
+            },
+        )?;
+
+        let bias = match bias_shape {
+            Some(bias_shape) => {
+                let bias_names = flat_axes(bias_shape, "bias shape")?;
+                report_axes(
+                    bias_names.iter().filter(|name| !right_names.contains(name)),
+                    "Bias axes are not in the output",
+                )?;
+                sized_axes(&bias_names, &size, "bias")?;
+                // The bias keeps every output axis so that it broadcasts
+                let bias_dims = right_names
+                    .iter()
+                    .map(|name| {
+                        if bias_names.contains(name) {
+                            size(name).expect("bias sizes were checked")
+                        } else {
+                            1
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let bias_bound = (1. / fan_in).sqrt();
+                Some(vb.get_with_hints(
+                    bias_dims,
+                    "bias",
+                    Init::Uniform {
+                        lo: -bias_bound,
+                        up: bias_bound,
+                    },
+                )?)
+            }
+            None => None,
+        };
+
+        let pre = if left.iter().any(|group| group.len() != 1) {
+            let decomposition = left
+                .iter()
+                .map(|group| match group.as_slice() {
+                    [name] => name.clone(),
+                    group => {
+                        let names = group
+                            .iter()
+                            .map(|name| match size(name) {
+                                Some(_) => format!("{{{name}}}"),
+                                None => name.clone(),
+                            })
+                            .collect::<Vec<_>>();
+                        format!("({})", names.join(" "))
+                    }
+                })
+                .collect::<Vec<_>>();
+            Some(Pattern::parse(&format!(
+                "{} -> {}",
+                decomposition.join(" "),
+                left_names.join(" ")
+            ))?)
+        } else {
+            None
+        };
+        let post = if right.iter().any(|group| group.len() != 1) {
+            Some(Pattern::parse(&format!(
+                "{} -> {}",
+                right_names.join(" "),
+                right_text.trim()
+            ))?)
+        } else {
+            None
+        };
+        let equation = Equation::parse(&format!(
+            "{}, {} -> {}",
+            left_names.join(" "),
+            weight_names.join(" "),
+            right_names.join(" ")
+        ))?;
+
+        Ok(Self {
+            source: pattern.to_string(),
+            pre,
+            equation,
+            post,
+            weight,
+            bias,
+            sizes: sizes
+                .iter()
+                .map(|&(name, size)| (name.to_string(), size))
+                .collect(),
+        })
+    }
+
+    /// Returns the weight, with one axis per name in the weight shape.
+    pub fn weight(&self) -> &Tensor {
+        &self.weight
+    }
+
+    /// Returns the bias, which has one axis per elementary output axis and
+    /// length 1 on the axes missing from the bias shape.
+    pub fn bias(&self) -> Option<&Tensor> {
+        self.bias.as_ref()
+    }
+}
+
+impl candle_nn::Module for EinMix {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        let input = match &self.pre {
+            Some(pre) => apply_layer(pre, &self.sizes, xs)?,
+            None => xs.clone(),
+        };
+        let output = self
+            .equation
+            .with_spec(|spec| execute_binary_ellipsis_einsum(&input, &self.weight, spec))?;
+        let output = match &self.bias {
+            Some(bias) => output.broadcast_add(bias)?,
+            None => output,
+        };
+        match &self.post {
+            Some(post) => post.apply(&output, &[]),
+            None => Ok(output),
+        }
+    }
+}
+
+impl fmt::Display for EinMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EinMix({})", self.source)
+    }
+}
+
+/// A [`VarBuilder`] backend that rearranges tensors as they are loaded.
+///
+/// Each rule pairs a glob over tensor names with a [`Rearrange`] pattern from
+/// the stored layout to the layout the model requests. `*` in a glob matches
+/// any run of characters, including `.`, and `?` matches one character. The
+/// first matching rule is applied; other tensors are fetched unchanged.
+/// Names are relative to the wrapped builder's prefix.
+///
+/// Conversions go through the same permute-and-compose lowering as
+/// [`einops!`](crate::einops), so a converted tensor is a strided view of the
+/// stored one whenever its layout allows. Tensors missing from the wrapped
+/// builder, such as new variables in a [`candle_nn::VarMap`], are created
+/// directly in the requested layout.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use candle_core::{DType, Device, Result, Tensor};
+/// use candle_einops::nn::RearrangeBackend;
+///
+/// # fn main() -> Result<()> {
+/// // A fused projection stored as `(3 h d) c`
+/// let stored = Tensor::zeros((3 * 4 * 8, 32), DType::F32, &Device::Cpu)?;
+/// let checkpoint = candle_nn::VarBuilder::from_tensors(
+///     HashMap::from([("layers.0.qkv.weight".to_string(), stored)]),
+///     DType::F32,
+///     &Device::Cpu,
+/// );
+///
+/// let vb = RearrangeBackend::new(checkpoint)
+///     .with_rule(
+///         "layers.*.qkv.weight",
+///         "({three} {h} d) c -> c h three d",
+///         &[("three", 3), ("h", 4)],
+///     )?
+///     .into_var_builder();
+/// let weight = vb.pp("layers.0.qkv").get((32, 4, 3, 8), "weight")?;
+/// assert_eq!(weight.dims(), &[32, 4, 3, 8]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RearrangeBackend<'a> {
+    inner: VarBuilder<'a>,
+    rules: Vec<(String, Rearrange)>,
+}
+
+impl<'a> RearrangeBackend<'a> {
+    /// Wraps `inner` with no rules.
+    pub fn new(inner: VarBuilder<'a>) -> Self {
+        Self {
+            inner,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Adds a rule applying `pattern` to tensors whose name matches `glob`.
+    ///
+    /// `sizes` supplies the length of every braced `{name}` axis.
+    pub fn with_rule(mut self, glob: &str, pattern: &str, sizes: &[(&str, usize)]) -> Result<Self> {
+        self.rules
+            .push((glob.to_string(), Rearrange::new(pattern, sizes)?));
+        Ok(self)
+    }
+
+    /// Returns a builder that loads through this backend, with the dtype and
+    /// device of the wrapped builder.
+    pub fn into_var_builder(self) -> VarBuilder<'a> {
+        let dtype = self.inner.dtype();
+        let device = self.inner.device().clone();
+        VarBuilder::from_backend(Box::new(self), dtype, device)
+    }
+
+    fn rule(&self, name: &str) -> Option<&Rearrange> {
+        self.rules
+            .iter()
+            .find(|(glob, _)| glob_matches(glob, name))
+            .map(|(_, rearrange)| rearrange)
+    }
+}
+
+impl SimpleBackend for RearrangeBackend<'_> {
+    fn get(&self, s: Shape, name: &str, h: Init, dtype: DType, dev: &Device) -> Result<Tensor> {
+        let tensor = match self.rule(name) {
+            Some(rearrange) if self.inner.contains_tensor(name) => {
+                let tensor = rearrange.forward(&self.inner.get_unchecked_dtype(name, dtype)?)?;
+                if tensor.shape() != &s {
+                    Err(candle_core::Error::UnexpectedShape {
+                        msg: format!("shape mismatch for {name} after applying {rearrange}"),
+                        expected: s,
+                        got: tensor.shape().clone(),
+                    }
+                    .bt())?
+                }
+                tensor
+            }
+            _ => self.inner.get_with_hints_dtype(s, name, h, dtype)?,
+        };
+        tensor.to_device(dev)
+    }
+
+    fn get_unchecked(&self, name: &str, dtype: DType, dev: &Device) -> Result<Tensor> {
+        let tensor = self.inner.get_unchecked_dtype(name, dtype)?;
+        let tensor = match self.rule(name) {
+            Some(rearrange) => rearrange.forward(&tensor)?,
+            None => tensor,
+        };
+        tensor.to_device(dev)
+    }
+
+    fn contains_tensor(&self, name: &str) -> bool {
+        self.inner.contains_tensor(name)
+    }
+}
+
+impl fmt::Debug for RearrangeBackend<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RearrangeBackend")
+            .field("prefix", &self.inner.prefix())
+            .field("rules", &self.rules)
+            .finish()
+    }
+}
+
+/// Matches `name` against a glob where `*` matches any run of characters
+/// and `?` matches one character.
+fn glob_matches(glob: &str, name: &str) -> bool {
+    let glob = glob.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut g, mut n) = (0, 0);
+    // Position after the last `*` and the name position it is retried from
+    let mut backtrack = None;
+    while n < name.len() {
+        match glob.get(g) {
+            Some('*') => {
+                backtrack = Some((g + 1, n));
+                g += 1;
+            }
+            Some('?') => (g, n) = (g + 1, n + 1),
+            Some(&character) if character == name[n] => (g, n) = (g + 1, n + 1),
+            _ => match backtrack {
+                Some((star, start)) => {
+                    (g, n) = (star, start + 1);
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&character| character == '*')
+}
+
+fn unique_axes(groups: &[Vec<String>], side: &str) -> Result<Vec<String>> {
+    let mut seen = HashSet::new();
+    for name in groups.iter().flatten() {
+        if !seen.insert(name) {
+            candle_core::bail!("Axis `{name}` appears more than once {side}")
+        }
+    }
+    Ok(groups.iter().flatten().cloned().collect())
+}
+
+fn flat_axes(text: &str, kind: &str) -> Result<Vec<String>> {
+    let groups = parse_axis_groups(text)?;
+    if groups.iter().any(|group| group.len() != 1) {
+        candle_core::bail!("The {kind} `{text}` cannot contain groups")
+    }
+    unique_axes(&groups, &format!("in the {kind}"))
+}
+
+fn sized_axes(
+    names: &[String],
+    size: &impl Fn(&str) -> Option<usize>,
+    kind: &str,
+) -> Result<Vec<usize>> {
+    report_axes(
+        names.iter().filter(|name| size(name).is_none()),
+        &format!("Sizes are required for {kind} axes"),
+    )?;
+    Ok(names.iter().filter_map(|name| size(name)).collect())
+}
+
+fn report_axes<S: AsRef<str>>(axes: impl Iterator<Item = S>, message: &str) -> Result<()> {
+    let axes = axes
+        .map(|axis| axis.as_ref().to_string())
+        .collect::<Vec<_>>();
+    if !axes.is_empty() {
+        candle_core::bail!("{message}: {}", axes.join(", "))
+    }
+    Ok(())
+}
+
+fn layer_sizes(pattern: &Pattern, sizes: &[(&str, usize)]) -> Result<Vec<(String, usize)>> {
+    if pattern.splits() {
+        candle_core::bail!(
+            "einops expression `{pattern}` splits its output, which a layer cannot return"
+        )
+    }
+    Ok(sizes
+        .iter()
+        .map(|&(name, size)| (name.to_string(), size))
+        .collect())
+}
+
+fn apply_layer(pattern: &Pattern, sizes: &[(String, usize)], xs: &Tensor) -> Result<Tensor> {
+    let sizes = sizes
+        .iter()
+        .map(|(name, size)| (name.as_str(), *size))
+        .collect::<Vec<_>>();
+    pattern.apply(xs, &sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_matches;
+
+    #[test]
+    fn globs_match_tensor_names() {
+        assert!(glob_matches("layers.*.qkv.weight", "layers.0.qkv.weight"));
+        assert!(glob_matches("layers.*.qkv.weight", "layers.10.qkv.weight"));
+        assert!(glob_matches("*.weight", "encoder.layers.3.mlp.weight"));
+        assert!(glob_matches("layers.?.mlp.*", "layers.7.mlp.bias"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("a*b*c", "aXbYbZc"));
+        assert!(!glob_matches("layers.*.qkv.weight", "layers.0.qkv.bias"));
+        assert!(!glob_matches("layers.?.mlp.*", "layers.12.mlp.bias"));
+        assert!(!glob_matches("*.weight", "weight"));
+        assert!(!glob_matches("a*b*c", "aXbYbZ"));
+    }
+}
@@ -0,0 +1,238 @@
+use candle_core::{Result, Tensor};
+
+use crate::Backend;
+
+/// A parsed pack pattern such as `"b .. c"`.
+struct PackPattern {
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+impl PackPattern {
+    fn parse(pattern: &str) -> Result<Self> {
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        let mut captured = false;
+        for label in pattern.split_whitespace() {
+            // `*` is accepted as the Python einops spelling of the capture
+            if label == ".." || label == "*" {
+                if captured {
+                    candle_core::bail!("pack pattern `{pattern}` contains more than one `..`")
+                }
+                captured = true;
+                continue;
+            }
+            let mut characters = label.chars();
+            let valid_start = characters
+                .next()
+                .is_some_and(|character| character == '_' || character.is_alphabetic());
+            if !valid_start
+                || !characters.all(|character| character == '_' || character.is_alphanumeric())
+            {
+                candle_core::bail!("invalid pack axis label `{label}`")
+            }
+            if before.iter().chain(&after).any(|name| name == label) {
+                candle_core::bail!("Axis `{label}` appears more than once in pack pattern")
+            }
+            if captured {
+                after.push(label.to_owned());
+            } else {
+                before.push(label.to_owned());
+            }
+        }
+        if !captured {
+            candle_core::bail!("pack pattern `{pattern}` must contain exactly one `..`")
+        }
+        Ok(Self { before, after })
+    }
+
+    fn explicit_rank(&self) -> usize {
+        self.before.len() + self.after.len()
+    }
+
+    fn axis_name(&self, axis: usize) -> &str {
+        self.before
+            .iter()
+            .chain(&self.after)
+            .nth(axis)
+            .expect("named axis index is in range")
+    }
+}
+
+/// The captured shapes recorded by [`pack`] and consumed by [`unpack`].
+///
+/// Each entry holds the axes one input contributed to the packed `..` axis.
+/// An empty entry means that input had no captured axes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackedShapes {
+    shapes: Vec<Vec<usize>>,
+}
+
+impl PackedShapes {
+    /// Creates a record from explicit captured shapes.
+    pub fn new(shapes: Vec<Vec<usize>>) -> Self {
+        Self { shapes }
+    }
+
+    /// Returns the captured shape of every packed tensor, in input order.
+    #[must_use]
+    pub fn shapes(&self) -> &[Vec<usize>] {
+        &self.shapes
+    }
+
+    /// Returns the number of packed tensors.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    /// Returns `true` if the record describes no tensors.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    fn lengths(&self) -> Result<Vec<usize>> {
+        self.shapes
+            .iter()
+            .map(|shape| {
+                shape
+                    .iter()
+                    .try_fold(1usize, |product, &extent| product.checked_mul(extent))
+                    .ok_or_else(|| candle_core::Error::msg("packed shape product overflows usize"))
+            })
+            .collect()
+    }
+}
+
+/// Packs tensors of varying rank into one tensor along a `..` axis.
+///
+/// The named axes of `pattern` must have equal lengths in every tensor. The
+/// `..` captures the remaining axes of each tensor, which are flattened and
+/// concatenated into one axis. `*` is accepted in place of `..`, matching
+/// Python einops. All tensors must share a dtype and device.
+///
+/// ```
+/// use candle_core::{DType, Device, Result, Tensor};
+/// use candle_einops::{pack, unpack};
+///
+/// # fn main() -> Result<()> {
+/// let class_token = Tensor::zeros((2, 8), DType::F32, &Device::Cpu)?;
+/// let patches = Tensor::ones((2, 4, 4, 8), DType::F32, &Device::Cpu)?;
+/// let (tokens, shapes) = pack(&[&class_token, &patches], "b .. c")?;
+/// assert_eq!(tokens.dims(), &[2, 17, 8]);
+///
+/// let restored = unpack(&tokens, &shapes, "b .. c")?;
+/// assert_eq!(restored[1].dims(), patches.dims());
+/// # Ok(())
+/// # }
+/// ```
+pub fn pack<T: AsRef<Tensor>>(tensors: &[T], pattern: &str) -> Result<(Tensor, PackedShapes)> {
+    let parsed = PackPattern::parse(pattern)?;
+    let Some(first) = tensors.first() else {
+        candle_core::bail!("pack requires at least one tensor")
+    };
+    let first = first.as_ref();
+    let position = parsed.before.len();
+
+    let mut shapes = Vec::with_capacity(tensors.len());
+    let mut flattened = Vec::with_capacity(tensors.len());
+    for (index, tensor) in tensors.iter().enumerate() {
+        let tensor = tensor.as_ref();
+        if tensor.dtype() != first.dtype() {
+            candle_core::bail!(
+                "pack operands have different dtypes: operand 0 {:?}, operand {index} {:?}",
+                first.dtype(),
+                tensor.dtype()
+            )
+        }
+        if !tensor.device().same_device(first.device()) {
+            candle_core::bail!(
+                "pack operands are on different devices: operand 0 {:?}, operand {index} {:?}",
+                first.device(),
+                tensor.device()
+            )
+        }
+        let shape = Backend::shape(tensor);
+        let capture = shape
+            .len()
+            .checked_sub(parsed.explicit_rank())
+            .ok_or_else(|| {
+                candle_core::Error::msg(format!(
+                    "pack operand {index} has rank {}, but pattern `{pattern}` requires at least {} axes",
+                    shape.len(),
+                    parsed.explicit_rank()
+                ))
+            })?;
+        let captured = shape[position..position + capture].to_vec();
+        let mut packed_shape = shape[..position].to_vec();
+        packed_shape.push(captured.iter().product());
+        packed_shape.extend_from_slice(&shape[position + capture..]);
+        flattened.push(Backend::reshape(tensor, &packed_shape)?);
+        shapes.push(captured);
+    }
+
+    let reference = flattened[0].dims();
+    for (index, tensor) in flattened.iter().enumerate().skip(1) {
+        for (axis, (&extent, &expected)) in tensor.dims().iter().zip(reference).enumerate() {
+            if axis != position && extent != expected {
+                let name = parsed.axis_name(if axis < position { axis } else { axis - 1 });
+                candle_core::bail!(
+                    "pack operand {index} axis `{name}` has length {extent}, expected {expected}"
+                )
+            }
+        }
+    }
+
+    let packed = if flattened.len() == 1 {
+        flattened.pop().expect("one packed tensor")
+    } else {
+        Tensor::cat(&flattened, position).map_err(|error| error.context("pack concatenation"))?
+    };
+    Ok((packed, PackedShapes { shapes }))
+}
+
+/// Splits a tensor produced by [`pack`] back into tensors of the recorded
+/// shapes.
+///
+/// `pattern` must name the same axes that were used for packing. Every
+/// returned tensor is a view of `packed` where Candle can reshape without
+/// copying.
+pub fn unpack(packed: &Tensor, shapes: &PackedShapes, pattern: &str) -> Result<Vec<Tensor>> {
+    let parsed = PackPattern::parse(pattern)?;
+    let position = parsed.before.len();
+    let shape = Backend::shape(packed);
+    if shape.len() != parsed.explicit_rank() + 1 {
+        candle_core::bail!(
+            "unpack input has rank {}, but pattern `{pattern}` requires {} axes",
+            shape.len(),
+            parsed.explicit_rank() + 1
+        )
+    }
+    let lengths = shapes.lengths()?;
+    let total = lengths
+        .iter()
+        .try_fold(0usize, |sum, &length| sum.checked_add(length))
+        .ok_or_else(|| candle_core::Error::msg("packed shape sum overflows usize"))?;
+    if total != shape[position] {
+        candle_core::bail!(
+            "unpack axis `..` has length {}, but the packed shapes describe {total} elements",
+            shape[position]
+        )
+    }
+
+    let mut offset = 0;
+    shapes
+        .shapes
+        .iter()
+        .zip(lengths)
+        .map(|(captured, length)| {
+            let piece = packed.narrow(position, offset, length)?;
+            offset += length;
+            let mut output_shape = shape[..position].to_vec();
+            output_shape.extend_from_slice(captured);
+            output_shape.extend_from_slice(&shape[position + 1..]);
+            Backend::reshape(&piece, &output_shape)
+        })
+        .collect()
+}
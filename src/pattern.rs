@@ -0,0 +1,589 @@
+use std::fmt;
+use std::str::FromStr;
+
+use candle_core::{Error, Result, Tensor};
+use candle_einops_syntax::einops::{
+    self, AxisSize, Expression, GroupAxis, InputAxis, OutputAxis, OutputGroup,
+};
+
+use crate::backend::built_in_operations;
+use crate::sizes::Sizes;
+use crate::stack::{StackedTensors, TensorList};
+use crate::{Backend, IndexOperation, Operation, ReduceStep, Reducer};
+
+#[derive(Clone, Copy, Debug)]
+enum Reduction {
+    Value(ReduceStep),
+    // Index reductions change the dtype, so they go through
+    // `Backend::arg_reduce_axes` instead of `Backend::reduce_axes`
+    Index(IndexOperation),
+}
+
+impl Reduction {
+    fn new(reduction: &einops::Reduction) -> Self {
+        let operation = match reduction {
+            einops::Reduction::Min => Operation::Min,
+            einops::Reduction::Max => Operation::Max,
+            einops::Reduction::Sum => Operation::Sum,
+            einops::Reduction::Mean => Operation::Mean,
+            einops::Reduction::Prod => Operation::Prod,
+            einops::Reduction::LogSumExp => Operation::LogSumExp,
+            einops::Reduction::Var => Operation::Var,
+            einops::Reduction::Std => Operation::Std,
+            einops::Reduction::Norm => Operation::Norm,
+            einops::Reduction::Custom => return Reduction::Value(ReduceStep::Custom),
+            einops::Reduction::ArgMin => return Reduction::Index(IndexOperation::ArgMin),
+            einops::Reduction::ArgMax => return Reduction::Index(IndexOperation::ArgMax),
+            einops::Reduction::Braced(_) => {
+                unreachable!("braced operations are rejected when the pattern is parsed")
+            }
+        };
+        Reduction::Value(operation.into())
+    }
+}
+
+/// Parses a list of named axes and parenthesized groups of named axes, such
+/// as one side of an `EinMix` pattern.
+#[cfg(feature = "nn")]
+pub(crate) fn parse_axis_groups(text: &str) -> Result<Vec<Vec<String>>> {
+    einops::parse_axis_groups(text).map_err(Error::msg)
+}
+
+/// Rejects the braced operations and fields that only the macro can
+/// evaluate.
+fn check_runtime_expression(expression: &Expression) -> Result<()> {
+    let mut reductions = Vec::new();
+    let mut sizes = Vec::new();
+    for axis in &expression.decomposition {
+        match axis {
+            InputAxis::Named { reduction, .. } | InputAxis::Ellipsis { reduction } => {
+                reductions.extend(reduction)
+            }
+            InputAxis::Group(group) => {
+                reductions.extend(group.iter().filter_map(|axis| axis.reduction.as_ref()));
+                sizes.extend(group.iter().filter_map(|axis| axis.size.as_ref()));
+            }
+        }
+    }
+    for group in &expression.composition {
+        let axes = match group {
+            OutputGroup::Individual(axis) => std::slice::from_ref(axis),
+            OutputGroup::Combined(group) => group.as_slice(),
+        };
+        sizes.extend(axes.iter().filter_map(|axis| match axis {
+            OutputAxis::New(size) => Some(size),
+            _ => None,
+        }));
+    }
+    if reductions
+        .iter()
+        .any(|reduction| matches!(reduction, einops::Reduction::Braced(_)))
+    {
+        candle_core::bail!(
+            "Braced reduction operations such as `{{op}}(h w)` require `einops!`; write the operation name into the pattern"
+        )
+    }
+    for size in sizes {
+        if let AxisSize::Braced(name) = size
+            && name.contains('.')
+        {
+            candle_core::bail!(
+                "Braced field `{{{name}}}` requires `einops!`; name the axis with an identifier and supply its size"
+            )
+        }
+    }
+    Ok(())
+}
+
+fn resolve_size(size: &AxisSize, sizes: &[(&str, usize)]) -> Result<usize> {
+    match size {
+        AxisSize::Literal(size) => Ok(*size),
+        AxisSize::Braced(name) => sizes
+            .iter()
+            .find(|(candidate, _)| candidate == name)
+            .map(|&(_, size)| size)
+            .ok_or_else(|| Error::msg(format!("No size was provided for axis `{name}`"))),
+    }
+}
+
+/// Resolves the extents of a decomposition group, deriving at most one axis.
+fn resolve_group(
+    group: &[GroupAxis],
+    dimension: usize,
+    sizes: &[(&str, usize)],
+) -> Result<Vec<usize>> {
+    let mut extents = Vec::with_capacity(group.len());
+    let mut derived = None;
+    let mut factor = Some(1usize);
+    for (index, axis) in group.iter().enumerate() {
+        match &axis.size {
+            Some(size) => {
+                let size = resolve_size(size, sizes)?;
+                factor = factor.and_then(|product| product.checked_mul(size));
+                extents.push(size);
+            }
+            None => {
+                derived = Some(index);
+                extents.push(0);
+            }
+        }
+    }
+    let factor =
+        factor.ok_or_else(|| Error::msg("decomposition factor product overflows usize"))?;
+    match derived {
+        Some(index) => {
+            if factor == 0 {
+                candle_core::bail!("decomposition factor must be non-zero")
+            }
+            if !dimension.is_multiple_of(factor) {
+                candle_core::bail!(
+                    "dimension size {dimension} is not divisible by decomposition factor {factor}"
+                )
+            }
+            extents[index] = dimension / factor;
+        }
+        None if factor != dimension => candle_core::bail!(
+            "dimension size {dimension} does not match decomposition product {factor}"
+        ),
+        None => {}
+    }
+    Ok(extents)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AxisKey<'a> {
+    Named(&'a str),
+    Ellipsis(usize),
+    Anonymous,
+}
+
+/// An einops expression parsed at runtime.
+///
+/// Patterns use the same grammar as [`einops!`](crate::einops), including
+/// `..`, decomposition groups such as `(b1:2 b2)`, reductions such as
+/// `sum(h w)`, checked sizes such as `c:3`, and new axes such as `copy:5`. A
+/// braced axis `{n}` takes its size from the sizes supplied to
+/// [`Pattern::apply`]. Malformed patterns return the diagnostics the macro
+/// reports at compile time. A leading `axis *` on the right splits the output
+/// with [`Pattern::apply_split`].
+///
+/// ```
+/// use candle_core::{Device, Result, Tensor};
+/// use candle_einops::Pattern;
+///
+/// # fn main() -> Result<()> {
+/// let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+/// let pattern = Pattern::parse("b (h {half}) w -> b h (w {half})")?;
+/// let output = pattern.apply(&input, &[("half", 3)])?;
+/// assert_eq!(output.dims(), &[2, 1, 12]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    source: String,
+    decomposition: Vec<InputAxis>,
+    composition: Vec<OutputGroup>,
+    split: bool,
+}
+
+impl Pattern {
+    /// Parses an einops expression.
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let expression = Expression::parse(pattern).map_err(Error::msg)?;
+        check_runtime_expression(&expression)?;
+        Ok(Self {
+            source: pattern.to_string(),
+            decomposition: expression.decomposition,
+            composition: expression.composition,
+            split: expression.split,
+        })
+    }
+
+    /// Returns `true` if the pattern reduces at least one axis.
+    pub fn reduces(&self) -> bool {
+        self.decomposition.iter().any(|axis| match axis {
+            InputAxis::Named { reduction, .. } | InputAxis::Ellipsis { reduction } => {
+                reduction.is_some()
+            }
+            InputAxis::Group(group) => group.iter().any(|axis| axis.reduction.is_some()),
+        })
+    }
+
+    /// Returns `true` if the pattern repeats along a new axis.
+    ///
+    /// Inserting a literal `1` axis is not a repetition.
+    pub fn repeats(&self) -> bool {
+        self.output_axes().any(
+            |axis| matches!(axis, OutputAxis::New(size) if !matches!(size, AxisSize::Literal(1))),
+        )
+    }
+
+    /// Returns `true` if the pattern splits its output along a leading
+    /// `axis *`, which requires [`Pattern::apply_split`].
+    pub fn splits(&self) -> bool {
+        self.split
+    }
+
+    fn output_axes(&self) -> impl Iterator<Item = &OutputAxis> {
+        self.composition.iter().flat_map(|group| match group {
+            OutputGroup::Individual(axis) => std::slice::from_ref(axis),
+            OutputGroup::Combined(group) => group.as_slice(),
+        })
+    }
+
+    /// Applies the pattern through [`Backend`].
+    ///
+    /// `sizes` supplies the length of every braced `{name}` axis. Like the
+    /// macro, the steps are decomposition, reduction, permutation, repetition,
+    /// and composition, and steps that are not required are skipped.
+    pub fn apply(&self, tensor: &Tensor, sizes: &[(&str, usize)]) -> Result<Tensor> {
+        self.check_unsplit()?;
+        self.apply_input(tensor, sizes, None, None)
+    }
+
+    /// Applies the pattern like [`Pattern::apply`] and also returns the
+    /// length of every named input axis.
+    pub fn apply_with_sizes(
+        &self,
+        tensor: &Tensor,
+        sizes: &[(&str, usize)],
+    ) -> Result<(Tensor, Sizes)> {
+        self.check_unsplit()?;
+        let mut resolved = Sizes::default();
+        let output = self.apply_input(tensor, sizes, None, Some(&mut resolved))?;
+        Ok((output, resolved))
+    }
+
+    /// Applies a pattern whose `reduce(...)` groups use a custom [`Reducer`].
+    ///
+    /// Built-in reductions in the same pattern are applied as usual.
+    pub fn apply_with_reducer(
+        &self,
+        tensor: &Tensor,
+        sizes: &[(&str, usize)],
+        reducer: &dyn Reducer,
+    ) -> Result<Tensor> {
+        self.check_unsplit()?;
+        self.apply_input(tensor, sizes, Some(reducer), None)
+    }
+
+    /// Applies a split pattern such as `"b (three:3 d) -> three * b d"` and
+    /// returns one tensor per index of the leading output axis.
+    ///
+    /// Every piece is a view of the transformed tensor.
+    pub fn apply_split(&self, tensor: &Tensor, sizes: &[(&str, usize)]) -> Result<Vec<Tensor>> {
+        if !self.split {
+            candle_core::bail!(
+                "einops expression `{}` has no split marker `*`; use `Pattern::apply`",
+                self.source
+            )
+        }
+        Backend::unbind(self.apply_input(tensor, sizes, None, None)?)
+    }
+
+    fn check_unsplit(&self) -> Result<()> {
+        if self.split {
+            candle_core::bail!(
+                "einops expression `{}` splits its output; use `Pattern::apply_split`",
+                self.source
+            )
+        }
+        Ok(())
+    }
+
+    /// Applies the pattern to a list of equally shaped tensors.
+    ///
+    /// The first axis of the pattern names the list, as in Python einops.
+    /// Elements are stacked as part of the first transformation, and a
+    /// reduction over the list axis combines them without stacking.
+    pub fn apply_list<T: AsRef<Tensor>>(
+        &self,
+        tensors: &[T],
+        sizes: &[(&str, usize)],
+    ) -> Result<Tensor> {
+        self.check_unsplit()?;
+        self.apply_input(&StackedTensors::new(tensors.tensors())?, sizes, None, None)
+    }
+
+    fn apply_input<B>(
+        &self,
+        input: B,
+        sizes: &[(&str, usize)],
+        reducer: Option<&dyn Reducer>,
+        resolved: Option<&mut Sizes>,
+    ) -> Result<Tensor>
+    where
+        B: Backend<Output = Tensor> + Copy,
+    {
+        let shape = Backend::shape(input);
+        let has_ellipsis = self
+            .decomposition
+            .iter()
+            .any(|axis| matches!(axis, InputAxis::Ellipsis { .. }));
+        let minimum_rank = self.decomposition.len() - usize::from(has_ellipsis);
+        if shape.len() < minimum_rank {
+            candle_core::bail!(
+                "shape index {} out of range for rank {}; einops expression requires at least {} axes",
+                minimum_rank - 1,
+                shape.len(),
+                minimum_rank,
+            )
+        }
+        if !has_ellipsis && shape.len() != minimum_rank {
+            candle_core::bail!(
+                "einops expression `{}` expects {minimum_rank} axes, input rank is {}",
+                self.source,
+                shape.len()
+            )
+        }
+        let ignored_len = shape.len() - minimum_rank;
+
+        // Step 1, decomposing dimensions into their elementary axes
+        let mut axes = Vec::new();
+        let mut requires_decomposition = false;
+        let mut dimensions = shape.iter().copied();
+        for axis in &self.decomposition {
+            match axis {
+                InputAxis::Named {
+                    name,
+                    size,
+                    reduction,
+                } => {
+                    let extent = dimensions.next().expect("rank was validated");
+                    if let Some(size) = size
+                        && extent != *size
+                    {
+                        candle_core::bail!("axis `{name}` has length {extent}, expected {size}")
+                    }
+                    axes.push((
+                        AxisKey::Named(name),
+                        extent,
+                        reduction.as_ref().map(Reduction::new),
+                    ));
+                }
+                InputAxis::Ellipsis { reduction } => {
+                    for index in 0..ignored_len {
+                        let extent = dimensions.next().expect("rank was validated");
+                        axes.push((
+                            AxisKey::Ellipsis(index),
+                            extent,
+                            reduction.as_ref().map(Reduction::new),
+                        ));
+                    }
+                }
+                InputAxis::Group(group) => {
+                    let dimension = dimensions.next().expect("rank was validated");
+                    let extents = resolve_group(group, dimension, sizes)?;
+                    for (axis, extent) in group.iter().zip(extents) {
+                        let key = axis
+                            .name
+                            .as_deref()
+                            .map_or(AxisKey::Anonymous, AxisKey::Named);
+                        axes.push((key, extent, axis.reduction.as_ref().map(Reduction::new)));
+                    }
+                    requires_decomposition = true;
+                }
+            }
+        }
+
+        if let Some(resolved) = resolved {
+            *resolved = axes
+                .iter()
+                .filter_map(|&(key, extent, _)| match key {
+                    AxisKey::Named(name) => Some((name, extent)),
+                    _ => None,
+                })
+                .collect();
+        }
+
+        // The first transformation consumes the input, later ones the output
+        let mut output: Option<Tensor> = None;
+        macro_rules! step {
+            ($method:ident($($argument:expr),*)) => {
+                output = Some(match output.take() {
+                    Some(tensor) => Backend::$method(&tensor, $($argument),*)?,
+                    None => Backend::$method(input, $($argument),*)?,
+                })
+            };
+        }
+        if requires_decomposition {
+            let extents = axes
+                .iter()
+                .map(|&(_, extent, _)| extent)
+                .collect::<Vec<_>>();
+            step!(reshape(&extents));
+        }
+
+        // Step 2, reducing dimensions
+        let mut reductions = Vec::new();
+        let mut index_reduction = None;
+        for (index, &(_, _, operation)) in axes.iter().enumerate() {
+            match operation {
+                Some(Reduction::Value(step)) => reductions.push((index, step)),
+                Some(Reduction::Index(operation)) => index_reduction
+                    .get_or_insert((Vec::new(), operation))
+                    .0
+                    .push(index),
+                None => {}
+            }
+        }
+        if let Some((index_axes, operation)) = index_reduction {
+            step!(arg_reduce_axes(&index_axes, operation));
+        } else if !reductions.is_empty() {
+            match (reducer, built_in_operations(&reductions)) {
+                (Some(reducer), _) => step!(reduce_axes_with(&mut reductions, reducer)),
+                (None, Some(mut operations)) => step!(reduce_axes(&mut operations)),
+                (None, None) => candle_core::bail!(
+                    "einops expression `{}` uses `reduce(...)`; use `Pattern::apply_with_reducer`",
+                    self.source
+                ),
+            }
+        }
+        let kept = axes
+            .into_iter()
+            .filter(|(_, _, operation)| operation.is_none())
+            .map(|(key, extent, _)| (key, extent))
+            .collect::<Vec<_>>();
+        let position = |key: AxisKey| {
+            kept.iter()
+                .position(|&(candidate, _)| candidate == key)
+                .expect("right-hand axes were validated against the left")
+        };
+
+        // Resolve every elementary output axis to its source position or a
+        // repeated length, and the number of elementary axes in each group
+        let mut permutation = Vec::new();
+        let mut repeat = Vec::new();
+        let mut extents = Vec::new();
+        let mut group_lengths = Vec::new();
+        let mut requires_composition = false;
+        for group in &self.composition {
+            let group_axes = match group {
+                OutputGroup::Individual(axis) => std::slice::from_ref(axis),
+                OutputGroup::Combined(group) => {
+                    requires_composition = true;
+                    group.as_slice()
+                }
+            };
+            let start = extents.len();
+            for axis in group_axes {
+                match axis {
+                    OutputAxis::Existing(name) => {
+                        let index = position(AxisKey::Named(name));
+                        permutation.push(index);
+                        extents.push(kept[index].1);
+                    }
+                    OutputAxis::Ellipsis => {
+                        for ignored in 0..ignored_len {
+                            let index = position(AxisKey::Ellipsis(ignored));
+                            permutation.push(index);
+                            extents.push(kept[index].1);
+                        }
+                    }
+                    OutputAxis::New(size) => {
+                        let size = resolve_size(size, sizes)?;
+                        repeat.push((extents.len(), size));
+                        extents.push(size);
+                    }
+                }
+            }
+            match group {
+                OutputGroup::Individual(_) => {
+                    group_lengths.extend(std::iter::repeat_n(1, extents.len() - start))
+                }
+                OutputGroup::Combined(_) => group_lengths.push(extents.len() - start),
+            }
+        }
+        let composition_shape = || {
+            let mut cursor = 0;
+            group_lengths
+                .iter()
+                .map(|&length| {
+                    let group = &extents[cursor..cursor + length];
+                    cursor += length;
+                    group
+                        .iter()
+                        .try_fold(1usize, |product, &extent| product.checked_mul(extent))
+                        .ok_or_else(|| Error::msg("composition group product overflows usize"))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+
+        // Steps 3 to 5, permuting, repeating, and composing
+        let requires_permute = permutation.windows(2).any(|window| window[0] > window[1]);
+        if requires_permute && repeat.is_empty() && requires_composition {
+            step!(permute_and_compose(
+                &permutation,
+                &composition_shape()?,
+                &group_lengths
+            ));
+        } else {
+            if requires_permute {
+                step!(transpose(&permutation));
+            }
+            if !repeat.is_empty() {
+                step!(add_axes(extents.len(), &repeat));
+            }
+            if requires_composition {
+                step!(compose_axes(&composition_shape()?, &group_lengths));
+            }
+        }
+        match output {
+            Some(output) => Ok(output),
+            None => Backend::reshape(input, &shape),
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        Self::parse(pattern)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Rearranges `tensor` with a runtime pattern.
+///
+/// The pattern may transpose, compose, decompose, squeeze, and insert `1`
+/// axes, but must not reduce or repeat.
+pub fn rearrange(tensor: &Tensor, pattern: &str, sizes: &[(&str, usize)]) -> Result<Tensor> {
+    let pattern = Pattern::parse(pattern)?;
+    if pattern.reduces() {
+        candle_core::bail!("`rearrange` patterns cannot reduce axes; use `reduce`")
+    }
+    if pattern.repeats() {
+        candle_core::bail!("`rearrange` patterns cannot repeat axes; use `repeat`")
+    }
+    pattern.apply(tensor, sizes)
+}
+
+/// Reduces `tensor` with a runtime pattern such as `"b sum(h w) c -> b c"`.
+///
+/// The pattern may also rearrange the retained axes, but must not repeat.
+pub fn reduce(tensor: &Tensor, pattern: &str, sizes: &[(&str, usize)]) -> Result<Tensor> {
+    let pattern = Pattern::parse(pattern)?;
+    if pattern.repeats() {
+        candle_core::bail!("`reduce` patterns cannot repeat axes; use `repeat`")
+    }
+    pattern.apply(tensor, sizes)
+}
+
+/// Repeats `tensor` along new axes with a runtime pattern such as
+/// `"h w -> h {copies} w"`.
+///
+/// The pattern may also rearrange the input axes, but must not reduce.
+pub fn repeat(tensor: &Tensor, pattern: &str, sizes: &[(&str, usize)]) -> Result<Tensor> {
+    let pattern = Pattern::parse(pattern)?;
+    if pattern.reduces() {
+        candle_core::bail!("`repeat` patterns cannot reduce axes; use `reduce`")
+    }
+    pattern.apply(tensor, sizes)
+}
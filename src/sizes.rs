@@ -0,0 +1,79 @@
+use std::ops::Index;
+
+/// Named axis lengths resolved while applying an einops expression.
+///
+/// Returned by [`einops_with_sizes!`](crate::einops_with_sizes) and
+/// [`Pattern::apply_with_sizes`](crate::Pattern::apply_with_sizes). Every
+/// named input axis is listed in pattern order, including decomposed and
+/// reduced axes.
+///
+/// ```
+/// use candle_core::{DType, Device, Result, Tensor};
+/// use candle_einops::einops_with_sizes;
+///
+/// # fn main() -> Result<()> {
+/// let input = Tensor::zeros((2, 12), DType::F32, &Device::Cpu)?;
+/// let (output, sizes) = einops_with_sizes!("b (h:3 w) -> b h w", &input)?;
+/// assert_eq!(output.dims(), &[2, 3, 4]);
+/// assert_eq!(sizes["w"], 4);
+/// assert_eq!(sizes.get("c"), None);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sizes {
+    extents: Vec<(String, usize)>,
+}
+
+impl Sizes {
+    /// Returns the length of the axis called `name`.
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.extents
+            .iter()
+            .find(|(candidate, _)| candidate == name)
+            .map(|&(_, extent)| extent)
+    }
+
+    /// Iterates over the axis names and lengths in pattern order.
+    ///
+    /// The pairs can be collected and passed as the sizes of a
+    /// [`Pattern`](crate::Pattern).
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.extents
+            .iter()
+            .map(|(name, extent)| (name.as_str(), *extent))
+    }
+
+    /// Returns the number of named axes.
+    pub fn len(&self) -> usize {
+        self.extents.len()
+    }
+
+    /// Returns `true` when the pattern names no axes.
+    pub fn is_empty(&self) -> bool {
+        self.extents.is_empty()
+    }
+}
+
+impl Index<&str> for Sizes {
+    type Output = usize;
+
+    fn index(&self, name: &str) -> &usize {
+        self.extents
+            .iter()
+            .find(|(candidate, _)| candidate == name)
+            .map(|(_, extent)| extent)
+            .unwrap_or_else(|| panic!("no axis named `{name}`"))
+    }
+}
+
+impl<S: Into<String>> FromIterator<(S, usize)> for Sizes {
+    fn from_iter<I: IntoIterator<Item = (S, usize)>>(iter: I) -> Self {
+        Self {
+            extents: iter
+                .into_iter()
+                .map(|(name, extent)| (name.into(), extent))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use candle_core::{Result, Tensor};
+
+use crate::backend::{built_in_operations, built_in_steps};
+use crate::{Backend, IndexOperation, Operation, ReduceStep, Reducer};
+
+/// A list of equally shaped tensors viewed as one tensor with a leading list
+/// axis.
+///
+/// Transformations stack the elements as part of their first step, so
+/// transposes stack directly into the requested axis order and reductions
+/// over the list axis combine elements without materializing the stack.
+#[derive(Clone, Debug)]
+pub struct StackedTensors {
+    tensors: Vec<Tensor>,
+}
+
+impl StackedTensors {
+    /// Validates that every element shares the first element's shape, dtype,
+    /// and device.
+    pub(crate) fn new(tensors: Vec<Tensor>) -> Result<Self> {
+        let Some(first) = tensors.first() else {
+            candle_core::bail!("einops input list must contain at least one tensor")
+        };
+        for (index, tensor) in tensors.iter().enumerate().skip(1) {
+            if tensor.dims() != first.dims() {
+                candle_core::bail!(
+                    "einops input list element {index} has shape {:?}, expected {:?}",
+                    tensor.dims(),
+                    first.dims()
+                )
+            }
+            if tensor.dtype() != first.dtype() {
+                candle_core::bail!(
+                    "einops input list elements have different dtypes: element 0 {:?}, element {index} {:?}",
+                    first.dtype(),
+                    tensor.dtype()
+                )
+            }
+            if !tensor.device().same_device(first.device()) {
+                candle_core::bail!(
+                    "einops input list elements are on different devices: element 0 {:?}, element {index} {:?}",
+                    first.device(),
+                    tensor.device()
+                )
+            }
+        }
+        Ok(Self { tensors })
+    }
+
+    fn stack(&self, axis: usize) -> Result<Tensor> {
+        Tensor::stack(&self.tensors, axis).map_err(|error| error.context("einops list stacking"))
+    }
+
+    fn reduce_list_axes(
+        &self,
+        axes_steps: &mut [(usize, ReduceStep)],
+        reducer: Option<&dyn Reducer>,
+    ) -> Result<Tensor> {
+        let reduce = |tensor: &Tensor, axes_steps: &mut [(usize, ReduceStep)]| match reducer {
+            Some(reducer) => Backend::reduce_axes_with(tensor, axes_steps, reducer),
+            None => match built_in_operations(axes_steps) {
+                Some(mut axes_operations) => Backend::reduce_axes(tensor, &mut axes_operations),
+                None => unreachable!("`reduce_axes` passes only built-in operations"),
+            },
+        };
+
+        // Statistics and custom reducers over the list axis need every
+        // element at once
+        if axes_steps.iter().any(|&(axis, step)| {
+            axis == 0
+                && match step {
+                    ReduceStep::Operation(operation) => matches!(
+                        operation,
+                        Operation::LogSumExp | Operation::Var | Operation::Std | Operation::Norm
+                    ),
+                    ReduceStep::Custom => true,
+                }
+        }) {
+            return reduce(&self.stack(0)?, axes_steps);
+        }
+        let mut list_operation = None;
+        let mut element_steps = Vec::with_capacity(axes_steps.len());
+        for &(axis, step) in axes_steps.iter() {
+            if axis == 0 {
+                let ReduceStep::Operation(operation) = step else {
+                    unreachable!("custom reducers over the list axis are applied after stacking")
+                };
+                if list_operation.replace(operation).is_some() {
+                    candle_core::bail!("reduce_axes: duplicate axis 0")
+                }
+            } else {
+                element_steps.push((axis - 1, step));
+            }
+        }
+
+        // Backends reduce higher axes first, so the list axis is reduced last
+        let elements = if element_steps.is_empty() {
+            self.tensors.clone()
+        } else {
+            self.tensors
+                .iter()
+                .map(|tensor| reduce(tensor, &mut element_steps.clone()))
+                .collect::<Result<Vec<_>>>()?
+        };
+        match list_operation {
+            Some(operation) => combine_elements(elements, operation),
+            None => StackedTensors { tensors: elements }.stack(0),
+        }
+    }
+}
+
+/// Combines list elements pairwise, which keeps products and sums balanced.
+fn combine_elements(mut tensors: Vec<Tensor>, operation: Operation) -> Result<Tensor> {
+    let count = tensors.len();
+    while tensors.len() > 1 {
+        let mut pairs = tensors.into_iter();
+        let mut combined = Vec::with_capacity(pairs.len().div_ceil(2));
+        while let Some(left) = pairs.next() {
+            combined.push(match pairs.next() {
+                None => left,
+                Some(right) => match operation {
+                    Operation::Min => left.minimum(&right)?,
+                    Operation::Max => left.maximum(&right)?,
+                    Operation::Sum | Operation::Mean => left.add(&right)?,
+                    Operation::Prod => left.mul(&right)?,
+                    Operation::LogSumExp | Operation::Var | Operation::Std | Operation::Norm => {
+                        unreachable!("statistics over the list axis are reduced after stacking")
+                    }
+                },
+            });
+        }
+        tensors = combined;
+    }
+    let output = tensors.pop().expect("input lists are not empty");
+    match operation {
+        Operation::Mean => output.affine(1f64 / count as f64, 0f64),
+        _ => Ok(output),
+    }
+}
+
+impl Backend for &StackedTensors {
+    type Output = Tensor;
+
+    fn shape(self) -> Vec<usize> {
+        let mut shape = Vec::with_capacity(self.tensors[0].rank() + 1);
+        shape.push(self.tensors.len());
+        shape.extend_from_slice(self.tensors[0].dims());
+        shape
+    }
+
+    fn reshape(self, shape: &[usize]) -> Result<Self::Output> {
+        Backend::reshape(&self.stack(0)?, shape)
+    }
+
+    fn transpose(self, axes: &[usize]) -> Result<Self::Output> {
+        let rank = self.tensors[0].rank() + 1;
+        if axes.len() != rank {
+            candle_core::bail!("transpose: permutation {axes:?} does not match rank {rank}")
+        }
+        let Some(list_position) = axes.iter().position(|&axis| axis == 0) else {
+            candle_core::bail!("transpose: permutation {axes:?} does not contain axis 0")
+        };
+        let element_axes = axes
+            .iter()
+            .filter(|&&axis| axis != 0)
+            .map(|&axis| axis - 1)
+            .collect::<Vec<_>>();
+        let permuted = self
+            .tensors
+            .iter()
+            .map(|tensor| tensor.permute(element_axes.as_slice()))
+            .collect::<Result<Vec<_>>>()?;
+        Tensor::stack(&permuted, list_position)
+            .map_err(|error| error.context("einops list stacking"))
+    }
+
+    fn compose_axes(self, output_shape: &[usize], group_lengths: &[usize]) -> Result<Self::Output> {
+        Backend::compose_axes(&self.stack(0)?, output_shape, group_lengths)
+    }
+
+    fn permute_and_compose(
+        self,
+        permutation: &[usize],
+        output_shape: &[usize],
+        group_lengths: &[usize],
+    ) -> Result<<Self::Output as Backend>::Output> {
+        // Stacking into the permuted position leaves contiguous storage, so the
+        // composition that follows is a view.
+        let permuted = self.transpose(permutation)?;
+        Backend::compose_axes(&permuted, output_shape, group_lengths)
+    }
+
+    fn reduce_axes(self, axes_operations: &mut [(usize, Operation)]) -> Result<Self::Output> {
+        self.reduce_list_axes(&mut built_in_steps(axes_operations), None)
+    }
+
+    fn reduce_axes_with(
+        self,
+        axes_steps: &mut [(usize, ReduceStep)],
+        reducer: &dyn Reducer,
+    ) -> Result<Self::Output> {
+        self.reduce_list_axes(axes_steps, Some(reducer))
+    }
+
+    fn arg_reduce_axes(self, axes: &[usize], operation: IndexOperation) -> Result<Self::Output> {
+        Backend::arg_reduce_axes(&self.stack(0)?, axes, operation)
+    }
+
+    fn add_axes(self, naxes: usize, pos2len: &[(usize, usize)]) -> Result<Self::Output> {
+        Backend::add_axes(&self.stack(0)?, naxes, pos2len)
+    }
+
+    fn unbind(self) -> Result<Vec<Self::Output>> {
+        Ok(self.tensors.clone())
+    }
+}
+
+impl Backend for StackedTensors {
+    type Output = Tensor;
+
+    fn shape(self) -> Vec<usize> {
+        Backend::shape(&self)
+    }
+
+    fn reshape(self, shape: &[usize]) -> Result<Self::Output> {
+        Backend::reshape(&self, shape)
+    }
+
+    fn transpose(self, axes: &[usize]) -> Result<Self::Output> {
+        Backend::transpose(&self, axes)
+    }
+
+    fn compose_axes(self, output_shape: &[usize], group_lengths: &[usize]) -> Result<Self::Output> {
+        Backend::compose_axes(&self, output_shape, group_lengths)
+    }
+
+    fn permute_and_compose(
+        self,
+        permutation: &[usize],
+        output_shape: &[usize],
+        group_lengths: &[usize],
+    ) -> Result<<Self::Output as Backend>::Output> {
+        Backend::permute_and_compose(&self, permutation, output_shape, group_lengths)
+    }
+
+    fn reduce_axes(self, axes_operations: &mut [(usize, Operation)]) -> Result<Self::Output> {
+        Backend::reduce_axes(&self, axes_operations)
+    }
+
+    fn reduce_axes_with(
+        self,
+        axes_steps: &mut [(usize, ReduceStep)],
+        reducer: &dyn Reducer,
+    ) -> Result<Self::Output> {
+        Backend::reduce_axes_with(&self, axes_steps, reducer)
+    }
+
+    fn arg_reduce_axes(self, axes: &[usize], operation: IndexOperation) -> Result<Self::Output> {
+        Backend::arg_reduce_axes(&self, axes, operation)
+    }
+
+    fn add_axes(self, naxes: usize, pos2len: &[(usize, usize)]) -> Result<Self::Output> {
+        Backend::add_axes(&self, naxes, pos2len)
+    }
+
+    fn unbind(self) -> Result<Vec<Self::Output>> {
+        Ok(self.tensors)
+    }
+}
+
+/// Collections of tensors accepted as an `einops!` input list.
+pub trait TensorList {
+    fn tensors(&self) -> Vec<Tensor>;
+}
+
+impl<T: AsRef<Tensor>> TensorList for [T] {
+    fn tensors(&self) -> Vec<Tensor> {
+        self.iter().map(|tensor| tensor.as_ref().clone()).collect()
+    }
+}
+
+impl<T: AsRef<Tensor>, const N: usize> TensorList for [T; N] {
+    fn tensors(&self) -> Vec<Tensor> {
+        self.as_slice().tensors()
+    }
+}
+
+impl<T: AsRef<Tensor>> TensorList for Vec<T> {
+    fn tensors(&self) -> Vec<Tensor> {
+        self.as_slice().tensors()
+    }
+}
+
+impl<L: TensorList + ?Sized> TensorList for &L {
+    fn tensors(&self) -> Vec<Tensor> {
+        (**self).tensors()
+    }
+}
+
+// `einops!` wraps its input as `&&EinopsInput(&input)` and calls
+// `einops_input_kind`. Method resolution prefers the tensor impl, then the
+// list impl one autoref later, then the generic backend impl one deref later,
+// so the choice is made at compile time without overlapping impls.
+
+pub struct EinopsInput<T>(pub T);
+
+pub struct PassThroughInput;
+
+impl PassThroughInput {
+    pub fn prepare<T>(self, input: T) -> Result<T> {
+        Ok(input)
+    }
+}
+
+pub struct ListInput;
+
+impl ListInput {
+    pub fn prepare<T: TensorList>(self, input: T) -> Result<StackedTensors> {
+        StackedTensors::new(input.tensors())
+    }
+}
+
+pub trait TensorInputKind {
+    fn einops_input_kind(&self) -> PassThroughInput {
+        PassThroughInput
+    }
+}
+
+impl<T: AsRef<Tensor>> TensorInputKind for &EinopsInput<T> {}
+
+pub trait ListInputKind {
+    fn einops_input_kind(&self) -> ListInput {
+        ListInput
+    }
+}
+
+impl<T: TensorList> ListInputKind for &&EinopsInput<T> {}
+
+pub trait BackendInputKind {
+    fn einops_input_kind(&self) -> PassThroughInput {
+        PassThroughInput
+    }
+}
+
+impl<T> BackendInputKind for EinopsInput<T> {}
@@ -0,0 +1,95 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{Pattern, Sizes, einops, einops_with_sizes};
+
+fn pairs(sizes: &Sizes) -> Vec<(&str, usize)> {
+    sizes.iter().collect()
+}
+
+#[test]
+fn standalone_axis_sizes_are_asserted() -> Result<()> {
+    let images = Tensor::zeros((2, 3, 4, 5), DType::F32, &Device::Cpu)?;
+
+    let output = einops!("b c:3 h w -> b h w c", &images)?;
+    assert_eq!(output.dims(), &[2, 4, 5, 3]);
+    let output = einops!("b .. w:5 -> w b ..", &images)?;
+    assert_eq!(output.dims(), &[5, 2, 3, 4]);
+
+    let error = einops!("b c:4 h w -> b h w c", &images).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("axis `c` has length 3, expected 4"),
+        "{error}"
+    );
+    let error = einops!(".. h w:4 -> .. (h w)", &images).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("axis `w` has length 5, expected 4"),
+        "{error}"
+    );
+    Ok(())
+}
+
+#[test]
+fn assertions_alone_keep_the_input_layout() -> Result<()> {
+    let input = Tensor::arange(0f32, 6f32, &Device::Cpu)?.reshape((2, 3))?;
+
+    let output = einops!("a b:3 -> a b", &input)?;
+    assert_eq!(output.dims(), &[2, 3]);
+    assert_eq!(output.to_vec2::<f32>()?, input.to_vec2::<f32>()?);
+
+    let runtime = Pattern::parse("a b:2 -> a b")?.apply(&input, &[]);
+    assert!(
+        runtime
+            .unwrap_err()
+            .to_string()
+            .contains("axis `b` has length 3, expected 2")
+    );
+    Ok(())
+}
+
+#[test]
+fn sizes_cover_every_named_input_axis() -> Result<()> {
+    let input = Tensor::zeros((2, 12, 5), DType::F32, &Device::Cpu)?;
+
+    let (output, sizes) = einops_with_sizes!("b (h:3 w) c -> b h (w c)", &input)?;
+    assert_eq!(output.dims(), &[2, 3, 20]);
+    assert_eq!(pairs(&sizes), [("b", 2), ("h", 3), ("w", 4), ("c", 5)]);
+
+    let (output, sizes) = einops_with_sizes!("b h mean(c) -> b h", &input)?;
+    assert_eq!(output.dims(), &[2, 12]);
+    assert_eq!(pairs(&sizes), [("b", 2), ("h", 12), ("c", 5)]);
+
+    let (output, sizes) = einops_with_sizes!(".. c:5 -> c ..", &input)?;
+    assert_eq!(output.dims(), &[5, 2, 12]);
+    assert_eq!(pairs(&sizes), [("c", 5)]);
+
+    let (output, sizes) = einops_with_sizes!("b h c -> b h c", &input)?;
+    assert_eq!(output.dims(), input.dims());
+    assert_eq!(sizes.len(), 3);
+    Ok(())
+}
+
+#[test]
+fn sizes_are_returned_with_split_outputs() -> Result<()> {
+    let input = Tensor::zeros((3, 2, 4), DType::F32, &Device::Cpu)?;
+
+    let ([first, _, _], sizes) = einops_with_sizes!("(k:3) b d -> k* b d", &input)?;
+    assert_eq!(first.dims(), &[2, 4]);
+    assert_eq!(pairs(&sizes), [("k", 3), ("b", 2), ("d", 4)]);
+    Ok(())
+}
+
+#[test]
+fn runtime_patterns_report_sizes() -> Result<()> {
+    let input = Tensor::zeros((2, 12, 5), DType::F32, &Device::Cpu)?;
+    let pattern = Pattern::parse("b (h:3 w) c:5 -> b h (w c)")?;
+
+    let (output, sizes) = pattern.apply_with_sizes(&input, &[])?;
+    assert_eq!(output.dims(), &[2, 3, 20]);
+    let (_, expected) = einops_with_sizes!("b (h:3 w) c:5 -> b h (w c)", &input)?;
+    assert_eq!(sizes, expected);
+    assert_eq!(sizes["w"], 4);
+    Ok(())
+}
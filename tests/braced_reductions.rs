@@ -0,0 +1,91 @@
+use candle_core::{Device, Result, Tensor};
+use candle_einops::{Operation, Pattern, Reducer, einops};
+
+struct Pooling {
+    operation: Operation,
+}
+
+#[test]
+fn braced_operations_are_selected_at_runtime() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+
+    for (op, expected) in [
+        (Operation::Mean, input.mean((1, 2))?),
+        (Operation::Max, input.max(2)?.max(1)?),
+        (Operation::Sum, input.sum((1, 2))?),
+    ] {
+        let output = einops!("b {op}(h w) -> b", &input)?;
+        assert_eq!(
+            output.to_vec1::<f32>()?,
+            expected.to_vec1::<f32>()?,
+            "{op:?}"
+        );
+    }
+
+    let pooling = Pooling {
+        operation: Operation::Min,
+    };
+    let output = einops!("b h {pooling.operation}(w) -> h b", &input)?;
+    assert_eq!(
+        output.to_vec2::<f32>()?,
+        input.min(2)?.t()?.to_vec2::<f32>()?
+    );
+    Ok(())
+}
+
+#[test]
+fn braced_operations_mix_with_other_reductions() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+    let op = Operation::Max;
+
+    let output = einops!("{op}(b) sum(h) {op}(w) ->", &input)?;
+    assert_eq!(
+        output.to_scalar::<f32>()?,
+        input.max(2)?.sum(1)?.max(0)?.to_scalar::<f32>()?
+    );
+
+    let output = einops!("{op}(..) w -> w", &input)?;
+    assert_eq!(output.to_vec1::<f32>()?, [20., 21., 22., 23.]);
+
+    let list = [input.get(0)?, input.get(1)?];
+    let output = einops!("{op}(n) h w -> h w", &list)?;
+    assert_eq!(
+        output.flatten_all()?.to_vec1::<f32>()?,
+        input.get(1)?.flatten_all()?.to_vec1::<f32>()?
+    );
+    Ok(())
+}
+
+#[test]
+fn braced_operations_mix_with_custom_reducers() -> Result<()> {
+    struct Range;
+
+    impl Reducer for Range {
+        fn reduce(&self, tensor: &Tensor, axes: &[usize]) -> Result<Tensor> {
+            let &[axis] = axes else {
+                candle_core::bail!("Range reduces one axis at a time")
+            };
+            tensor.max(axis)? - tensor.min(axis)?
+        }
+    }
+
+    let input = Tensor::new(&[[1f32, 5., 2.], [4., 4., 9.]], &Device::Cpu)?;
+    let op = Operation::Sum;
+    let output = einops!("{op}(b) reduce(c) ->", &input, Range)?;
+    assert_eq!(output.to_scalar::<f32>()?, 9.);
+    Ok(())
+}
+
+#[test]
+fn runtime_patterns_report_braced_operations() {
+    let error = Pattern::parse("b {op}(h w) -> b").unwrap_err().to_string();
+    assert!(error.contains("require `einops!`"), "{error}");
+}
+
+#[test]
+fn runtime_patterns_report_braced_fields() {
+    let error = Pattern::parse("b (h {shape.w}) -> b h {shape.w}")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("`{shape.w}` requires `einops!`"), "{error}");
+}
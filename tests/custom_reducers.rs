@@ -0,0 +1,118 @@
+use std::cell::RefCell;
+
+use candle_core::{Device, Result, Tensor};
+use candle_einops::{Pattern, Reducer, einops};
+
+struct GeneralizedMean(f64);
+
+impl Reducer for GeneralizedMean {
+    fn reduce(&self, tensor: &Tensor, axes: &[usize]) -> Result<Tensor> {
+        tensor.powf(self.0)?.mean(axes)?.powf(1. / self.0)
+    }
+}
+
+/// Sums while recording the axes of every call.
+#[derive(Default)]
+struct RecordingSum(RefCell<Vec<Vec<usize>>>);
+
+impl Reducer for RecordingSum {
+    fn reduce(&self, tensor: &Tensor, axes: &[usize]) -> Result<Tensor> {
+        self.0.borrow_mut().push(axes.to_vec());
+        tensor.sum(axes)
+    }
+}
+
+fn assert_close(actual: &Tensor, expected: &Tensor) -> Result<()> {
+    let actual = actual.flatten_all()?.to_vec1::<f32>()?;
+    let expected = expected.flatten_all()?.to_vec1::<f32>()?;
+    assert_eq!(actual.len(), expected.len());
+    for (index, (actual, expected)) in actual.iter().zip(&expected).enumerate() {
+        assert!(
+            (actual - expected).abs() <= 1e-4 * expected.abs().max(1.),
+            "value {index} differs: {actual} vs {expected}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn reducer_applies_to_reduce_groups() -> Result<()> {
+    let input = Tensor::arange(1f32, 25f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+
+    let output = einops!("b reduce(h w) -> b", &input, GeneralizedMean(3.))?;
+    let expected = input.powf(3.)?.mean((1, 2))?.powf(1. / 3.)?;
+    assert_close(&output, &expected)?;
+
+    let reducer = GeneralizedMean(1.);
+    let output = einops!("b reduce(h) w -> w b", &input, &reducer)?;
+    assert_close(&output, &input.mean(1)?.t()?)?;
+    Ok(())
+}
+
+#[test]
+fn custom_and_built_in_reductions_mix() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+
+    let reducer = RecordingSum::default();
+    let output = einops!("reduce(b) max(h) reduce(w) ->", &input, &reducer)?;
+    assert_close(&output, &input.max(1)?.sum_all()?)?;
+    // Interleaved built-in runs keep the custom axes in separate calls
+    assert_eq!(*reducer.0.borrow(), [vec![2], vec![0]]);
+
+    let reducer = RecordingSum::default();
+    let output = einops!("reduce(b) h reduce(w) -> h", &input, &reducer)?;
+    assert_close(&output, &input.sum((0, 2))?)?;
+    // Non-adjacent custom axes are reduced jointly in ascending order
+    assert_eq!(*reducer.0.borrow(), [vec![0, 2]]);
+    Ok(())
+}
+
+#[test]
+fn reducer_combines_list_elements() -> Result<()> {
+    let input = Tensor::arange(1f32, 13f32, &Device::Cpu)?.reshape((3, 4))?;
+    let list = [input.get(0)?, input.get(1)?, input.get(2)?];
+
+    let output = einops!("reduce(n) c -> c", &list, GeneralizedMean(2.))?;
+    let expected = einops!("reduce(n) c -> c", &input, GeneralizedMean(2.))?;
+    assert_close(&output, &expected)?;
+    Ok(())
+}
+
+#[test]
+fn runtime_patterns_accept_a_reducer() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+    let pattern = Pattern::parse("b reduce(h) min(w) -> b")?;
+
+    let output = pattern.apply_with_reducer(&input, &[], &RecordingSum::default())?;
+    let expected = einops!("b reduce(h) min(w) -> b", &input, RecordingSum::default())?;
+    assert_close(&output, &expected)?;
+
+    let error = pattern.apply(&input, &[]).unwrap_err().to_string();
+    assert!(error.contains("Pattern::apply_with_reducer"), "{error}");
+
+    // Without a call, `reduce` stays an axis name
+    let output = Pattern::parse("reduce h w -> w h reduce")?.apply(&input, &[])?;
+    assert_eq!(output.dims(), &[4, 3, 2]);
+    Ok(())
+}
+
+#[test]
+fn reducer_output_shape_is_validated() -> Result<()> {
+    struct KeepDims;
+
+    impl Reducer for KeepDims {
+        fn reduce(&self, tensor: &Tensor, axes: &[usize]) -> Result<Tensor> {
+            tensor.sum_keepdim(axes)
+        }
+    }
+
+    let input = Tensor::arange(0f32, 6f32, &Device::Cpu)?.reshape((2, 3))?;
+    let error = einops!("b reduce(c) -> b", &input, KeepDims)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("custom reducer returned shape [2, 1]"),
+        "{error}"
+    );
+    Ok(())
+}
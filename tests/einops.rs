@@ -18,7 +18,7 @@ fn candle_layers() -> Result<()> {
 
 #[test]
 fn consistency_checks() -> Result<()> {
-    let input = Tensor::arange(0f32, (1 * 2 * 3 * 5 * 7 * 11) as f32, &Device::Cpu)?
+    let input = Tensor::arange(0f32, (2 * 3 * 5 * 7 * 11) as f32, &Device::Cpu)?
         .reshape(&[1, 2, 3, 5, 7, 11])?;
 
     let output = einops!("a b c d e f -> a (b) (c d e) f", &input)?;
@@ -150,8 +150,7 @@ macro_rules! seq_test {
 
 #[test]
 fn equivalent_repeat() -> Result<()> {
-    let input =
-        Tensor::arange(0f32, (1 * 2 * 4 * 6) as f32, &Device::Cpu)?.reshape(&[1, 2, 4, 6])?;
+    let input = Tensor::arange(0f32, (2 * 4 * 6) as f32, &Device::Cpu)?.reshape(&[1, 2, 4, 6])?;
     seq_test![
         (
             "a b c d -> (c 2 d a b)",
@@ -0,0 +1,61 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{einops, einops_shape};
+
+#[test]
+fn named_axes_are_extracted() -> Result<()> {
+    let images = Tensor::zeros((2, 3, 4, 5), DType::F32, &Device::Cpu)?;
+
+    let shape = einops_shape!("b _ h w", &images)?;
+    assert_eq!((shape.b, shape.h, shape.w), (2, 4, 5));
+
+    let shape = einops_shape!("b c h w", images)?;
+    assert_eq!((shape.b, shape.c, shape.h, shape.w), (2, 3, 4, 5));
+    Ok(())
+}
+
+#[test]
+fn ellipsis_skips_any_number_of_axes() -> Result<()> {
+    let input = Tensor::zeros((2, 3, 4, 5), DType::F32, &Device::Cpu)?;
+
+    let shape = einops_shape!("b .. w", &input)?;
+    assert_eq!((shape.b, shape.w), (2, 5));
+    let shape = einops_shape!(".. h _", &input)?;
+    assert_eq!(shape.h, 4);
+    let shape = einops_shape!("b c h w ..", &input)?;
+    assert_eq!(shape.w, 5);
+    let shape = einops_shape!("b h ..", &input)?;
+    assert_eq!((shape.b, shape.h), (2, 3));
+    Ok(())
+}
+
+#[test]
+fn sizes_feed_the_next_pattern() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+
+    let shape = einops_shape!("b h w", &input)?;
+    let flat = einops!("b h w -> b (h w)", &input)?;
+    let restored = einops!("b (h {shape.w}) -> b h {shape.w}", &flat)?;
+    assert_eq!(restored.dims(), &[shape.b, shape.h, shape.w]);
+    Ok(())
+}
+
+#[test]
+fn rank_mismatches_return_errors() -> Result<()> {
+    let input = Tensor::zeros((2, 3), DType::F32, &Device::Cpu)?;
+
+    let error = einops_shape!("b c h", &input).unwrap_err().to_string();
+    assert!(
+        error.contains(
+            "shape index 2 out of range for rank 2; einops expression requires at least 3 axes"
+        ),
+        "{error}"
+    );
+    let error = einops_shape!("b .. c h", &input).unwrap_err().to_string();
+    assert!(error.contains("requires at least 3 axes"), "{error}");
+    let error = einops_shape!("b", &input).unwrap_err().to_string();
+    assert!(
+        error.contains("einops expression `b` expects 1 axes, input rank is 2"),
+        "{error}"
+    );
+    Ok(())
+}
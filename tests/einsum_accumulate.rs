@@ -0,0 +1,132 @@
+use candle_core::{DType, Device, Result, Tensor, Var};
+use candle_einops::{EinsumOptions, einsum, einsum_with};
+
+fn accumulated(equation: &str, operands: &[&Tensor], dtype: DType) -> Result<Tensor> {
+    let options = EinsumOptions {
+        accumulate: Some(dtype),
+        ..EinsumOptions::default()
+    };
+    einsum_with(equation, operands, options)
+}
+
+fn ones(dims: &[usize], dtype: DType) -> Result<Tensor> {
+    Tensor::ones(dims, dtype, &Device::Cpu)
+}
+
+fn values(tensor: &Tensor) -> Result<Vec<f32>> {
+    tensor.to_dtype(DType::F32)?.flatten_all()?.to_vec1::<f32>()
+}
+
+#[test]
+fn macro_accumulates_narrow_floats_in_f32() -> Result<()> {
+    let long = ones(&[4096], DType::F16)?;
+    // An `f16` running sum stops growing at 2048
+    assert_eq!(values(&einsum!("i ->", &long)?)?, [2048.]);
+    let sum = einsum!("i ->", &long; accumulate = f32)?;
+    assert_eq!(sum.dtype(), DType::F16);
+    assert_eq!(values(&sum)?, [4096.]);
+
+    let dot = einsum!("i, i ->", &long, &long; accumulate = f32)?;
+    assert_eq!(values(&dot)?, [4096.]);
+    let batch = ones(&[2, 4096], DType::F16)?;
+    let traced = einsum!(".. i, i, i -> ..", &batch, &long, &long; accumulate = f32)?;
+    assert_eq!(
+        (traced.dtype(), values(&traced)?),
+        (DType::F16, vec![4096.; 2])
+    );
+
+    // Candle has no CPU `bf16` matmul, so the widened lowering also reaches it
+    let left = ones(&[2, 8], DType::BF16)?;
+    let right = ones(&[8, 3], DType::BF16)?;
+    assert!(einsum!("a i, i b -> a b", &left, &right).is_err());
+    let product = einsum!("a i, i b -> a b", &left, &right; accumulate = f32)?;
+    assert_eq!(product.dtype(), DType::BF16);
+    assert_eq!(values(&product)?, [8.; 6]);
+
+    let chain = einsum!(
+        "a i, i b, b c -> a c", &left, &right, &ones(&[3, 4], DType::BF16)?;
+        path = [(1, 2), (0, 1)], accumulate = f64
+    )?;
+    assert_eq!(
+        (chain.dtype(), values(&chain)?),
+        (DType::BF16, vec![24.; 8])
+    );
+    Ok(())
+}
+
+#[test]
+fn label_means_and_log_sum_exp_accumulate_in_the_wider_dtype() -> Result<()> {
+    let long = ones(&[4096], DType::F16)?;
+    assert_eq!(values(&einsum!("t -> mean(t)", &long)?)?, [0.5]);
+    let mean = einsum!("t -> mean(t)", &long; accumulate = f32)?;
+    assert_eq!((mean.dtype(), values(&mean)?), (DType::F16, vec![1.]));
+
+    // The log of 4096 `exp(0)` terms, which an `f16` sum caps at 2048
+    let zeros = Tensor::zeros(4096, DType::F16, &Device::Cpu)?;
+    let narrow = einsum!(semiring = log_sum_exp, "i ->", &zeros)?;
+    let wide = einsum!(semiring = log_sum_exp, "i ->", &zeros; accumulate = f32)?;
+    assert!((values(&narrow)?[0] - 2048f32.ln()).abs() < 1e-2);
+    assert!((values(&wide)?[0] - 4096f32.ln()).abs() < 1e-2);
+    Ok(())
+}
+
+#[test]
+fn runtime_accumulation_matches_the_macro() -> Result<()> {
+    let left = ones(&[3, 4096], DType::F16)?;
+    let right = ones(&[4096], DType::F16)?;
+    let actual = accumulated("a i, i -> a", &[&left, &right], DType::F32)?;
+    let expected = einsum!("a i, i -> a", &left, &right; accumulate = f32)?;
+    assert_eq!(actual.dtype(), DType::F16);
+    assert_eq!(values(&actual)?, values(&expected)?);
+
+    let numpy = einsum!(numpy "ai,i", &left, &right; accumulate = f32)?;
+    assert_eq!(values(&numpy)?, values(&expected)?);
+    Ok(())
+}
+
+#[test]
+fn accumulation_never_narrows_or_casts_integers() -> Result<()> {
+    // 1 + 2^-30 is not representable in `f32`
+    let precise = Tensor::new(&[1. + 2f64.powi(-30), 1.], &Device::Cpu)?;
+    let sum = accumulated("i ->", &[&precise], DType::F32)?;
+    assert_eq!(sum.dtype(), DType::F64);
+    assert_eq!(sum.to_scalar::<f64>()?, 2. + 2f64.powi(-30));
+
+    let counts = Tensor::new(&[[1u32, 2], [3, 4]], &Device::Cpu)?;
+    let rows = accumulated("a b -> a", &[&counts], DType::F32)?;
+    assert_eq!(rows.to_vec1::<u32>()?, [3, 7]);
+
+    let half = ones(&[2], DType::F16)?;
+    let error = accumulated("i, i ->", &[&half, &precise], DType::F32).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("einsum operands have different dtypes")
+    );
+    let error = accumulated("i ->", &[&half], DType::U32).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("einsum accumulation dtype must be floating point, received U32")
+    );
+    let error = accumulated("i, i ->", &[&half], DType::F32).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("einsum equation has 2 inputs but received 1 operands")
+    );
+    Ok(())
+}
+
+#[test]
+fn accumulation_casts_are_differentiable() -> Result<()> {
+    let weight = Var::from_tensor(&Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?)?;
+    let input = Tensor::new(&[1f32, -1.], &Device::Cpu)?;
+    let output = einsum!("o i, i -> o", weight.as_tensor(), &input; accumulate = f64)?;
+    assert_eq!(output.dtype(), DType::F32);
+    let gradients = output.sum_all()?.backward()?;
+    let gradient = gradients.get(weight.as_tensor()).unwrap();
+    assert_eq!(gradient.dtype(), DType::F32);
+    assert_eq!(gradient.to_vec2::<f32>()?, [[1., -1.], [1., -1.]]);
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{
+    EinsumOptions, EinsumPlanCache, EinsumPlanCacheConfig, EinsumPlanCacheStats, EinsumStrategy,
+    einsum, einsum_with,
+};
+
+fn chain(rows: usize) -> Result<Tensor> {
+    let a = Tensor::ones((rows, 3), DType::F32, &Device::Cpu)?;
+    let b = Tensor::ones((3, 4), DType::F32, &Device::Cpu)?;
+    let c = Tensor::ones((4, 2), DType::F32, &Device::Cpu)?;
+    einsum("a b, b c, c d -> a d", &[&a, &b, &c])
+}
+
+fn stats(hits: u64, misses: u64, evictions: u64) -> EinsumPlanCacheStats {
+    EinsumPlanCacheStats {
+        hits,
+        misses,
+        evictions,
+    }
+}
+
+// The cache configuration is process-wide, so every scope is exercised in one
+// test rather than in tests that could run concurrently.
+#[test]
+fn plan_cache_scopes_count_hits_misses_and_evictions() -> Result<()> {
+    assert_eq!(
+        EinsumPlanCache::config(),
+        EinsumPlanCacheConfig::ThreadLocal { capacity: 16 }
+    );
+
+    EinsumPlanCache::configure(EinsumPlanCacheConfig::ThreadLocal { capacity: 2 })?;
+    assert_eq!(EinsumPlanCache::stats(), stats(0, 0, 0));
+    chain(2)?;
+    chain(2)?;
+    assert_eq!(EinsumPlanCache::stats(), stats(1, 1, 0));
+    // Another thread plans the same shapes again
+    std::thread::spawn(|| chain(2)).join().unwrap()?;
+    assert_eq!(EinsumPlanCache::stats(), stats(1, 2, 0));
+    chain(3)?;
+    chain(4)?;
+    assert_eq!(EinsumPlanCache::stats(), stats(1, 4, 1));
+    chain(2)?;
+    assert_eq!(EinsumPlanCache::stats(), stats(1, 5, 2));
+
+    EinsumPlanCache::configure(EinsumPlanCacheConfig::Shared {
+        capacity: 8,
+        shards: 4,
+    })?;
+    chain(2)?;
+    let workers = (0..4)
+        .map(|_| std::thread::spawn(|| chain(2)))
+        .collect::<Vec<_>>();
+    for worker in workers {
+        assert_eq!(worker.join().unwrap()?.to_vec2::<f32>()?, [[12f32; 2]; 2]);
+    }
+    assert_eq!(EinsumPlanCache::stats(), stats(4, 1, 0));
+
+    // Eight shards for three orders keep at most three of ten equations
+    EinsumPlanCache::configure(EinsumPlanCacheConfig::Shared {
+        capacity: 3,
+        shards: 8,
+    })?;
+    for rows in 1..=10 {
+        chain(rows)?;
+    }
+    let filled = EinsumPlanCache::stats();
+    assert_eq!((filled.hits, filled.misses), (0, 10));
+    assert!(filled.evictions >= 7, "{filled:?}");
+    for rows in 1..=10 {
+        chain(rows)?;
+    }
+    assert!(EinsumPlanCache::stats().hits <= 3);
+
+    EinsumPlanCache::configure(EinsumPlanCacheConfig::Shared {
+        capacity: 8,
+        shards: 4,
+    })?;
+    chain(2)?;
+    chain(2)?;
+    assert_eq!(EinsumPlanCache::stats(), stats(1, 1, 0));
+
+    // Clearing drops every entry but keeps the counters
+    EinsumPlanCache::clear();
+    chain(2)?;
+    assert_eq!(EinsumPlanCache::stats(), stats(1, 2, 0));
+
+    // Explicit strategies never consult the cache
+    let a = Tensor::ones((2, 3), DType::F32, &Device::Cpu)?;
+    let b = Tensor::ones((3, 4), DType::F32, &Device::Cpu)?;
+    let c = Tensor::ones((4, 2), DType::F32, &Device::Cpu)?;
+    let options = EinsumOptions {
+        strategy: EinsumStrategy::Optimal,
+        ..EinsumOptions::default()
+    };
+    einsum_with("a b, b c, c d -> a d", &[&a, &b, &c], options)?;
+    assert_eq!(EinsumPlanCache::stats(), stats(1, 2, 0));
+
+    EinsumPlanCache::configure(EinsumPlanCacheConfig::Disabled)?;
+    chain(2)?;
+    chain(2)?;
+    assert_eq!(EinsumPlanCache::stats(), stats(0, 0, 0));
+
+    let error = EinsumPlanCache::configure(EinsumPlanCacheConfig::Shared {
+        capacity: 8,
+        shards: 0,
+    })
+    .unwrap_err();
+    assert!(error.to_string().contains("needs at least one shard"));
+    assert_eq!(EinsumPlanCache::config(), EinsumPlanCacheConfig::Disabled);
+
+    EinsumPlanCache::configure(EinsumPlanCacheConfig::default())?;
+    Ok(())
+}
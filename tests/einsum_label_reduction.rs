@@ -0,0 +1,147 @@
+use candle_core::{DType, Device, Result, Tensor, Var};
+use candle_einops::{EinsumOptions, EinsumSemiring, PreparedEinsumPlan, einsum, einsum_with};
+
+fn ramp(dims: &[usize]) -> Result<Tensor> {
+    let len = dims.iter().product::<usize>();
+    let values = (0..len)
+        .map(|index| ((index * 5) % 7) as f32 - 3.)
+        .collect::<Vec<_>>();
+    Tensor::from_vec(values, dims, &Device::Cpu)
+}
+
+#[test]
+fn annotated_labels_reduce_after_the_contractions_that_consume_them() -> Result<()> {
+    let x = ramp(&[2, 3, 4])?;
+    let w = ramp(&[4, 5])?;
+    let kept = einsum!("b t d, d e -> b t e", &x, &w)?;
+
+    let mean = einsum!("b t d, d e -> b mean(t) e", &x, &w)?;
+    assert_eq!(mean.to_vec2::<f32>()?, kept.mean(1)?.to_vec2::<f32>()?);
+    // A maximum taken before the sum over `d` would differ
+    let max = einsum!("b t d, d e -> b max(t) e", &x, &w)?;
+    let early = einsum!("b t d, d e -> b e", &x.max_keepdim(1)?, &w)?;
+    assert_eq!(max.to_vec2::<f32>()?, kept.max(1)?.to_vec2::<f32>()?);
+    assert_ne!(max.to_vec2::<f32>()?, early.to_vec2::<f32>()?);
+
+    let unary = einsum!("b t d -> d min(t) b", &x)?;
+    assert_eq!(unary.to_vec2::<f32>()?, x.min(1)?.t()?.to_vec2::<f32>()?);
+
+    let v = ramp(&[5, 2])?;
+    let chained = einsum!("b t d, d e, e f -> b max(t) f", &x, &w, &v)?;
+    let kept = einsum!("b t d, d e, e f -> b t f", &x, &w, &v)?;
+    assert_eq!(chained.to_vec2::<f32>()?, kept.max(1)?.to_vec2::<f32>()?);
+    let pathed = einsum!("b t d, d e, e f -> b max(t) f", &x, &w, &v; path = [(0, 1), (0, 1)])?;
+    assert_eq!(pathed.to_vec2::<f32>()?, chained.to_vec2::<f32>()?);
+
+    let batched = ramp(&[2, 3, 4, 5])?;
+    let ellipsis = einsum!(".. t d -> .. mean(t)", &batched)?;
+    let explicit = batched.sum(3)?.mean(2)?;
+    assert_eq!(ellipsis.to_vec2::<f32>()?, explicit.to_vec2::<f32>()?);
+    Ok(())
+}
+
+#[test]
+fn reductions_apply_in_written_order() -> Result<()> {
+    let x = ramp(&[2, 3, 4])?;
+    let mean_then_max = einsum!("b s t -> b mean(s) max(t)", &x)?;
+    assert_eq!(
+        mean_then_max.to_vec1::<f32>()?,
+        x.mean(1)?.max(1)?.to_vec1::<f32>()?
+    );
+    let max_then_mean = einsum!("b s t -> b max(t) mean(s)", &x)?;
+    assert_eq!(
+        max_then_mean.to_vec1::<f32>()?,
+        x.max(2)?.mean(1)?.to_vec1::<f32>()?
+    );
+    assert_ne!(
+        mean_then_max.to_vec1::<f32>()?,
+        max_then_mean.to_vec1::<f32>()?
+    );
+    let grouped = einsum!("b s t -> max(s t) b", &x)?;
+    assert_eq!(
+        grouped.to_vec1::<f32>()?,
+        x.max(1)?.max(1)?.to_vec1::<f32>()?
+    );
+    Ok(())
+}
+
+#[test]
+fn runtime_forms_match_the_macro() -> Result<()> {
+    let x = ramp(&[2, 3, 4])?;
+    let w = ramp(&[4, 5])?;
+    let expected = einsum!("b t d, d e -> b mean(t) e", &x, &w)?;
+    let runtime = einsum("b t d, d e -> b mean(t) e", &[&x, &w])?;
+    assert_eq!(runtime.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+
+    let plan = PreparedEinsumPlan::new(
+        "b t d, d e -> b mean(t) e",
+        &[&[2, 3, 4], &[4, 5]],
+        &Device::Cpu,
+    )?;
+    assert_eq!(plan.output_shape(), &[2, 5]);
+    assert_eq!(
+        plan.execute(&[&x, &w])?.to_vec2::<f32>()?,
+        expected.to_vec2::<f32>()?
+    );
+
+    let viterbi = einsum!(semiring = max_plus, "b t d, d e -> b min(t) e", &x, &w)?;
+    let options = EinsumOptions {
+        semiring: EinsumSemiring::MaxPlus,
+        ..EinsumOptions::default()
+    };
+    let kept = einsum_with("b t d, d e -> b t e", &[&x, &w], options)?;
+    assert_eq!(viterbi.to_vec2::<f32>()?, kept.min(1)?.to_vec2::<f32>()?);
+
+    // An `f16` sum of 4096 ones stops at 2048 without a wider accumulator
+    let long = Tensor::ones((2, 4096), DType::F16, &Device::Cpu)?;
+    let wide = einsum!("t i -> mean(t)", &long; accumulate = f32)?;
+    assert_eq!(wide.dtype(), DType::F16);
+    assert_eq!(wide.to_dtype(DType::F32)?.to_scalar::<f32>()?, 4096.);
+    Ok(())
+}
+
+#[test]
+fn label_reduction_errors_name_the_label() -> Result<()> {
+    let x = ramp(&[2, 3])?;
+    let error = |equation: &str| einsum(equation, &[&x]).unwrap_err().to_string();
+    assert!(error("a b -> a sum(b)").contains("must be `mean`, `max`, or `min`, found `sum`"));
+    assert!(error("a b -> a mean(b) b").contains("label `b` is both kept and reduced"));
+    assert!(error("a b -> mean(b) max(b)").contains("label `b` is reduced more than once"));
+    assert!(error("a b -> a mean(c)").contains("reduced label `c` does not occur in the input"));
+    assert!(error("a b -> a mean()").contains("`mean(...)` must name at least one label"));
+    assert!(error("a b -> a mean(b").contains("`mean(` must be closed by `)`"));
+    assert!(error("a b -> a mean(max(b))").contains("cannot be nested"));
+    assert!(error("a b -> a b)").contains("`)` without a matching `(`"));
+    assert!(error("a .. -> a mean(..)").contains("cannot contain `..`"));
+
+    let counts = Tensor::new(&[[1u32, 2], [3, 4]], &Device::Cpu)?;
+    let message = einsum!("a b -> a mean(b)", &counts)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        message.contains("einsum mean over label `b` requires floating-point values, received U32")
+    );
+    assert_eq!(
+        einsum!("a b -> a max(b)", &counts)?.to_vec1::<u32>()?,
+        [2, 4]
+    );
+
+    let empty = Tensor::zeros((2, 0), DType::F32, &Device::Cpu)?;
+    let message = einsum!("a b -> a max(b)", &empty).unwrap_err().to_string();
+    assert!(message.contains("einsum max over label `b` has no elements"));
+    Ok(())
+}
+
+#[test]
+fn label_reductions_are_differentiable() -> Result<()> {
+    let x = Var::from_tensor(&ramp(&[2, 4])?)?;
+    let w = Tensor::new(&[1f32, -1., 2., 0.5], &Device::Cpu)?;
+    let output = einsum!("t d, d -> mean(t)", x.as_tensor(), &w)?;
+    let gradients = output.backward()?;
+    let gradient = gradients.get(x.as_tensor()).unwrap();
+    assert_eq!(
+        gradient.to_vec2::<f32>()?,
+        [[0.5f32, -0.5, 1., 0.25], [0.5, -0.5, 1., 0.25]]
+    );
+    Ok(())
+}
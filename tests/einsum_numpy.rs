@@ -0,0 +1,106 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{EinsumOptions, einsum, einsum_with};
+
+fn numpy(equation: &str, operands: &[&Tensor]) -> Result<Tensor> {
+    let options = EinsumOptions {
+        numpy: true,
+        ..EinsumOptions::default()
+    };
+    einsum_with(equation, operands, options)
+}
+
+fn values(tensor: &Tensor) -> Result<Vec<f32>> {
+    tensor.flatten_all()?.to_vec1::<f32>()
+}
+
+fn assert_same(actual: &Tensor, expected: &Tensor) -> Result<()> {
+    assert_eq!(actual.dims(), expected.dims());
+    assert_eq!(values(actual)?, values(expected)?);
+    Ok(())
+}
+
+#[test]
+fn numpy_equations_match_native_equations() -> Result<()> {
+    let batch = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+    let right = Tensor::arange(0f32, 40f32, &Device::Cpu)?.reshape((2, 4, 5))?;
+    let matrix = Tensor::arange(0f32, 9f32, &Device::Cpu)?.reshape((3, 3))?;
+    let vector = Tensor::new(&[1f32, -1., 2.], &Device::Cpu)?;
+    let ones = Tensor::ones(4, DType::F32, &Device::Cpu)?;
+
+    assert_same(
+        &einsum!(numpy "bij,bjk->bik", &batch, &right)?,
+        &einsum!("b i j, b j k -> b i k", &batch, &right)?,
+    )?;
+    assert_same(
+        &einsum!(numpy "ii->i", &matrix)?,
+        &einsum!("i i -> i", &matrix)?,
+    )?;
+    assert_same(
+        &einsum!(numpy "...j,j->...", &batch, &ones)?,
+        &einsum!(".. j, j -> ..", &batch, &ones)?,
+    )?;
+    assert_same(
+        &einsum!(numpy "ij,j,k", &matrix, &vector, &vector)?,
+        &einsum!("i j, j, k -> i k", &matrix, &vector, &vector)?,
+    )?;
+    Ok(())
+}
+
+#[test]
+fn implicit_outputs_sort_labels_that_occur_once() -> Result<()> {
+    let matrix = Tensor::arange(0f32, 6f32, &Device::Cpu)?.reshape((2, 3))?;
+    let square = Tensor::arange(0f32, 9f32, &Device::Cpu)?.reshape((3, 3))?;
+    let batch = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+
+    // `ba` has the output `ab`, so the implicit form transposes
+    assert_same(&einsum!(numpy "ba", &matrix)?, &matrix.t()?)?;
+    assert_same(
+        &einsum!(numpy "ij,jk", &matrix, &square)?,
+        &matrix.matmul(&square)?,
+    )?;
+    // Repeated labels are summed, so `ii` is the trace
+    assert_eq!(einsum!(numpy "ii", &square)?.to_scalar::<f32>()?, 12.);
+    // Uppercase labels sort before lowercase labels
+    assert_eq!(einsum!(numpy "aB", &matrix)?.dims(), &[3, 2]);
+    // An input ellipsis leads the implicit output
+    assert_same(
+        &einsum!(numpy "...ij", &batch)?,
+        &einsum!(".. i j -> .. i j", &batch)?,
+    )?;
+    Ok(())
+}
+
+#[test]
+fn runtime_numpy_equations_match_macro_expansions() -> Result<()> {
+    let batch = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+    let right = Tensor::arange(0f32, 40f32, &Device::Cpu)?.reshape((2, 4, 5))?;
+    let square = Tensor::arange(0f32, 9f32, &Device::Cpu)?.reshape((3, 3))?;
+
+    assert_same(
+        &numpy("bij,bjk->bik", &[&batch, &right])?,
+        &einsum!(numpy "bij,bjk->bik", &batch, &right)?,
+    )?;
+    assert_same(
+        &numpy("bij,bjk", &[&batch, &right])?,
+        &einsum("b i j, b j k -> i k", &[&batch, &right])?,
+    )?;
+    assert_same(&numpy(" i i ", &[&square])?, &einsum!(numpy "ii", &square)?)?;
+    Ok(())
+}
+
+#[test]
+fn runtime_numpy_equations_report_errors() -> Result<()> {
+    let square = Tensor::arange(0f32, 9f32, &Device::Cpu)?.reshape((3, 3))?;
+    let error =
+        |equation: &str, operands: &[&Tensor]| numpy(equation, operands).unwrap_err().to_string();
+
+    assert!(error("i1->i", &[&square]).contains("invalid einsum subscript `1`"));
+    assert!(error("i..->i", &[&square]).contains("ellipsis must be written `...`"));
+    assert!(error("ij->i->j", &[&square]).contains("more than one `->`"));
+    assert!(error("ij->i,j", &[&square]).contains("einsum output must be one axis list"));
+    assert!(error("ij->k", &[&square]).contains("`k` does not occur in the input"));
+    assert!(
+        error("ij,jk", &[&square]).contains("einsum equation has 2 inputs but received 1 operands")
+    );
+    Ok(())
+}
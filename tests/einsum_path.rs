@@ -0,0 +1,258 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{
+    EinsumOptions, EinsumSemiring, EinsumStrategy, einsum, einsum_path, einsum_path_with_strategy,
+    einsum_with,
+};
+
+fn along(equation: &str, operands: &[&Tensor], path: &[(usize, usize)]) -> Result<Tensor> {
+    let options = EinsumOptions {
+        path: Some(path),
+        ..EinsumOptions::default()
+    };
+    einsum_with(equation, operands, options)
+}
+
+fn pairs(path: &candle_einops::EinsumPath) -> Vec<(usize, usize)> {
+    path.steps().iter().map(|step| step.operands()).collect()
+}
+
+#[test]
+fn paths_report_each_contraction_and_its_cost() -> Result<()> {
+    let path = einsum_path(
+        "z a b, b c, c d x, d e, z e f, f a -> z x",
+        &[
+            &[2, 3, 4],
+            &[4, 5],
+            &[5, 6, 3],
+            &[6, 2],
+            &[2, 2, 7],
+            &[7, 3],
+        ],
+    )?;
+    assert_eq!(pairs(&path), [(4, 5), (0, 4), (0, 3), (0, 1), (0, 1)]);
+    let first = &path.steps()[0];
+    assert_eq!(first.labels(), ["z", "e", "a"]);
+    assert_eq!((first.flops(), first.elements()), (84, 12));
+    assert_eq!(path.steps().last().unwrap().labels(), ["z", "x"]);
+
+    assert_eq!(
+        path.flops(),
+        path.steps().iter().map(|step| step.flops()).sum::<u128>()
+    );
+    assert_eq!(path.largest_intermediate(), 30);
+    // The inputs alone hold 117 elements
+    assert!(path.peak_elements() > 117);
+
+    let report = path.to_string();
+    assert!(
+        report.starts_with("  Complete contraction:  z a b, b c, c d x, d e, z e f, f a -> z x\n")
+    );
+    assert!(report.contains("  Optimized FLOP count:  4.520e2\n"));
+    assert!(report.contains("  Largest intermediate:  3.000e1 elements\n"));
+    assert!(report.contains(
+        "    (4, 5)     8.400e1     1.200e1  z e f, f a -> z e a             \
+         z a b, b c, c d x, d e, z e a -> z x"
+    ));
+    Ok(())
+}
+
+#[test]
+fn paths_describe_the_order_execution_uses() -> Result<()> {
+    let shapes: [&[usize]; 4] = [&[128, 8], &[8, 1], &[1, 8], &[8, 128]];
+    let labels = ["a b", "b c", "c d", "d e"];
+    let path = einsum_path("a b, b c, c d, d e -> a e", &shapes)?;
+    assert_eq!(pairs(&path), [(1, 2), (0, 2), (0, 1)]);
+
+    // Replaying the reported pairs one binary einsum at a time gives the same result
+    let mut live = shapes
+        .iter()
+        .enumerate()
+        .map(|(index, shape)| {
+            let len = shape.iter().product::<usize>();
+            let tensor = (Tensor::arange(0f32, len as f32, &Device::Cpu)? + index as f64)?;
+            Ok((tensor.reshape(*shape)?, labels[index].to_string()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let operands = live
+        .iter()
+        .map(|(tensor, _)| tensor.clone())
+        .collect::<Vec<_>>();
+    for step in path.steps() {
+        let (left, right) = step.operands();
+        let (right_tensor, right_labels) = live.remove(right);
+        let (left_tensor, left_labels) = live.remove(left);
+        let equation = format!(
+            "{left_labels}, {right_labels} -> {}",
+            step.labels().join(" ")
+        );
+        let tensor = einsum(&equation, &[&left_tensor, &right_tensor])?;
+        assert_eq!(tensor.elem_count() as u128, step.elements());
+        live.push((tensor, step.labels().join(" ")));
+    }
+    let expected = einsum(
+        "a b, b c, c d, d e -> a e",
+        &operands.iter().collect::<Vec<_>>(),
+    )?;
+    assert_eq!(live[0].0.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+    Ok(())
+}
+
+#[test]
+fn strategy_paths_share_the_cost_model() -> Result<()> {
+    let equation = "a b, b c, c d, d e, e f, f g -> a g";
+    let extents = [9, 9, 8, 20, 32, 12, 30];
+    let shapes = extents
+        .windows(2)
+        .map(|pair| pair.to_vec())
+        .collect::<Vec<_>>();
+    let shapes = shapes.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+    let greedy = einsum_path(equation, &shapes)?;
+    let optimal = einsum_path_with_strategy(equation, &shapes, EinsumStrategy::Optimal)?;
+    let dynamic = einsum_path_with_strategy(
+        equation,
+        &shapes,
+        EinsumStrategy::DynamicProgramming {
+            max_intermediate_elements: None,
+        },
+    )?;
+    assert!(optimal.score() < greedy.score());
+    assert_eq!(dynamic.score(), optimal.score());
+    assert_eq!(optimal.steps().len(), 5);
+
+    let tensors = shapes
+        .iter()
+        .map(|shape| Tensor::ones(*shape, DType::F32, &Device::Cpu))
+        .collect::<Result<Vec<_>>>()?;
+    let operands = tensors.iter().collect::<Vec<_>>();
+    let options = EinsumOptions {
+        strategy: EinsumStrategy::Optimal,
+        ..EinsumOptions::default()
+    };
+    assert_eq!(
+        einsum_with(equation, &operands, options)?.to_vec2::<f32>()?,
+        einsum(equation, &operands)?.to_vec2::<f32>()?
+    );
+    Ok(())
+}
+
+#[test]
+fn paths_cover_short_equations_and_report_shape_errors() -> Result<()> {
+    let unary = einsum_path("i j -> j", &[&[3, 4]])?;
+    assert!(unary.steps().is_empty());
+    assert_eq!(unary.largest_intermediate(), 0);
+    assert_eq!(unary.peak_elements(), 12);
+
+    let binary = einsum_path("i i j, j k -> i k", &[&[3, 3, 4], &[4, 5]])?;
+    assert_eq!(pairs(&binary), [(0, 1)]);
+    assert_eq!(binary.steps()[0].elements(), 15);
+
+    let ellipsis = einsum_path(".. i j, j k, k -> .. i", &[&[2, 3, 4], &[4, 5], &[5]])?;
+    assert_eq!(ellipsis.steps().last().unwrap().labels(), ["..[0]", "i"]);
+
+    let error = |equation: &str, shapes: &[&[usize]]| {
+        einsum_path(equation, shapes).unwrap_err().to_string()
+    };
+    assert!(
+        error("i j, j k -> i k", &[&[2, 3]])
+            .contains("einsum equation has 2 inputs but received 1 operands")
+    );
+    assert!(
+        error("i i, i j -> j", &[&[2, 3], &[2, 4]])
+            .contains("repeated label `i` has unequal extents 2 and 3")
+    );
+    assert!(
+        error("i j, j k, k -> i", &[&[2, 3], &[4, 5], &[5]])
+            .contains("einsum label `j` cannot broadcast extents 3 and 4")
+    );
+    assert!(error("i j, j -> i", &[&[2, 3, 4], &[3]]).contains("has rank 3, expected 2"));
+    Ok(())
+}
+
+#[test]
+fn caller_paths_match_the_planned_result() -> Result<()> {
+    let w = Tensor::arange(0f32, 6., &Device::Cpu)?.reshape((2, 3))?;
+    let x = Tensor::arange(0f32, 12., &Device::Cpu)?.reshape((3, 4))?;
+    let y = Tensor::arange(0f32, 8., &Device::Cpu)?.reshape((4, 2))?;
+    let z = Tensor::arange(0f32, 10., &Device::Cpu)?.reshape((2, 5))?;
+    let expected = einsum!("a b, b c, c d, d e -> a e", &w, &x, &y, &z)?.to_vec2::<f32>()?;
+
+    let actual = einsum!(
+        "a b, b c, c d, d e -> a e", &w, &x, &y, &z;
+        path = [(1, 2), (0, 1), (0, 1)]
+    )?;
+    assert_eq!(actual.to_vec2::<f32>()?, expected);
+    let actual = einsum!(
+        "a b, b c, c d, d e -> a e", &w, &x, &y, &z;
+        path = [(2, 3), (1, 2), (0, 1)]
+    )?;
+    assert_eq!(actual.to_vec2::<f32>()?, expected);
+    let binary = einsum!("a b, b c -> a c", &w, &x; path = [(0, 1)])?;
+    assert_eq!(binary.dims(), &[2, 4]);
+
+    let operands = [&w, &x, &y, &z];
+    let shapes = operands.map(Tensor::dims);
+    let planned = einsum_path("a b, b c, c d, d e -> a e", &shapes)?;
+    let path = pairs(&planned);
+    let actual = along("a b, b c, c d, d e -> a e", &operands, &path)?;
+    assert_eq!(actual.to_vec2::<f32>()?, expected);
+    Ok(())
+}
+
+#[test]
+fn caller_paths_must_fit_the_operands() -> Result<()> {
+    let a = Tensor::ones((2, 3), DType::F32, &Device::Cpu)?;
+    let b = Tensor::ones((3, 4), DType::F32, &Device::Cpu)?;
+    let c = Tensor::ones((4, 5), DType::F32, &Device::Cpu)?;
+    let error = |operands: &[&Tensor], path: &[(usize, usize)]| {
+        let equation = ["a b", "b c", "c d"][..operands.len()].join(", ") + " -> a";
+        along(&equation, operands, path).unwrap_err().to_string()
+    };
+    assert!(
+        error(&[&a, &b, &c], &[(0, 1)]).contains("einsum path has 1 steps, but 3 operands need 2")
+    );
+    assert!(
+        error(&[&a, &b, &c], &[(0, 1), (0, 2)])
+            .contains("einsum path step 1 (0, 2) is out of range for 2 operands")
+    );
+    assert!(
+        error(&[&a, &b, &c], &[(1, 1), (0, 1)])
+            .contains("einsum path step 0 contracts operand 1 with itself")
+    );
+    assert!(error(&[&a, &b], &[]).contains("einsum path has 0 steps, but 2 operands need 1"));
+    assert!(error(&[&a], &[(0, 1)]).contains("einsum path has 1 steps, but 1 operands need 0"));
+
+    let unary = along("a b -> a", &[&a], &[])?;
+    assert_eq!(unary.to_vec1::<f32>()?, [3., 3.]);
+    Ok(())
+}
+
+#[test]
+fn caller_paths_exclude_other_orderings() -> Result<()> {
+    let a = Tensor::ones((2, 3), DType::F32, &Device::Cpu)?;
+    let b = Tensor::ones((3, 4), DType::F32, &Device::Cpu)?;
+    let c = Tensor::ones((4, 5), DType::F32, &Device::Cpu)?;
+    let path = [(1, 2), (0, 1)];
+    let error = |options: EinsumOptions<'_>| {
+        einsum_with("a b, b c, c d -> a d", &[&a, &b, &c], options)
+            .unwrap_err()
+            .to_string()
+    };
+    assert!(
+        error(EinsumOptions {
+            path: Some(&path),
+            strategy: EinsumStrategy::Optimal,
+            ..EinsumOptions::default()
+        })
+        .contains("einsum options `path` and `strategy` cannot be combined")
+    );
+    assert!(
+        error(EinsumOptions {
+            path: Some(&path),
+            semiring: EinsumSemiring::MaxPlus,
+            ..EinsumOptions::default()
+        })
+        .contains("only apply to the sum-product semiring")
+    );
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{
+    EinsumDevicePolicy, EinsumOperandPolicy, EinsumOptions, EinsumPromotion, einsum, einsum_with,
+};
+
+fn converted(equation: &str, operands: &[&Tensor], policy: EinsumOperandPolicy) -> Result<Tensor> {
+    let options = EinsumOptions {
+        policy,
+        ..EinsumOptions::default()
+    };
+    einsum_with(equation, operands, options)
+}
+
+fn ramp(dims: &[usize], dtype: DType) -> Result<Tensor> {
+    let len = dims.iter().product::<usize>();
+    let values = (0..len).map(|index| (index % 5) as f32 - 2.).collect();
+    Tensor::from_vec(values, dims, &Device::Cpu)?.to_dtype(dtype)
+}
+
+fn values(tensor: &Tensor) -> Result<Vec<f32>> {
+    tensor.to_dtype(DType::F32)?.flatten_all()?.to_vec1::<f32>()
+}
+
+const WIDEST: EinsumOperandPolicy = EinsumOperandPolicy {
+    promote: EinsumPromotion::Widest,
+    device: EinsumDevicePolicy::Strict,
+};
+
+#[test]
+fn widest_promotion_converts_mixed_operands() -> Result<()> {
+    let activations = ramp(&[2, 3], DType::F32)?;
+    let weights = ramp(&[3, 4], DType::BF16)?;
+    let error = einsum!("b i, i o -> b o", &activations, &weights).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("einsum operands have different dtypes")
+    );
+
+    let expected = einsum!(
+        "b i, i o -> b o",
+        &activations,
+        &weights.to_dtype(DType::F32)?
+    )?;
+    let output = einsum!("b i, i o -> b o", &activations, &weights; promote = widest)?;
+    assert_eq!(output.dtype(), DType::F32);
+    assert_eq!(values(&output)?, values(&expected)?);
+    let runtime = converted("b i, i o -> b o", &[&activations, &weights], WIDEST)?;
+    assert_eq!(values(&runtime)?, values(&expected)?);
+
+    // Half types of the same width meet in `f32`, and integer masks take the float type
+    let half = ramp(&[4, 5], DType::F16)?;
+    let mask = Tensor::new(&[[1u8, 0], [0, 1], [1, 1]], &Device::Cpu)?;
+    let chain = einsum!(
+        "b i, i o, o p, i b -> b p", &activations, &weights, &half, &mask;
+        promote = widest, path = [(0, 3), (0, 1), (0, 1)]
+    )?;
+    assert_eq!(chain.dtype(), DType::F32);
+    let masked = (activations.clone() * mask.t()?.to_dtype(DType::F32)?)?;
+    let expected = einsum!(
+        "b i, i o, o p -> b p",
+        &masked,
+        &weights.to_dtype(DType::F32)?,
+        &half.to_dtype(DType::F32)?
+    )?;
+    assert_eq!(values(&chain)?, values(&expected)?);
+
+    let counts = Tensor::new(&[1u32, 2, 3], &Device::Cpu)?;
+    let offsets = Tensor::new(&[-1i64, 1, 2], &Device::Cpu)?;
+    let product = converted("i, i -> i", &[&counts, &offsets], WIDEST)?;
+    assert_eq!(product.to_vec1::<i64>()?, [-1, 2, 6]);
+    Ok(())
+}
+
+#[test]
+fn integers_promote_to_a_float_that_holds_them() -> Result<()> {
+    let promoted = |int: &Tensor, float: DType| -> Result<Tensor> {
+        let scale = Tensor::ones(int.dims(), float, &Device::Cpu)?;
+        converted("i, i -> i", &[int, &scale], WIDEST)
+    };
+    let mask = Tensor::new(&[0u8, 255], &Device::Cpu)?;
+    assert_eq!(promoted(&mask, DType::F16)?.dtype(), DType::F16);
+
+    // `f16` has neither the range nor the precision for these indices
+    let indices = Tensor::new(&[70_001i64, 16_777_217], &Device::Cpu)?;
+    let product = promoted(&indices, DType::F16)?;
+    assert_eq!(product.dtype(), DType::F64);
+    assert_eq!(product.to_vec1::<f64>()?, [70_001., 16_777_217.]);
+    let counts = Tensor::new(&[16_777_217u32], &Device::Cpu)?;
+    assert_eq!(
+        promoted(&counts, DType::F32)?.to_vec1::<f64>()?,
+        [16_777_217.]
+    );
+    Ok(())
+}
+
+#[test]
+fn policies_combine_with_accumulation_and_keep_strict_parts() -> Result<()> {
+    let long = Tensor::ones(4096, DType::F16, &Device::Cpu)?;
+    let mask = Tensor::ones(4096, DType::U8, &Device::Cpu)?;
+    let sum = einsum!("i, i ->", &long, &mask; promote = widest, accumulate = f32)?;
+    assert_eq!((sum.dtype(), values(&sum)?), (DType::F16, vec![4096.]));
+
+    // Operands already on the chosen device are used as they are
+    let a = ramp(&[2, 3], DType::F32)?;
+    let b = ramp(&[3], DType::F32)?;
+    let moved = einsum!("a i, i -> a", &a, &b; device = operand(1))?;
+    assert_eq!(values(&moved)?, values(&einsum!("a i, i -> a", &a, &b)?)?);
+
+    let device_only = EinsumOperandPolicy {
+        device: EinsumDevicePolicy::Operand(0),
+        ..Default::default()
+    };
+    let b64 = b.to_dtype(DType::F64)?;
+    let error = converted("a i, i -> a", &[&a, &b64], device_only).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("einsum operands have different dtypes")
+    );
+    let out_of_range = EinsumOperandPolicy {
+        device: EinsumDevicePolicy::Operand(2),
+        ..WIDEST
+    };
+    let error = converted("a i, i -> a", &[&a, &b], out_of_range).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("einsum device operand 2 is out of range for 2 operands")
+    );
+    assert_eq!(
+        converted("a i, i -> a", &[&a, &b], EinsumOperandPolicy::default())?.dims(),
+        &[2]
+    );
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{PreparedEinsumPlan, einsum};
+
+fn ramp(dims: &[usize], offset: usize, dtype: DType) -> Result<Tensor> {
+    let len = dims.iter().product::<usize>();
+    let values = (0..len)
+        .map(|index| ((index + offset) % 7) as f64 - 3.)
+        .collect::<Vec<_>>();
+    Tensor::from_vec(values, dims, &Device::Cpu)?.to_dtype(dtype)
+}
+
+fn assert_same(actual: &Tensor, expected: &Tensor, context: &str) -> Result<()> {
+    assert_eq!(actual.dims(), expected.dims(), "{context}");
+    assert_eq!(
+        actual
+            .to_dtype(DType::F64)?
+            .flatten_all()?
+            .to_vec1::<f64>()?,
+        expected
+            .to_dtype(DType::F64)?
+            .flatten_all()?
+            .to_vec1::<f64>()?,
+        "{context}"
+    );
+    Ok(())
+}
+
+#[test]
+fn prepared_plans_match_einsum() -> Result<()> {
+    let cases: [(&str, &[&[usize]]); 15] = [
+        ("i j -> j", &[&[3, 4]]),
+        ("i i j -> j i", &[&[3, 3, 2]]),
+        (".. i i -> .. i", &[&[2, 4, 4]]),
+        ("i j, j k -> i k", &[&[3, 4], &[4, 5]]),
+        ("b i j, b j k -> b i k", &[&[2, 3, 4], &[2, 4, 5]]),
+        ("a b c, c d -> d a", &[&[2, 3, 4], &[4, 5]]),
+        ("i, j -> j i", &[&[3], &[4]]),
+        (".. i j, j k -> .. i k", &[&[2, 3, 4], &[4, 5]]),
+        ("i i j, j k -> i k", &[&[3, 3, 4], &[4, 5]]),
+        ("b i, b i, i -> b", &[&[1, 3], &[4, 3], &[3]]),
+        (
+            "b i i, b i j, j k -> b k",
+            &[&[2, 3, 3], &[2, 3, 4], &[4, 5]],
+        ),
+        (".. i j, j k, k -> .. i", &[&[2, 3, 4], &[4, 5], &[5]]),
+        (
+            "z a b, b c, c d x, d e, z e f, f a -> z x",
+            &[
+                &[2, 3, 4],
+                &[4, 5],
+                &[5, 6, 3],
+                &[6, 2],
+                &[2, 2, 7],
+                &[7, 3],
+            ],
+        ),
+        ("a b, b c, c d -> a d", &[&[2, 0], &[0, 3], &[3, 4]]),
+        ("i j, j k, k l -> l i", &[&[1, 3], &[3, 1], &[1, 5]]),
+    ];
+    for (equation, shapes) in cases {
+        let plan = PreparedEinsumPlan::new(equation, shapes, &Device::Cpu)?;
+        assert_eq!(plan.input_shapes().len(), shapes.len());
+        for dtype in [DType::F32, DType::F64] {
+            let tensors = shapes
+                .iter()
+                .enumerate()
+                .map(|(index, shape)| ramp(shape, index, dtype))
+                .collect::<Result<Vec<_>>>()?;
+            let operands = tensors.iter().collect::<Vec<_>>();
+            let actual = plan.execute(&operands)?;
+            assert_eq!(actual.dims(), plan.output_shape(), "{equation}");
+            assert_eq!(actual.dtype(), dtype, "{equation}");
+            assert_same(&actual, &einsum(equation, &operands)?, equation)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn prepared_plans_are_reused_across_values_and_layouts() -> Result<()> {
+    let equation = "b i i, b j i, j k -> b k";
+    let plan = PreparedEinsumPlan::new(equation, &[&[2, 3, 3], &[2, 4, 3], &[4, 5]], &Device::Cpu)?;
+    for offset in 0..3 {
+        let x = ramp(&[2, 3, 3], offset, DType::F32)?;
+        let y = ramp(&[2, 4, 3], offset + 1, DType::F32)?;
+        let w = ramp(&[4, 5], offset + 2, DType::F32)?;
+        assert_same(
+            &plan.execute(&[&x, &y, &w])?,
+            &einsum(equation, &[&x, &y, &w])?,
+            "contiguous",
+        )?;
+
+        // Strided views of the same shapes run through the same plan
+        let x = x.transpose(1, 2)?;
+        let y = ramp(&[2, 3, 4], offset + 1, DType::F32)?.transpose(1, 2)?;
+        let w = ramp(&[5, 4], offset + 2, DType::F32)?.t()?;
+        assert!(!x.is_contiguous() && !y.is_contiguous() && !w.is_contiguous());
+        assert_same(
+            &plan.execute(&[&x, &y, &w])?,
+            &einsum(equation, &[&x, &y, &w])?,
+            "strided",
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn prepared_plans_check_operands_before_running() -> Result<()> {
+    let plan =
+        PreparedEinsumPlan::new("a b, b c, c -> a", &[&[2, 3], &[3, 4], &[4]], &Device::Cpu)?;
+    assert_eq!(plan.output_shape(), &[2]);
+    assert!(plan.device().is_cpu());
+
+    let a = Tensor::ones((2, 3), DType::F32, &Device::Cpu)?;
+    let b = Tensor::ones((3, 4), DType::F32, &Device::Cpu)?;
+    let c = Tensor::ones(4, DType::F32, &Device::Cpu)?;
+    assert_eq!(plan.execute(&[&a, &b, &c])?.to_vec1::<f32>()?, [12., 12.]);
+
+    let error = |operands: &[&Tensor]| plan.execute(operands).unwrap_err().to_string();
+    assert!(error(&[&a, &b]).contains("prepared einsum plan expects 3 operands, received 2"));
+    let broadcast = Tensor::ones((1, 3), DType::F32, &Device::Cpu)?;
+    assert!(
+        error(&[&broadcast, &b, &c])
+            .contains("prepared einsum plan expects operand 0 with shape [2, 3], received [1, 3]")
+    );
+    let c64 = c.to_dtype(DType::F64)?;
+    assert!(error(&[&a, &b, &c64]).contains("einsum operands have different dtypes"));
+
+    let error = |equation: &str, shapes: &[&[usize]]| {
+        PreparedEinsumPlan::new(equation, shapes, &Device::Cpu)
+            .unwrap_err()
+            .to_string()
+    };
+    assert!(
+        error("a b, b c -> a c", &[&[2, 3]])
+            .contains("einsum equation has 2 inputs but received 1 operands")
+    );
+    assert!(
+        error("a b, b c, c -> a", &[&[2, 3], &[4, 5], &[5]])
+            .contains("einsum label `b` cannot broadcast extents 3 and 4")
+    );
+    assert!(
+        error("i i -> i", &[&[2, 3]]).contains("repeated label `i` has unequal extents 2 and 3")
+    );
+    assert!(error("a b -> a", &[&[2, 3, 4]]).contains("has rank 3, expected 2"));
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use candle_core::{Device, Result, Tensor};
+use candle_einops::einsum;
+
+fn values(tensor: &Tensor) -> Result<Vec<f32>> {
+    tensor.flatten_all()?.to_vec1::<f32>()
+}
+
+fn runtime_error(equation: &str, operands: &[&Tensor]) -> String {
+    einsum(equation, operands).unwrap_err().to_string()
+}
+
+#[test]
+fn runtime_equations_match_macro_expansions() -> Result<()> {
+    let matrix = Tensor::arange(0f32, 9f32, &Device::Cpu)?.reshape((3, 3))?;
+    let batch = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+    let right = Tensor::arange(0f32, 12f32, &Device::Cpu)?.reshape((4, 3))?;
+    let vector = Tensor::new(&[1f32, -1., 2.], &Device::Cpu)?;
+
+    let cases = [
+        (
+            einsum("rows columns -> columns rows", &[&matrix])?,
+            einsum!("rows columns -> columns rows", &matrix)?,
+        ),
+        (
+            einsum("index index -> index", &[&matrix])?,
+            einsum!("index index -> index", &matrix)?,
+        ),
+        (
+            einsum("index index ->", &[&matrix])?,
+            einsum!("index index ->", &matrix)?,
+        ),
+        (
+            einsum(".. feature -> feature", &[&batch])?,
+            einsum!(".. feature -> feature", &batch)?,
+        ),
+        (
+            einsum("b i k, k j -> b i j", &[&batch, &right])?,
+            einsum!("b i k, k j -> b i j", &batch, &right)?,
+        ),
+        (
+            einsum("row, column -> row column", &[&vector, &vector])?,
+            einsum!("row, column -> row column", &vector, &vector)?,
+        ),
+        (
+            einsum(".. k, k j -> .. j", &[&batch, &right])?,
+            einsum!(".. k, k j -> .. j", &batch, &right)?,
+        ),
+        (
+            einsum("b i k, k j, j -> b i", &[&batch, &right, &vector])?,
+            einsum!("b i k, k j, j -> b i", &batch, &right, &vector)?,
+        ),
+        (
+            einsum(
+                "i j, j k, k l, l -> i",
+                &[&matrix, &matrix, &matrix, &vector],
+            )?,
+            einsum!("i j, j k, k l, l -> i", &matrix, &matrix, &matrix, &vector)?,
+        ),
+    ];
+    for (actual, expected) in cases {
+        assert_eq!(actual.dims(), expected.dims());
+        assert_eq!(values(&actual)?, values(&expected)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn equations_may_be_built_at_runtime() -> Result<()> {
+    let operands = (0..4)
+        .map(|_| Tensor::ones((2, 2), candle_core::DType::F32, &Device::Cpu))
+        .collect::<Result<Vec<_>>>()?;
+    let operand_refs = operands.iter().collect::<Vec<_>>();
+    let inputs = (0..operands.len())
+        .map(|index| format!("a{index} a{}", index + 1))
+        .collect::<Vec<_>>();
+    let equation = format!("{} -> a0 a4", inputs.join(", "));
+
+    let output = einsum(&equation, &operand_refs)?;
+
+    assert_eq!(output.to_vec2::<f32>()?, [[8., 8.], [8., 8.]]);
+    Ok(())
+}
+
+#[test]
+fn malformed_equations_report_macro_diagnostics() -> Result<()> {
+    let vector = Tensor::new(&[1f32, 2.], &Device::Cpu)?;
+    let cases = [
+        ("a", "einsum equation requires exactly one explicit `->`"),
+        (
+            "a -> a -> a",
+            "einsum equation requires exactly one explicit `->`",
+        ),
+        (
+            "a -> a, a",
+            "einsum output must be one whitespace-delimited axis list",
+        ),
+        (
+            ".. a .. -> a",
+            "einsum operand axis list contains more than one `..`",
+        ),
+        (
+            "a -> .. a ..",
+            "einsum output axis list contains more than one `..`",
+        ),
+        ("a-b -> a", "invalid einsum axis label `a-b`"),
+        ("a -> .. a", "einsum output `..` requires an input `..`"),
+        ("a -> a a", "duplicate einsum output label `a`"),
+        (
+            "a -> b",
+            "einsum output label `b` does not occur in the input",
+        ),
+        (
+            "a, a -> a",
+            "einsum equation has 2 inputs but received 1 operands",
+        ),
+    ];
+    for (equation, message) in cases {
+        let error = runtime_error(equation, &[&vector]);
+        assert!(error.contains(message), "{equation}: {error}");
+    }
+    assert!(runtime_error("a -> a", &[]).contains("has 1 inputs but received 0 operands"));
+    Ok(())
+}
+
+#[test]
+fn tensor_dependent_failures_are_candle_errors() -> Result<()> {
+    let vector = Tensor::new(&[1f32, 2.], &Device::Cpu)?;
+    let matrix = Tensor::ones((2, 3), candle_core::DType::F32, &Device::Cpu)?;
+
+    assert!(runtime_error("a b -> a", &[&vector]).contains("rank"));
+    assert!(einsum("a b, b c -> a c", &[&matrix, &matrix]).is_err());
+    Ok(())
+}
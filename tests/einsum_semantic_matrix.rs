@@ -63,7 +63,7 @@ fn floating_forward_matrix_follows_candle_matmul_support() -> Result<()> {
 }
 
 #[test]
-fn integer_permutations_are_supported_and_contractions_are_rejected() -> Result<()> {
+fn integer_permutations_and_contractions_are_supported_without_matmul() -> Result<()> {
     for dtype in [DType::U8, DType::U32, DType::I64] {
         let matrix = Tensor::new(&[[1_i64, 2], [3, 4]], &Device::Cpu)?.to_dtype(dtype)?;
         let transposed = einsum!("row column -> column row", &matrix)?;
@@ -76,17 +76,52 @@ fn integer_permutations_are_supported_and_contractions_are_rejected() -> Result<
             direct.is_err(),
             "Candle unexpectedly added {dtype:?} matmul support"
         );
-        let error = einsum!("feature, feature ->", &vector, &vector)
-            .expect_err("integer contraction must preserve Candle's rejection");
-        let message = error.to_string();
-        assert!(
-            message.contains("einsum binary B/M/K/N matmul"),
-            "{message}"
-        );
+
+        // Contractions multiply and sum in the operand dtype instead
+        let dot = einsum!("feature, feature ->", &vector, &vector)?;
+        assert_eq!(dot.dtype(), dtype);
+        assert_eq!(flat_f64(&dot)?, [5.]);
+        let product = einsum!("row inner, inner column -> row column", &matrix, &matrix)?;
+        assert_eq!(product.dtype(), dtype);
+        assert_eq!(flat_f64(&product)?, [7., 10., 15., 22.]);
+        let batched = einsum!(
+            "batch row inner, inner column -> column batch row",
+            &Tensor::stack(&[&matrix, &transposed], 0)?,
+            &matrix
+        )?;
+        assert_eq!(flat_f64(&batched)?, [7., 15., 10., 14., 10., 22., 14., 20.]);
+        let chain = einsum!(
+            "row inner, inner column, column -> row",
+            &matrix,
+            &matrix,
+            &vector
+        )?;
+        assert_eq!((chain.dtype(), flat_f64(&chain)?), (dtype, vec![27., 59.]));
+        let runtime = candle_einops::einsum("i j, j k, k l -> i l", &[&matrix; 3])?;
+        assert_eq!(flat_f64(&runtime)?, [37., 54., 81., 118.]);
     }
     Ok(())
 }
 
+#[test]
+fn float_dtypes_without_matmul_contract_by_multiplication() -> Result<()> {
+    let dtype = DType::F8E4M3;
+    let matrix = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?.to_dtype(dtype)?;
+    assert!(
+        matrix.matmul(&matrix).is_err(),
+        "Candle unexpectedly added {dtype:?} matmul support"
+    );
+
+    // Candle converts F8E4M3 to F64 only through F32
+    let flat = |tensor: &Tensor| flat_f64(&tensor.to_dtype(DType::F32)?);
+    let product = einsum!("row inner, inner column -> row column", &matrix, &matrix)?;
+    assert_eq!(product.dtype(), dtype);
+    assert_eq!(flat(&product)?, [7., 10., 15., 22.]);
+    let runtime = candle_einops::einsum("i j, j k -> k i", &[&matrix, &matrix])?;
+    assert_eq!(flat(&runtime)?, [7., 15., 10., 22.]);
+    Ok(())
+}
+
 #[test]
 fn non_contraction_multiplication_follows_candle_dtype_support() -> Result<()> {
     for dtype in [DType::U8, DType::U32, DType::I64, DType::BF16] {
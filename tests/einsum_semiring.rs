@@ -0,0 +1,333 @@
+use candle_core::{DType, Device, Result, Tensor, Var};
+use candle_einops::{EinsumOptions, EinsumSemiring, einsum, einsum_with};
+
+fn in_semiring(equation: &str, operands: &[&Tensor], semiring: EinsumSemiring) -> Result<Tensor> {
+    let options = EinsumOptions {
+        semiring,
+        ..EinsumOptions::default()
+    };
+    einsum_with(equation, operands, options)
+}
+
+const SEMIRINGS: [EinsumSemiring; 3] = [
+    EinsumSemiring::MaxPlus,
+    EinsumSemiring::MinPlus,
+    EinsumSemiring::LogSumExp,
+];
+
+fn ramp(dims: &[usize], offset: usize) -> Result<Tensor> {
+    let len = dims.iter().product::<usize>();
+    let values = (0..len)
+        .map(|index| ((index * 7 + offset * 3) % 11) as f64 * 0.5 - 2.)
+        .collect::<Vec<_>>();
+    Tensor::from_vec(values, dims, &Device::Cpu)
+}
+
+fn add(semiring: EinsumSemiring, accumulated: f64, value: f64) -> f64 {
+    match semiring {
+        EinsumSemiring::SumProduct => accumulated + value,
+        EinsumSemiring::MaxPlus => accumulated.max(value),
+        EinsumSemiring::MinPlus => accumulated.min(value),
+        EinsumSemiring::LogSumExp => {
+            let shift = accumulated.max(value);
+            if shift == f64::NEG_INFINITY {
+                shift
+            } else {
+                shift + ((accumulated - shift).exp() + (value - shift).exp()).ln()
+            }
+        }
+    }
+}
+
+/// Evaluates an equation without ellipses by visiting every label assignment.
+fn reference(equation: &str, operands: &[&Tensor], semiring: EinsumSemiring) -> Result<Vec<f64>> {
+    let (inputs, output) = equation.split_once("->").unwrap();
+    let inputs = inputs
+        .split(',')
+        .map(|input| input.split_whitespace().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let output = output.split_whitespace().collect::<Vec<_>>();
+    let mut labels = Vec::<&str>::new();
+    let mut extents = Vec::<usize>::new();
+    for (input, operand) in inputs.iter().zip(operands) {
+        for (&label, &extent) in input.iter().zip(operand.dims()) {
+            match labels.iter().position(|&known| known == label) {
+                Some(position) if extents[position] == 1 => extents[position] = extent,
+                Some(_) => {}
+                None => {
+                    labels.push(label);
+                    extents.push(extent);
+                }
+            }
+        }
+    }
+    let position = |label: &str| labels.iter().position(|&known| known == label).unwrap();
+    let values = operands
+        .iter()
+        .map(|operand| {
+            operand
+                .to_dtype(DType::F64)?
+                .flatten_all()?
+                .to_vec1::<f64>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let identity = match semiring {
+        EinsumSemiring::SumProduct => 0.,
+        EinsumSemiring::MaxPlus | EinsumSemiring::LogSumExp => f64::NEG_INFINITY,
+        EinsumSemiring::MinPlus => f64::INFINITY,
+    };
+    let output_len = output
+        .iter()
+        .map(|&label| extents[position(label)])
+        .product();
+    let mut result = vec![identity; output_len];
+    let mut assignment = vec![0; labels.len()];
+    if extents.contains(&0) {
+        return Ok(result);
+    }
+    loop {
+        let mut value = match semiring {
+            EinsumSemiring::SumProduct => 1.,
+            _ => 0.,
+        };
+        for ((input, operand), values) in inputs.iter().zip(operands).zip(&values) {
+            let offset = input
+                .iter()
+                .zip(operand.dims())
+                .fold(0, |offset, (&label, &extent)| {
+                    offset * extent + assignment[position(label)].min(extent - 1)
+                });
+            value = match semiring {
+                EinsumSemiring::SumProduct => value * values[offset],
+                _ => value + values[offset],
+            };
+        }
+        let target = output.iter().fold(0, |offset, &label| {
+            offset * extents[position(label)] + assignment[position(label)]
+        });
+        result[target] = add(semiring, result[target], value);
+
+        let Some(axis) = (0..labels.len())
+            .rev()
+            .find(|&axis| assignment[axis] + 1 < extents[axis])
+        else {
+            return Ok(result);
+        };
+        assignment[axis] += 1;
+        assignment[axis + 1..].fill(0);
+    }
+}
+
+fn assert_matches(actual: &Tensor, expected: &[f64], context: &str) -> Result<()> {
+    let actual = actual
+        .to_dtype(DType::F64)?
+        .flatten_all()?
+        .to_vec1::<f64>()?;
+    assert_eq!(actual.len(), expected.len(), "{context}");
+    for (index, (&actual, &expected)) in actual.iter().zip(expected).enumerate() {
+        assert!(
+            actual == expected || (actual - expected).abs() <= 1e-9 * (1. + expected.abs()),
+            "{context}[{index}]: actual={actual}, expected={expected}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn semiring_contractions_match_the_naive_reference() -> Result<()> {
+    let cases: [(&str, &[&[usize]]); 10] = [
+        ("i j -> j", &[&[3, 4]]),
+        ("i i -> i", &[&[3, 3]]),
+        ("i i ->", &[&[4, 4]]),
+        ("b i, i j -> b j", &[&[2, 3], &[3, 4]]),
+        ("b i, b i -> b", &[&[1, 3], &[4, 3]]),
+        ("i, j -> j i", &[&[3], &[2]]),
+        ("a b c, c d -> d a", &[&[2, 3, 4], &[4, 5]]),
+        ("b i, i j, j k -> b k", &[&[2, 3], &[3, 4], &[4, 2]]),
+        ("i, i j, j k, k ->", &[&[3], &[3, 4], &[4, 5], &[5]]),
+        (
+            "b t s, s r, b t r, r q -> b q",
+            &[&[2, 3, 2], &[2, 3], &[2, 1, 3], &[3, 2]],
+        ),
+    ];
+    for (equation, shapes) in cases {
+        let tensors = shapes
+            .iter()
+            .enumerate()
+            .map(|(index, shape)| ramp(shape, index))
+            .collect::<Result<Vec<_>>>()?;
+        let operands = tensors.iter().collect::<Vec<_>>();
+        for semiring in SEMIRINGS {
+            let actual = in_semiring(equation, &operands, semiring)?;
+            assert_matches(
+                &actual,
+                &reference(equation, &operands, semiring)?,
+                &format!("{equation} {semiring:?}"),
+            )?;
+        }
+        let sum_product = in_semiring(equation, &operands, EinsumSemiring::SumProduct)?;
+        assert_matches(
+            &sum_product,
+            &reference(equation, &operands, EinsumSemiring::SumProduct)?,
+            equation,
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn macro_semirings_match_the_runtime_form() -> Result<()> {
+    let scores = ramp(&[2, 3], 0)?.to_dtype(DType::F32)?;
+    let transitions = ramp(&[3, 3], 1)?.to_dtype(DType::F32)?;
+    let emissions = ramp(&[3, 4], 2)?.to_dtype(DType::F32)?;
+    let operands = [&scores, &transitions, &emissions];
+
+    let viterbi = einsum!(
+        semiring = max_plus,
+        "b i, i j -> b j",
+        &scores,
+        &transitions
+    )?;
+    let expected = in_semiring("b i, i j -> b j", &operands[..2], EinsumSemiring::MaxPlus)?;
+    assert_eq!(viterbi.dtype(), DType::F32);
+    assert_eq!(viterbi.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+
+    let shortest = einsum!(
+        semiring = min_plus,
+        "b i, i j, j k -> b k",
+        &scores,
+        &transitions,
+        &emissions
+    )?;
+    let expected = in_semiring("b i, i j, j k -> b k", &operands, EinsumSemiring::MinPlus)?;
+    assert_eq!(shortest.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+
+    let forward = einsum!(semiring = log_sum_exp, numpy "bi,ij", &scores, &transitions)?;
+    let expected = in_semiring("b i, i j -> b j", &operands[..2], EinsumSemiring::LogSumExp)?;
+    assert_eq!(forward.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+
+    let batched = ramp(&[2, 5, 3], 3)?.to_dtype(DType::F32)?;
+    let ellipsis = einsum!(
+        semiring = max_plus,
+        ".. i, i j -> .. j",
+        &batched,
+        &transitions
+    )?;
+    let explicit = einsum!(
+        semiring = max_plus,
+        "a b i, i j -> a b j",
+        &batched,
+        &transitions
+    )?;
+    assert_eq!(ellipsis.to_vec3::<f32>()?, explicit.to_vec3::<f32>()?);
+
+    let ordinary = einsum!(
+        semiring = sum_product,
+        "b i, i j -> b j",
+        &scores,
+        &transitions
+    )?;
+    assert_eq!(
+        ordinary.to_vec2::<f32>()?,
+        einsum!("b i, i j -> b j", &scores, &transitions)?.to_vec2::<f32>()?
+    );
+    Ok(())
+}
+
+#[test]
+fn semirings_handle_identities_infinities_and_long_contractions() -> Result<()> {
+    let empty_left = Tensor::zeros((2, 0), DType::F32, &Device::Cpu)?;
+    let empty_right = Tensor::zeros((0, 3), DType::F32, &Device::Cpu)?;
+    let empty = [&empty_left, &empty_right];
+    for (semiring, identity) in [
+        (EinsumSemiring::MaxPlus, f32::NEG_INFINITY),
+        (EinsumSemiring::MinPlus, f32::INFINITY),
+        (EinsumSemiring::LogSumExp, f32::NEG_INFINITY),
+    ] {
+        let output = in_semiring("a i, i b -> a b", &empty, semiring)?;
+        assert_eq!(output.to_vec2::<f32>()?, [[identity; 3]; 2]);
+    }
+
+    // Log-sum-exp shifts by the maximum, and rows of -inf stay -inf
+    let large = Tensor::new(
+        &[[1000f64, 1000.], [f64::NEG_INFINITY, f64::NEG_INFINITY]],
+        &Device::Cpu,
+    )?;
+    let zeros = Tensor::zeros(2, DType::F64, &Device::Cpu)?;
+    let output = einsum!(semiring = log_sum_exp, "r i, i -> r", &large, &zeros)?;
+    assert_eq!(
+        output.to_vec1::<f64>()?,
+        [1000. + 2f64.ln(), f64::NEG_INFINITY]
+    );
+
+    // Large enough that K is contracted in several bounded chunks
+    let left = ramp(&[256, 40], 0)?.to_dtype(DType::F32)?;
+    let right = ramp(&[40, 256], 1)?.to_dtype(DType::F32)?;
+    let output = einsum!(semiring = max_plus, "m k, k n -> m n", &left, &right)?;
+    let direct = left
+        .unsqueeze(2)?
+        .broadcast_add(&right.unsqueeze(0)?)?
+        .max(1)?;
+    assert_eq!(output.to_vec2::<f32>()?, direct.to_vec2::<f32>()?);
+
+    let counts = Tensor::new(&[[1u32, 2], [3, 4]], &Device::Cpu)?;
+    let error = in_semiring(
+        "a b, b c -> a c",
+        &[&counts, &counts],
+        EinsumSemiring::MaxPlus,
+    )
+    .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("einsum MaxPlus semiring requires floating-point operands, received U32")
+    );
+    Ok(())
+}
+
+#[test]
+fn semiring_gradients_match_direct_candle() -> Result<()> {
+    let scores = Var::from_tensor(&ramp(&[2, 3], 0)?)?;
+    let transitions = Var::from_tensor(&ramp(&[3, 4], 1)?)?;
+    let weights = ramp(&[2, 4], 2)?;
+    let direct_sum = scores
+        .unsqueeze(2)?
+        .broadcast_add(&transitions.unsqueeze(0)?)?;
+
+    let viterbi = einsum!(
+        semiring = max_plus,
+        "b i, i j -> b j",
+        scores.as_tensor(),
+        transitions.as_tensor()
+    )?;
+    let direct = direct_sum.max(1)?;
+    let lse = einsum!(
+        semiring = log_sum_exp,
+        "b i, i j -> b j",
+        scores.as_tensor(),
+        transitions.as_tensor()
+    )?;
+    let direct_lse = direct_sum.log_sum_exp(1)?;
+    for (actual, expected) in [(viterbi, direct), (lse, direct_lse)] {
+        assert_matches(
+            &actual,
+            &expected.flatten_all()?.to_vec1::<f64>()?,
+            "forward",
+        )?;
+        let actual = (actual * &weights)?.sum_all()?.backward()?;
+        let expected = (expected * &weights)?.sum_all()?.backward()?;
+        for variable in [&scores, &transitions] {
+            let expected = expected
+                .get(variable.as_tensor())
+                .unwrap()
+                .flatten_all()?
+                .to_vec1::<f64>()?;
+            assert_matches(
+                actual.get(variable.as_tensor()).unwrap(),
+                &expected,
+                "gradient",
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{EinsumOptions, EinsumStrategy, einsum, einsum_with};
+
+fn searched(equation: &str, operands: &[&Tensor], strategy: EinsumStrategy) -> Result<Tensor> {
+    let options = EinsumOptions {
+        strategy,
+        ..EinsumOptions::default()
+    };
+    einsum_with(equation, operands, options)
+}
+
+const STRATEGIES: [EinsumStrategy; 5] = [
+    EinsumStrategy::Greedy,
+    EinsumStrategy::Optimal,
+    EinsumStrategy::BranchBound { width: 1 },
+    EinsumStrategy::BranchBound { width: 3 },
+    EinsumStrategy::DynamicProgramming {
+        max_intermediate_elements: None,
+    },
+];
+
+fn ramp(dims: &[usize], dtype: DType) -> Result<Tensor> {
+    let len = dims.iter().product::<usize>();
+    let values = (0..len).map(|index| (index % 5) as f64).collect::<Vec<_>>();
+    Tensor::from_vec(values, dims, &Device::Cpu)?.to_dtype(dtype)
+}
+
+#[test]
+fn every_strategy_matches_the_default_on_a_tensor_network() -> Result<()> {
+    // A ring of six tensors sharing bonds, with a batch axis on two of them
+    let equation = "z a b, b c, c d x, d e, z e f, f a -> z x";
+    let shapes: [&[usize]; 6] = [
+        &[2, 3, 4],
+        &[4, 5],
+        &[5, 6, 3],
+        &[6, 2],
+        &[2, 2, 7],
+        &[7, 3],
+    ];
+    for dtype in [DType::F32, DType::F64] {
+        let tensors = shapes
+            .iter()
+            .map(|shape| ramp(shape, dtype))
+            .collect::<Result<Vec<_>>>()?;
+        let operands = tensors.iter().collect::<Vec<_>>();
+        let expected = einsum(equation, &operands)?.to_dtype(DType::F64)?;
+        for strategy in STRATEGIES {
+            let actual = searched(equation, &operands, strategy)?;
+            assert_eq!(actual.dims(), &[2, 3], "{strategy:?}");
+            assert_eq!(
+                actual.to_dtype(DType::F64)?.to_vec2::<f64>()?,
+                expected.to_vec2::<f64>()?,
+                "{strategy:?}"
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn strategies_apply_to_ellipses_and_ignore_short_equations() -> Result<()> {
+    let left = ramp(&[2, 3, 4], DType::F32)?;
+    let middle = ramp(&[4, 5], DType::F32)?;
+    let right = ramp(&[5], DType::F32)?;
+    for strategy in STRATEGIES {
+        let actual = searched(
+            ".. i j, j k, k -> .. i",
+            &[&left, &middle, &right],
+            strategy,
+        )?;
+        let expected = einsum(".. i j, j k, k -> .. i", &[&left, &middle, &right])?;
+        assert_eq!(actual.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+
+        let product = searched("i j, j k -> i k", &[&left.get(0)?, &middle], strategy)?;
+        assert_eq!(product.dims(), &[3, 5]);
+        let trace = searched("i j -> j", &[&middle], strategy)?;
+        assert_eq!(trace.dims(), &[5]);
+    }
+    Ok(())
+}
+
+#[test]
+fn strategies_report_unsatisfiable_searches() -> Result<()> {
+    let a = Tensor::ones((30, 35), DType::F32, &Device::Cpu)?;
+    let b = Tensor::ones((35, 15), DType::F32, &Device::Cpu)?;
+    let c = Tensor::ones((15, 5), DType::F32, &Device::Cpu)?;
+    let d = Tensor::ones((5, 10), DType::F32, &Device::Cpu)?;
+    let operands = [&a, &b, &c, &d];
+
+    let capped = |max| {
+        searched(
+            "a b, b c, c d, d e -> a e",
+            &operands,
+            EinsumStrategy::DynamicProgramming {
+                max_intermediate_elements: Some(max),
+            },
+        )
+    };
+    // The final 30 x 10 result is exempt from the cap
+    assert_eq!(capped(175)?.dims(), &[30, 10]);
+    let error = capped(100).unwrap_err().to_string();
+    assert!(
+        error.contains("no contraction order whose intermediates stay within 100 elements"),
+        "{error}"
+    );
+
+    let error = searched(
+        "a b, b c, c d, d e -> a e",
+        &operands,
+        EinsumStrategy::BranchBound { width: 0 },
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("width must be at least 1"));
+
+    let error = searched("a b, b c -> a c", &operands, EinsumStrategy::Optimal).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("einsum equation has 2 inputs but received 4 operands")
+    );
+    Ok(())
+}
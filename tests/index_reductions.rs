@@ -0,0 +1,110 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{IndexOperation, Pattern, einops};
+
+#[test]
+fn argmax_returns_u32_class_indices() -> Result<()> {
+    let logits = Tensor::new(&[[0.1f32, 2., -1.], [3., 0., 1.]], &Device::Cpu)?;
+
+    let classes = einops!("b argmax(classes) -> b", &logits)?;
+    assert_eq!(classes.dtype(), DType::U32);
+    assert_eq!(classes.to_vec1::<u32>()?, [1, 0]);
+
+    let classes = einops!("b argmin(classes) -> b", &logits)?;
+    assert_eq!(classes.to_vec1::<u32>()?, [2, 1]);
+    Ok(())
+}
+
+#[test]
+fn grouped_axes_return_a_flat_index_in_written_order() -> Result<()> {
+    // The maximum of each batch sits at h = 0, w = 2
+    let mut values = vec![0f32; 2 * 2 * 3];
+    values[2] = 9.;
+    values[6 + 2] = 7.;
+    let input = Tensor::from_vec(values, (2, 2, 3), &Device::Cpu)?;
+
+    let flat = einops!("b argmax(h w) -> b", &input)?;
+    assert_eq!(flat.to_vec1::<u32>()?, [2, 2]);
+    let flat = einops!("b argmax(h) argmax(w) -> b", &input)?;
+    assert_eq!(flat.to_vec1::<u32>()?, [2, 2]);
+
+    // Written as `w h`, the group is flattened with `w` as the outer axis
+    let flat = einops!("b argmax(w h) -> b", &input.permute((0, 2, 1))?)?;
+    assert_eq!(flat.to_vec1::<u32>()?, [4, 4]);
+
+    // Non-adjacent axes are grouped as well
+    let flat = einops!("argmax(h) b argmax(w) -> b", &input.permute((1, 0, 2))?)?;
+    assert_eq!(flat.to_vec1::<u32>()?, [2, 2]);
+    Ok(())
+}
+
+#[test]
+fn index_reductions_compose_with_other_steps() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+
+    let indices = einops!("a argmax(b) c -> c a", &input)?;
+    assert_eq!(indices.dims(), &[4, 2]);
+    assert_eq!(indices.to_vec2::<u32>()?, [[2, 2], [2, 2], [2, 2], [2, 2]]);
+
+    let indices = einops!("(a1:2 a2) argmin(b) c -> a2 (a1 c)", &input)?;
+    assert_eq!(indices.dims(), &[1, 8]);
+
+    let indices = einops!("argmax(..) c -> c", &input)?;
+    assert_eq!(indices.to_vec1::<u32>()?, [5, 5, 5, 5]);
+
+    let list = [input.get(0)?, input.get(1)?];
+    let indices = einops!("argmax(n) b c -> b c", &list)?;
+    assert_eq!(indices.flatten_all()?.to_vec1::<u32>()?, [1; 12]);
+    Ok(())
+}
+
+#[test]
+fn keywords_without_a_call_remain_axis_names() -> Result<()> {
+    let input = Tensor::arange(0f32, 6f32, &Device::Cpu)?.reshape((2, 3))?;
+
+    assert_eq!(
+        einops!("argmax argmin -> argmin argmax", &input)?.dims(),
+        &[3, 2]
+    );
+    let runtime = Pattern::parse("argmax argmin -> argmin argmax")?.apply(&input, &[])?;
+    assert_eq!(runtime.dims(), &[3, 2]);
+    Ok(())
+}
+
+#[test]
+fn runtime_patterns_match_the_macro() -> Result<()> {
+    let input = Tensor::new(
+        &[[[4f32, 1.], [0., 8.]], [[2., 6.], [7., 3.]]],
+        &Device::Cpu,
+    )?;
+
+    let runtime = Pattern::parse("b argmax(h w) -> b")?.apply(&input, &[])?;
+    assert_eq!(runtime.dtype(), DType::U32);
+    assert_eq!(runtime.to_vec1::<u32>()?, [3, 2]);
+    let runtime = Pattern::parse("b argmin(w h) -> b")?.apply(&input.permute((0, 2, 1))?, &[])?;
+    assert_eq!(runtime.to_vec1::<u32>()?, [1, 0]);
+
+    for pattern in ["b argmax(h) sum(w) -> b", "b argmax(h) argmin(w) -> b"] {
+        let error = Pattern::parse(pattern).unwrap_err().to_string();
+        assert!(error.contains("cannot be combined"), "{pattern}: {error}");
+    }
+    Ok(())
+}
+
+#[test]
+fn backend_rejects_empty_and_invalid_groups() -> Result<()> {
+    let input = Tensor::zeros((2, 0, 3), DType::F32, &Device::Cpu)?;
+
+    let error = candle_einops::Backend::arg_reduce_axes(&input, &[1, 2], IndexOperation::ArgMax)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("empty group"), "{error}");
+    let error = candle_einops::Backend::arg_reduce_axes(&input, &[2, 2], IndexOperation::ArgMax)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("duplicate axis 2"), "{error}");
+    let error = candle_einops::Backend::arg_reduce_axes(&input, &[3], IndexOperation::ArgMin)
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("out of range"), "{error}");
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{Pattern, einops};
+
+fn values(tensor: &Tensor) -> Result<Vec<f32>> {
+    tensor.flatten_all()?.to_vec1::<f32>()
+}
+
+fn elements() -> Result<Vec<Tensor>> {
+    (0..3)
+        .map(|index| {
+            let start = (index * 8) as f32;
+            Tensor::arange(start, start + 8., &Device::Cpu)?.reshape((2, 4))
+        })
+        .collect()
+}
+
+macro_rules! assert_list_matches_stacked {
+    ($pattern:literal, $list:expr) => {
+        let stacked = Tensor::stack($list, 0)?;
+        let expected = einops!($pattern, &stacked)?;
+        let from_macro = einops!($pattern, $list)?;
+        let from_pattern = Pattern::parse($pattern)?.apply_list($list, &[])?;
+        for actual in [from_macro, from_pattern] {
+            assert_eq!(actual.dims(), expected.dims(), "{}", $pattern);
+            assert_eq!(values(&actual)?, values(&expected)?, "{}", $pattern);
+        }
+    };
+}
+
+#[test]
+fn lists_match_their_stacked_tensor() -> Result<()> {
+    let list = elements()?;
+
+    assert_list_matches_stacked!("n b c -> b (n c)", &list);
+    assert_list_matches_stacked!("n b c -> (n b) c", &list);
+    assert_list_matches_stacked!("n b c -> c n b", &list);
+    assert_list_matches_stacked!("n b c -> b c n", &list);
+    assert_list_matches_stacked!("n b (c1:2 c2) -> c2 b n c1", &list);
+    assert_list_matches_stacked!("sum(n) b c -> c b", &list);
+    assert_list_matches_stacked!("mean(n) b max(c) -> b", &list);
+    assert_list_matches_stacked!("prod(n) b c -> b c", &list);
+    assert_list_matches_stacked!("min(n b) c -> c", &list);
+    assert_list_matches_stacked!("n sum(b) c -> c n", &list);
+    assert_list_matches_stacked!("n .. -> .. n", &list);
+    assert_list_matches_stacked!("n b c -> n b copy:2 c", &list);
+    Ok(())
+}
+
+#[test]
+fn list_reductions_do_not_depend_on_element_reduction_order() -> Result<()> {
+    let list = [
+        Tensor::new(&[[1f32, 5.], [2., 0.]], &Device::Cpu)?,
+        Tensor::new(&[[4f32, 0.], [1., 1.]], &Device::Cpu)?,
+    ];
+
+    // Each element is summed over `b` before the maximum over the list
+    let output = einops!("max(n) sum(b) c -> c", &list)?;
+
+    assert_eq!(output.to_vec1::<f32>()?, [5., 5.]);
+    Ok(())
+}
+
+#[test]
+fn owned_vectors_arrays_and_reference_lists_are_accepted() -> Result<()> {
+    let list = elements()?;
+    let references = list.iter().collect::<Vec<_>>();
+
+    assert_eq!(einops!("n b c -> b n c", &references)?.dims(), &[2, 3, 4]);
+    let array = [list[0].clone(), list[1].clone()];
+    assert_eq!(einops!("n b c -> b (n c)", array)?.dims(), &[2, 8]);
+    assert_eq!(
+        einops!("n b c -> c b n", list.as_slice())?.dims(),
+        &[4, 2, 3]
+    );
+    assert_eq!(einops!("n b c -> (n b) c", list)?.dims(), &[6, 4]);
+    Ok(())
+}
+
+#[test]
+fn mismatched_elements_return_candle_errors() -> Result<()> {
+    let left = Tensor::zeros((2, 3), DType::F32, &Device::Cpu)?;
+    let right = Tensor::zeros((3, 2), DType::F32, &Device::Cpu)?;
+    let integer = Tensor::zeros((2, 3), DType::U32, &Device::Cpu)?;
+
+    let error = einops!("n b c -> b n c", &[&left, &right]).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("element 1 has shape [3, 2], expected [2, 3]"),
+        "{error}"
+    );
+    let error = einops!("n b c -> b n c", &[&left, &integer]).unwrap_err();
+    assert!(error.to_string().contains("different dtypes"), "{error}");
+    let empty: Vec<Tensor> = Vec::new();
+    let error = Pattern::parse("n b c -> b n c")?
+        .apply_list(&empty, &[])
+        .unwrap_err();
+    assert!(error.to_string().contains("at least one tensor"), "{error}");
+    let error = einops!("n b -> b n", &[&left, &left]).unwrap_err();
+    assert!(
+        error.to_string().contains("does not match rank 3"),
+        "{error}"
+    );
+    Ok(())
+}
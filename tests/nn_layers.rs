@@ -0,0 +1,262 @@
+#![cfg(feature = "nn")]
+
+use std::collections::HashMap;
+
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::nn::{EinMix, Rearrange, RearrangeBackend, Reduce};
+use candle_einops::{einops, einsum};
+use candle_nn::{Module, VarBuilder, VarMap};
+
+fn assert_same(actual: &Tensor, expected: &Tensor) -> Result<()> {
+    assert_eq!(actual.dims(), expected.dims());
+    assert_eq!(
+        actual.flatten_all()?.to_vec1::<f32>()?,
+        expected.flatten_all()?.to_vec1::<f32>()?
+    );
+    Ok(())
+}
+
+fn error<T: std::fmt::Debug>(result: Result<T>) -> String {
+    result.unwrap_err().to_string()
+}
+
+#[test]
+fn layers_match_macro_expansions() -> Result<()> {
+    let input = Tensor::arange(0f32, 96f32, &Device::Cpu)?.reshape((2, 3, 4, 4))?;
+
+    let layer = Rearrange::new("b c (h {p}) w -> b h (p w c)", &[("p", 2)])?;
+    assert_same(
+        &layer.forward(&input)?,
+        &einops!("b c (h p:2) w -> b h (p w c)", &input)?,
+    )?;
+
+    let layer = Reduce::new("b c (h max(2)) (w max(2)) -> b h w c", &[])?;
+    assert_same(
+        &layer.forward(&input)?,
+        &einops!("b c (h max(2)) (w max(2)) -> b h w c", &input)?,
+    )?;
+
+    let layer = Reduce::new("b c mean(h w) -> b c", &[])?;
+    assert_same(
+        &layer.forward(&input)?,
+        &einops!("b c mean(h w) -> b c", &input)?,
+    )?;
+    Ok(())
+}
+
+#[test]
+fn layers_compose_in_sequential_models() -> Result<()> {
+    let input = Tensor::arange(0f32, 96f32, &Device::Cpu)?.reshape((2, 3, 4, 4))?;
+    let model = candle_nn::seq()
+        .add(Rearrange::new("b c h w -> b (h w) c", &[])?)
+        .add(Reduce::new("b sum(n) c -> c b", &[])?);
+
+    let output = model.forward(&input)?;
+    assert_same(&output, &einops!("b c sum(h w) -> c b", &input)?)?;
+    Ok(())
+}
+
+#[test]
+fn layers_reject_unsupported_patterns() {
+    assert!(error(Rearrange::new("a sum(b) -> a", &[])).contains("cannot reduce axes"));
+    assert!(error(Rearrange::new("a -> a {n}", &[("n", 2)])).contains("cannot repeat axes"));
+    assert!(error(Reduce::new("a max(b) -> a copy:2", &[])).contains("cannot repeat axes"));
+    assert!(
+        error(Rearrange::new("(k:2 a) -> k* a", &[]))
+            .contains("splits its output, which a layer cannot return")
+    );
+    assert!(error(Rearrange::new("a b -> a", &[])).contains("Axes missing on the right: b"));
+}
+
+#[test]
+fn layers_report_tensor_errors_when_applied() -> Result<()> {
+    let layer = Rearrange::new("b (h {p}) -> b h p", &[("p", 3)])?;
+    assert_eq!(layer.to_string(), "Rearrange(b (h {p}) -> b h p)");
+
+    let input = Tensor::zeros((2, 4), DType::F32, &Device::Cpu)?;
+    assert!(layer.forward(&input).is_err());
+    Ok(())
+}
+
+fn ramp(dims: &[usize], scale: f32) -> Result<Tensor> {
+    let len = dims.iter().product::<usize>();
+    (Tensor::arange(0f32, len as f32, &Device::Cpu)? * scale as f64)?.reshape(dims)
+}
+
+#[test]
+fn einmix_contracts_with_loaded_parameters() -> Result<()> {
+    let weight = ramp(&[3, 2, 4], 0.1)?;
+    let bias = ramp(&[1, 1, 2, 4], 1.)?;
+    let vb = VarBuilder::from_tensors(
+        HashMap::from([
+            ("mix.weight".to_string(), weight.clone()),
+            ("mix.bias".to_string(), bias.clone()),
+        ]),
+        DType::F32,
+        &Device::Cpu,
+    );
+    let layer = EinMix::new(
+        "b t c -> b t (h d)",
+        "c h d",
+        Some("h d"),
+        &[("c", 3), ("h", 2), ("d", 4)],
+        vb.pp("mix"),
+    )?;
+    assert_eq!(layer.bias().map(Tensor::dims), Some(&[1, 1, 2, 4][..]));
+
+    let input = ramp(&[2, 5, 3], 1.)?;
+    let mixed = einsum!("b t c, c h d -> b t h d", &input, &weight)?.broadcast_add(&bias)?;
+    assert_same(
+        &layer.forward(&input)?,
+        &einops!("b t h d -> b t (h d)", &mixed)?,
+    )?;
+    Ok(())
+}
+
+#[test]
+fn einmix_decomposes_the_input() -> Result<()> {
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+    let layer = EinMix::new(
+        "(b t) c -> b t2 c",
+        "t t2",
+        None,
+        &[("t", 4), ("t2", 6)],
+        vb,
+    )?;
+    assert_eq!(layer.weight().dims(), &[4, 6]);
+    assert!(layer.bias().is_none());
+
+    let input = ramp(&[8, 3], 1.)?;
+    let expected = einsum!(
+        "b t c, t t2 -> b t2 c",
+        &input.reshape((2, 4, 3))?,
+        layer.weight()
+    )?;
+    assert_same(&layer.forward(&input)?, &expected)?;
+    Ok(())
+}
+
+#[test]
+fn einmix_initializes_new_parameters_by_fan_in() -> Result<()> {
+    let varmap = VarMap::new();
+    let vb = VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+    let layer = EinMix::new("b c -> b h", "c h", Some("h"), &[("c", 12), ("h", 16)], vb)?;
+
+    let bound = |tensor: &Tensor| -> Result<f32> {
+        tensor.abs()?.flatten_all()?.max(0)?.to_scalar::<f32>()
+    };
+    assert!(bound(layer.weight())? <= 0.5);
+    assert!(bound(layer.bias().unwrap())? <= (1f32 / 12.).sqrt());
+    assert_eq!(varmap.all_vars().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn einmix_rejects_inconsistent_shapes() {
+    let build = |pattern: &str, weight: &str, bias: Option<&str>| {
+        let vb = VarBuilder::zeros(DType::F32, &Device::Cpu);
+        error(EinMix::new(
+            pattern,
+            weight,
+            bias,
+            &[("c", 2), ("h", 3)],
+            vb,
+        ))
+    };
+    assert!(
+        build("b c -> b h d", "c h", None)
+            .contains("Axes on the right are not in the input or weight: d")
+    );
+    assert!(build("b c -> b c", "c h", None).contains("Weight axes are not in the pattern: h"));
+    assert!(build("b c -> b h", "c h", Some("c")).contains("Bias axes are not in the output: c"));
+    assert!(build("b c -> b h", "(c h)", None).contains("cannot contain groups"));
+    assert!(build("b c c -> b h", "c h", None).contains("appears more than once on the left"));
+    assert!(build("b c -> b h", "c h", Some("b")).contains("Sizes are required for bias axes: b"));
+    assert!(build("b .. -> b h", "c h", None).contains("Only identifiers"));
+
+    let vb = VarBuilder::zeros(DType::F32, &Device::Cpu);
+    let missing = error(EinMix::new("b c -> b h", "c h", None, &[("h", 3)], vb));
+    assert!(
+        missing.contains("Sizes are required for weight axes: c"),
+        "{missing}"
+    );
+}
+
+fn checkpoint(tensors: &[(&str, &Tensor)]) -> VarBuilder<'static> {
+    let tensors = tensors
+        .iter()
+        .map(|(name, tensor)| (name.to_string(), (*tensor).clone()))
+        .collect();
+    VarBuilder::from_tensors(tensors, DType::F32, &Device::Cpu)
+}
+
+#[test]
+fn rearrange_backend_converts_matching_tensors() -> Result<()> {
+    let qkv = ramp(&[3 * 2 * 4, 5], 1.)?;
+    let bias = ramp(&[5], 1.)?;
+    let vb = RearrangeBackend::new(checkpoint(&[
+        ("blocks.0.qkv.weight", &qkv),
+        ("blocks.0.qkv.bias", &bias),
+    ]))
+    .with_rule(
+        "blocks.*.qkv.weight",
+        "({three} {h} d) c -> c h three d",
+        &[("three", 3), ("h", 2)],
+    )?
+    .into_var_builder();
+
+    let weight = vb.pp("blocks.0.qkv").get((5, 2, 3, 4), "weight")?;
+    assert_same(&weight, &einops!("(three:3 h:2 d) c -> c h three d", &qkv)?)?;
+    // The conversion is a strided view of the stored tensor
+    assert!(!weight.is_contiguous());
+
+    let unchecked = vb.get_unchecked("blocks.0.qkv.weight")?;
+    assert_eq!(unchecked.dims(), &[5, 2, 3, 4]);
+    assert_same(&vb.pp("blocks.0.qkv").get(5, "bias")?, &bias)?;
+    Ok(())
+}
+
+#[test]
+fn rearrange_backend_applies_the_first_matching_rule() -> Result<()> {
+    let stored = ramp(&[2, 3], 1.)?;
+    let vb = RearrangeBackend::new(checkpoint(&[("encoder.proj", &stored)]).pp("encoder"))
+        .with_rule("proj", "a b -> b a", &[])?
+        .with_rule("*", "a b -> (a b)", &[])?
+        .into_var_builder();
+
+    assert_same(&vb.get((3, 2), "proj")?, &stored.t()?)?;
+    assert!(vb.contains_tensor("proj"));
+    assert!(!vb.contains_tensor("encoder.proj"));
+    Ok(())
+}
+
+#[test]
+fn rearrange_backend_reports_layout_mismatches() -> Result<()> {
+    let stored = ramp(&[6, 4], 1.)?;
+    let vb = RearrangeBackend::new(checkpoint(&[("w", &stored)]))
+        .with_rule("w", "(h {d}) c -> c h d", &[("d", 3)])?
+        .into_var_builder();
+
+    let mismatch = error(vb.get((4, 3, 2), "w"));
+    assert!(mismatch.contains("shape mismatch for w"), "{mismatch}");
+    assert!(error(vb.get((4, 2), "missing")).contains("missing"));
+    assert!(
+        error(RearrangeBackend::new(vb).with_rule("*", "a b -> a", &[]))
+            .contains("Axes missing on the right: b")
+    );
+    Ok(())
+}
+
+#[test]
+fn rearrange_backend_creates_missing_variables_in_the_requested_layout() -> Result<()> {
+    let varmap = VarMap::new();
+    let vb = RearrangeBackend::new(VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu))
+        .with_rule("*.weight", "o i -> i o", &[])?
+        .into_var_builder();
+
+    let weight = vb.get_with_hints((3, 4), "linear.weight", candle_nn::Init::Const(1.))?;
+    assert_eq!(weight.dims(), &[3, 4]);
+    assert_eq!(varmap.all_vars().len(), 1);
+    Ok(())
+}
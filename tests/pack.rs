@@ -0,0 +1,87 @@
+use candle_core::{DType, Device, Result, Tensor};
+use candle_einops::{PackedShapes, pack, unpack};
+
+#[test]
+fn packs_heterogeneous_ranks_against_explicit_values() -> Result<()> {
+    let class_token = Tensor::new(&[[100u32, 101], [200, 201]], &Device::Cpu)?;
+    let patches = Tensor::arange(0u32, 12, &Device::Cpu)?.reshape((2, 1, 3, 2))?;
+
+    let (packed, shapes) = pack(&[&class_token, &patches], "b .. c")?;
+
+    assert_eq!(packed.dims(), &[2, 4, 2]);
+    assert_eq!(
+        packed.to_vec3::<u32>()?,
+        &[
+            [[100, 101], [0, 1], [2, 3], [4, 5]],
+            [[200, 201], [6, 7], [8, 9], [10, 11]],
+        ]
+    );
+    assert_eq!(shapes.shapes(), &[vec![], vec![1, 3]]);
+    assert_eq!(shapes.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn unpack_restores_every_original_tensor() -> Result<()> {
+    let inputs = [
+        Tensor::arange(0f32, 6f32, &Device::Cpu)?.reshape((2, 3))?,
+        Tensor::arange(6f32, 30f32, &Device::Cpu)?.reshape((2, 4, 3))?,
+        Tensor::arange(30f32, 42f32, &Device::Cpu)?.reshape((2, 2, 1, 3))?,
+    ];
+
+    let (packed, shapes) = pack(&inputs, "* c")?;
+    assert_eq!(packed.dims(), &[2 + 8 + 4, 3]);
+    let restored = unpack(&packed, &shapes, "* c")?;
+
+    assert_eq!(restored.len(), inputs.len());
+    for (restored, input) in restored.iter().zip(&inputs) {
+        assert_eq!(restored.dims(), input.dims());
+        assert_eq!(
+            restored.flatten_all()?.to_vec1::<f32>()?,
+            input.flatten_all()?.to_vec1::<f32>()?
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn leading_and_zero_sized_captures_round_trip() -> Result<()> {
+    let empty = Tensor::zeros((0, 4), DType::F32, &Device::Cpu)?;
+    let vector = Tensor::ones(4, DType::F32, &Device::Cpu)?;
+
+    let (packed, shapes) = pack(&[&empty, &vector], ".. feature")?;
+    assert_eq!(packed.dims(), &[1, 4]);
+    assert_eq!(shapes, PackedShapes::new(vec![vec![0], vec![]]));
+
+    let restored = unpack(&packed, &shapes, ".. feature")?;
+    assert_eq!(restored[0].dims(), &[0, 4]);
+    assert_eq!(restored[1].dims(), &[4]);
+    Ok(())
+}
+
+#[test]
+fn mismatched_inputs_return_candle_errors() -> Result<()> {
+    let left = Tensor::zeros((2, 3), DType::F32, &Device::Cpu)?;
+    let right = Tensor::zeros((2, 4), DType::F32, &Device::Cpu)?;
+    let integer = Tensor::zeros((2, 3), DType::U32, &Device::Cpu)?;
+
+    let error = pack(&[&left, &right], "b .. c").unwrap_err().to_string();
+    assert!(
+        error.contains("axis `c` has length 4, expected 3"),
+        "{error}"
+    );
+    let error = pack(&[&left, &integer], "b .. c").unwrap_err().to_string();
+    assert!(error.contains("different dtypes"), "{error}");
+    let error = pack(&[&left], "a b .. c").unwrap_err().to_string();
+    assert!(error.contains("requires at least 3 axes"), "{error}");
+    let error = pack::<Tensor>(&[], "b .. c").unwrap_err().to_string();
+    assert!(error.contains("at least one tensor"), "{error}");
+    assert!(pack(&[&left], "b c").is_err());
+    assert!(pack(&[&left], "b .. .. c").is_err());
+
+    let (packed, _) = pack(&[&left], "b .. c")?;
+    let shapes = PackedShapes::new(vec![vec![2]]);
+    let error = unpack(&packed, &shapes, "b .. c").unwrap_err().to_string();
+    assert!(error.contains("describe 2 elements"), "{error}");
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use candle_core::{Device, Result, Tensor};
+use candle_einops::{Pattern, einops, rearrange, reduce, repeat};
+
+macro_rules! assert_matches_macro {
+    ($pattern:literal, $tensor:expr) => {
+        let expected = einops!($pattern, $tensor)?;
+        let actual = Pattern::parse($pattern)?.apply($tensor, &[])?;
+        assert_eq!(actual.dims(), expected.dims(), "{}", $pattern);
+        assert_eq!(
+            actual.flatten_all()?.to_vec1::<f32>()?,
+            expected.flatten_all()?.to_vec1::<f32>()?,
+            "{}",
+            $pattern
+        );
+    };
+}
+
+fn parse_error(pattern: &str) -> String {
+    Pattern::parse(pattern).unwrap_err().to_string()
+}
+
+#[test]
+fn runtime_patterns_match_macro_expansions() -> Result<()> {
+    let input = Tensor::arange(0f32, 48f32, &Device::Cpu)?.reshape((1, 2, 4, 6))?;
+
+    assert_matches_macro!("a b c d -> d c b a", &input);
+    assert_matches_macro!("a b c d -> (c d) (a b)", &input);
+    assert_matches_macro!("1 b c d -> b (d c)", &input);
+    assert_matches_macro!("a b (c1:2 c2) d -> a c2 b c1 d", &input);
+    assert_matches_macro!("a b c (d1 d2:3) -> (d2 a) b (c d1)", &input);
+    assert_matches_macro!("a b sum(c) d -> d a b", &input);
+    assert_matches_macro!("a mean(b c) d -> a d", &input);
+    assert_matches_macro!("a b (c max(2)) (d min(3)) -> a b c d", &input);
+    assert_matches_macro!("a b (prod(c1:2) c2) d -> d c2 b a", &input);
+    assert_matches_macro!("a .. d -> d .. a", &input);
+    assert_matches_macro!("a .. d -> (d ..) a", &input);
+    assert_matches_macro!("a b sum(..) -> b a", &input);
+    assert_matches_macro!("max(..) d -> d", &input);
+    assert_matches_macro!("a b c d -> a copy:2 b c d", &input);
+    assert_matches_macro!("a b c d -> (copy:3 d) c b a", &input);
+    assert_matches_macro!("1 .. -> 3 ..", &input);
+    assert_matches_macro!("1 b c d -> (1 1) (1 b) 2 c 3 d (1 1)", &input);
+    Ok(())
+}
+
+#[test]
+fn braced_sizes_are_supplied_at_application_time() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 12))?;
+    let (columns, copies) = (3, 2);
+
+    let expected = einops!("b (h {columns}) -> b h {copies} {columns}", &input)?;
+    let actual = Pattern::parse("b (h {columns}) -> b h {copies} {columns}")?
+        .apply(&input, &[("columns", columns), ("copies", copies)])?;
+
+    assert_eq!(actual.dims(), &[2, 4, 2, 3]);
+    assert_eq!(
+        actual.flatten_all()?.to_vec1::<f32>()?,
+        expected.flatten_all()?.to_vec1::<f32>()?
+    );
+
+    let error = Pattern::parse("b (h {columns}) -> b h {columns}")?
+        .apply(&input, &[])
+        .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("No size was provided for axis `columns`")
+    );
+    Ok(())
+}
+
+#[test]
+fn functions_restrict_pattern_kinds() -> Result<()> {
+    let input = Tensor::arange(0f32, 6f32, &Device::Cpu)?.reshape((2, 3))?;
+
+    assert_eq!(rearrange(&input, "h w -> w h 1", &[])?.dims(), &[3, 2, 1]);
+    assert_eq!(
+        reduce(&input, "h sum(w) -> h", &[])?.to_vec1::<f32>()?,
+        [3., 12.]
+    );
+    assert_eq!(
+        repeat(&input, "h w -> h {n} w", &[("n", 4)])?.dims(),
+        &[2, 4, 3]
+    );
+
+    let error = rearrange(&input, "h sum(w) -> h", &[]).unwrap_err();
+    assert!(error.to_string().contains("cannot reduce"));
+    let error = rearrange(&input, "h w -> h copy:2 w", &[]).unwrap_err();
+    assert!(error.to_string().contains("cannot repeat"));
+    let error = reduce(&input, "h w -> h copy:2 w", &[]).unwrap_err();
+    assert!(error.to_string().contains("cannot repeat"));
+    let error = repeat(&input, "h max(w) -> h", &[]).unwrap_err();
+    assert!(error.to_string().contains("cannot reduce"));
+    Ok(())
+}
+
+#[test]
+fn malformed_patterns_report_macro_diagnostics() {
+    let cases = [
+        ("a b -> a", "Axes missing on the right: b"),
+        ("a b c -> a", "Axes missing on the right: b, c"),
+        ("a a -> a", "Axis `a` appears more than once on the left"),
+        (
+            ".. sum(..) -> ..",
+            "Ellipsis `..` appears more than once on the left",
+        ),
+        ("a -> a a", "Axis `a` appears more than once on the right"),
+        ("a -> a b", "New axis `b` requires an explicit size"),
+        (
+            "a -> a ..",
+            "Ellipsis `..` must appear on both sides of the expression",
+        ),
+        (
+            "a sum(b:2) -> a",
+            "Axis sizes are not allowed in top-level reductions",
+        ),
+        ("() a -> a", "Decomposition groups cannot be empty"),
+        ("a -> () a", "Composition groups cannot be empty"),
+        ("sum() a -> a", "Reduction groups cannot be empty"),
+        (
+            "a (b c) -> a b c",
+            "Shape information required to complete decomposition",
+        ),
+        (
+            "a -> a:2",
+            "Axis `a` cannot be assigned a size on the right",
+        ),
+        ("2 a -> a", "Literal Int 2 not allowed on the left side"),
+        ("a b", "expected `->`"),
+    ];
+    for (pattern, message) in cases {
+        let error = parse_error(pattern);
+        assert!(error.contains(message), "{pattern}: {error}");
+    }
+}
+
+#[test]
+fn runtime_shape_failures_are_candle_errors() -> Result<()> {
+    let input = Tensor::arange(0f32, 5f32, &Device::Cpu)?;
+
+    let error = Pattern::parse("(rows:2 columns) -> rows columns")?
+        .apply(&input, &[])
+        .unwrap_err();
+    assert!(error.to_string().contains("not divisible"));
+
+    let error = Pattern::parse("a b -> b a")?
+        .apply(&input, &[])
+        .unwrap_err();
+    assert!(error.to_string().contains("requires at least 2 axes"));
+    Ok(())
+}
+
+#[test]
+fn patterns_round_trip_through_display() -> Result<()> {
+    let pattern: Pattern = "b (h w:2) -> b h w".parse()?;
+
+    assert_eq!(pattern.to_string(), "b (h w:2) -> b h w");
+    assert!(!pattern.reduces());
+    assert!(!pattern.repeats());
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use candle_core::{Device, Result, Tensor};
+use candle_einops::{Pattern, einops};
+
+fn values(tensor: &Tensor) -> Result<Vec<f32>> {
+    tensor.flatten_all()?.to_vec1::<f32>()
+}
+
+#[test]
+fn literal_split_axes_return_arrays_of_views() -> Result<()> {
+    let qkv = Tensor::arange(0f32, 48f32, &Device::Cpu)?.reshape((2, 2, 12))?;
+
+    let [q, k, v] = einops!("b n (three:3 h d:2) -> three * b h n d", &qkv)?;
+
+    let expected = einops!("b n (three:3 h d:2) -> three b h n d", &qkv)?;
+    for (index, piece) in [&q, &k, &v].into_iter().enumerate() {
+        assert_eq!(piece.dims(), &[2, 2, 2, 2]);
+        assert_eq!(values(piece)?, values(&expected.get(index)?)?);
+    }
+    // Unbinding narrows the decomposed input instead of copying it
+    assert_eq!(k.layout().start_offset(), 4);
+    assert_eq!(v.layout().start_offset(), 8);
+    Ok(())
+}
+
+#[test]
+fn runtime_split_lengths_return_vectors() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 12))?;
+    let heads = 4;
+
+    let pieces = einops!("b (h {heads}) -> {heads} * b h", &input)?;
+    assert_eq!(pieces.len(), heads);
+    assert_eq!(pieces[1].to_vec2::<f32>()?, [[1., 5., 9.], [13., 17., 21.]]);
+
+    let rows = einops!("r c -> r * c", &input)?;
+    assert_eq!(rows.len(), 2);
+    assert_eq!(values(&rows[1])?, values(&input.get(1)?)?);
+
+    let copies = einops!("b c -> copy:2 * c b", &input)?;
+    assert_eq!(copies[0].dims(), &[12, 2]);
+    Ok(())
+}
+
+#[test]
+fn splits_compose_with_reductions_and_lists() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+
+    let [first, second] = einops!("(pair:2) sum(c) w -> pair * w", &input)?;
+    assert_eq!(first.to_vec1::<f32>()?, [12., 15., 18., 21.]);
+    assert_eq!(second.to_vec1::<f32>()?, [48., 51., 54., 57.]);
+
+    let list = [input.get(0)?, input.get(1)?];
+    let pieces = einops!("n c w -> w * n c", &list)?;
+    assert_eq!(pieces.len(), 4);
+    assert_eq!(pieces[0].to_vec2::<f32>()?, [[0., 4., 8.], [12., 16., 20.]]);
+    Ok(())
+}
+
+#[test]
+fn runtime_patterns_split_with_apply_split() -> Result<()> {
+    let qkv = Tensor::arange(0f32, 48f32, &Device::Cpu)?.reshape((2, 2, 12))?;
+    let pattern = Pattern::parse("b n (three:3 h d:2) -> three * b h n d")?;
+    assert!(pattern.splits());
+
+    let pieces = pattern.apply_split(&qkv, &[])?;
+    let [q, k, v] = einops!("b n (three:3 h d:2) -> three * b h n d", &qkv)?;
+    for (actual, expected) in pieces.iter().zip([q, k, v]) {
+        assert_eq!(values(actual)?, values(&expected)?);
+    }
+
+    let error = pattern.apply(&qkv, &[]).unwrap_err();
+    assert!(error.to_string().contains("use `Pattern::apply_split`"));
+    let error = Pattern::parse("b n c -> b n c")?
+        .apply_split(&qkv, &[])
+        .unwrap_err();
+    assert!(error.to_string().contains("has no split marker"));
+    for (pattern, message) in [
+        ("a b -> a b * c:2", "must follow the first output axis"),
+        ("a b -> a * b *", "may appear only once"),
+        ("a b -> (a b) * b", "must follow the first output axis"),
+    ] {
+        let error = Pattern::parse(pattern).unwrap_err().to_string();
+        assert!(error.contains(message), "{pattern}: {error}");
+    }
+    Ok(())
+}
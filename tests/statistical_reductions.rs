@@ -0,0 +1,105 @@
+use candle_core::{Device, Result, Tensor};
+use candle_einops::{Pattern, einops};
+
+fn assert_close(actual: &Tensor, expected: &[f32], tolerance: f32) -> Result<()> {
+    let actual = actual.flatten_all()?.to_vec1::<f32>()?;
+    assert_eq!(actual.len(), expected.len());
+    for (index, (&actual, &expected)) in actual.iter().zip(expected).enumerate() {
+        assert!(
+            actual == expected || (actual - expected).abs() <= tolerance * expected.abs().max(1.),
+            "value {index} differs: {actual} vs {expected}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn statistics_match_direct_formulas() -> Result<()> {
+    let input = Tensor::new(
+        &[[[1f32, 2.], [3., 4.]], [[0., -1.], [2., 5.]]],
+        &Device::Cpu,
+    )?;
+
+    let logsumexp = einops!("b logsumexp(h w) -> b", &input)?;
+    let expected = [
+        (1f32.exp() + 2f32.exp() + 3f32.exp() + 4f32.exp()).ln(),
+        (0f32.exp() + (-1f32).exp() + 2f32.exp() + 5f32.exp()).ln(),
+    ];
+    assert_close(&logsumexp, &expected, 1e-6)?;
+
+    assert_close(&einops!("b var(h w) -> b", &input)?, &[1.25, 5.25], 1e-6)?;
+    assert_close(
+        &einops!("b std(h w) -> b", &input)?,
+        &[1.25f32.sqrt(), 5.25f32.sqrt()],
+        1e-6,
+    )?;
+    assert_close(
+        &einops!("b h norm(w) -> b h", &input)?,
+        &[5f32.sqrt(), 5., 1., 29f32.sqrt()],
+        1e-6,
+    )?;
+    Ok(())
+}
+
+#[test]
+fn non_adjacent_axes_reduce_jointly() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+
+    let split = einops!("var(a) b var(c) -> b", &input)?;
+    let joint = einops!("b var(a c) -> b", &input.permute((1, 0, 2))?)?;
+
+    assert_close(&split, &joint.to_vec1::<f32>()?, 1e-6)?;
+    assert_close(&split, &[37.25, 37.25, 37.25], 1e-6)?;
+    Ok(())
+}
+
+#[test]
+fn reductions_are_numerically_stable() -> Result<()> {
+    let large = Tensor::new(&[[1000f32, 1000.], [-1000., -1000.]], &Device::Cpu)?;
+    let logsumexp = einops!("r logsumexp(c) -> r", &large)?;
+    assert_close(&logsumexp, &[1000. + 2f32.ln(), -1000. + 2f32.ln()], 1e-6)?;
+
+    let infinite = Tensor::new(&[[f32::NEG_INFINITY, f32::NEG_INFINITY]], &Device::Cpu)?;
+    let logsumexp = einops!("r logsumexp(c) -> r", &infinite)?;
+    assert_eq!(logsumexp.to_vec1::<f32>()?, [f32::NEG_INFINITY]);
+
+    // A one-pass `E[x²] - E[x]²` loses every significant digit here in f32
+    let offset = Tensor::new(&[10_000f32 + 1., 10_000. + 2., 10_000. + 3.], &Device::Cpu)?;
+    let variance = einops!("var(n) ->", &offset)?;
+    assert_close(&variance, &[2. / 3.], 1e-4)?;
+    Ok(())
+}
+
+#[test]
+fn keywords_without_a_call_remain_axis_names() -> Result<()> {
+    let input = Tensor::arange(0f32, 6f32, &Device::Cpu)?.reshape((2, 3))?;
+
+    assert_eq!(einops!("var norm -> norm var", &input)?.dims(), &[3, 2]);
+    let runtime = Pattern::parse("std norm -> norm std")?.apply(&input, &[])?;
+    assert_eq!(runtime.dims(), &[3, 2]);
+    Ok(())
+}
+
+#[test]
+fn runtime_patterns_and_lists_support_statistics() -> Result<()> {
+    let input = Tensor::arange(0f32, 24f32, &Device::Cpu)?.reshape((2, 3, 4))?;
+
+    for (pattern, dims) in [
+        ("a logsumexp(b) c -> c a", &[4, 2][..]),
+        ("a var(b c) -> a", &[2]),
+        ("std(..) c -> c", &[4]),
+        ("a b norm(c) -> b a", &[3, 2]),
+    ] {
+        let runtime = Pattern::parse(pattern)?.apply(&input, &[])?;
+        assert_eq!(runtime.dims(), dims, "{pattern}");
+    }
+    let runtime = Pattern::parse("a var(b c) -> a")?.apply(&input, &[])?;
+    let expected = einops!("a var(b c) -> a", &input)?;
+    assert_close(&runtime, &expected.to_vec1::<f32>()?, 1e-6)?;
+
+    let list = [input.get(0)?, input.get(1)?];
+    let from_list = einops!("var(n) b c -> b c", &list)?;
+    let stacked = einops!("var(n) b c -> b c", &input)?;
+    assert_close(&from_list, &stacked.flatten_all()?.to_vec1::<f32>()?, 1e-6)?;
+    Ok(())
+}